@@ -0,0 +1,126 @@
+// compares the batched hit counter writes with the previous design, which issued one
+// UPDATE per clip directly against the pool
+//
+// run with: cargo bench --bench hitcounter
+use std::path::Path;
+use std::time::Duration;
+
+use clipstash::data::{AppDatabase, DatabasePool};
+use clipstash::domain::audit::Actor;
+use clipstash::domain::clip::field::{
+    Content, Expires, ForkedFrom, Owner, Password, Tags, Title, Visibility,
+};
+use clipstash::domain::secret::SecretScanner;
+use clipstash::service::{action, ask};
+use clipstash::web::hitcounter::{HitCounter, HitCounterConfig};
+use clipstash::ShortCode;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tokio::runtime::Runtime;
+
+fn setup(rt: &Runtime, clips: usize) -> (AppDatabase, Vec<ShortCode>) {
+    rt.block_on(async move {
+        let db = AppDatabase::new(":memory:").await;
+        sqlx::migrate::Migrator::new(Path::new("./migrations"))
+            .await
+            .expect("failed to load migrations")
+            .run(db.get_pool())
+            .await
+            .expect("failed to run migrations");
+
+        let mut shortcodes = Vec::with_capacity(clips);
+        for i in 0..clips {
+            let req = ask::NewClip {
+                content: Content::new(&format!("clip {}", i)).unwrap(),
+                title: Title::default(),
+                expires: Expires::default(),
+                password: Password::default(),
+                owner: Owner::default(),
+                visibility: Visibility::default(),
+                tags: Tags::default(),
+                forked_from: ForkedFrom::default(),
+                allow_secrets: false,
+            };
+            let clip = action::new_clip(
+                req,
+                &Actor::default(),
+                &SecretScanner::default(),
+                Default::default(),
+                db.get_pool(),
+            )
+            .await
+            .unwrap();
+            shortcodes.push(clip.shortcode);
+        }
+
+        (db, shortcodes)
+    })
+}
+
+// the previous commit path: a transaction was opened but every update ran against the pool
+async fn legacy_commit(hits: &[(ShortCode, u32)], pool: &DatabasePool) {
+    let transaction = action::begin_transaction(pool).await.unwrap();
+    for (shortcode, count) in hits {
+        sqlx::query("UPDATE clips SET hits = hits + ? WHERE shortcode = ?")
+            .bind(count)
+            .bind(shortcode.as_str())
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+    action::end_transaction(transaction).await.unwrap();
+}
+
+fn commit(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("commit");
+
+    for clips in [10, 100, 500] {
+        let (db, shortcodes) = setup(&rt, clips);
+        let hits: Vec<(ShortCode, u32)> = shortcodes.into_iter().map(|s| (s, 1)).collect();
+
+        group.throughput(Throughput::Elements(clips as u64));
+        group.bench_with_input(BenchmarkId::new("per_row", clips), &hits, |b, hits| {
+            b.iter(|| rt.block_on(legacy_commit(hits, db.get_pool())))
+        });
+        group.bench_with_input(BenchmarkId::new("batched", clips), &hits, |b, hits| {
+            b.iter(|| {
+                rt.block_on(action::increase_hit_counts(hits, db.get_pool()))
+                    .unwrap()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn enqueue(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let (db, shortcodes) = setup(&rt, 100);
+    let hit_counter = HitCounter::with_config(
+        db.get_pool().clone(),
+        rt.handle().clone(),
+        HitCounterConfig {
+            flush_interval: Duration::from_millis(50),
+            ..HitCounterConfig::default()
+        },
+    );
+
+    let mut group = c.benchmark_group("enqueue");
+    group.throughput(Throughput::Elements(10_000));
+    group.bench_function("hit_then_flush", |b| {
+        b.iter(|| {
+            rt.block_on(async {
+                for i in 0..10_000 {
+                    hit_counter
+                        .hit(shortcodes[i % shortcodes.len()].clone(), 1)
+                        .await;
+                }
+                hit_counter.flush().await;
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, commit, enqueue);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+-- the API key that created the clip, clips posted from the web have no owner
+ALTER TABLE clips ADD COLUMN owner BLOB;
+
+-- views aggregated per day, referrer host and user agent family
+CREATE TABLE
+  IF NOT EXISTS clip_stats (
+    shortcode TEXT NOT NULL REFERENCES clips (shortcode) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    referrer TEXT NOT NULL,
+    agent TEXT NOT NULL,
+    views BIGINT NOT NULL,
+    PRIMARY KEY (shortcode, day, referrer, agent)
+  );
+
+-- hashed visitor ids used to count unique visitors per day
+CREATE TABLE
+  IF NOT EXISTS clip_visitors (
+    shortcode TEXT NOT NULL REFERENCES clips (shortcode) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    visitor TEXT NOT NULL,
+    PRIMARY KEY (shortcode, day, visitor)
+  );
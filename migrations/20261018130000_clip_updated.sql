@@ -0,0 +1,4 @@
+-- the last time the clip was changed, served as `Last-Modified`
+ALTER TABLE clips ADD COLUMN updated DATETIME;
+
+UPDATE clips SET updated = posted;
@@ -0,0 +1,2 @@
+-- content is stored compressed when `content_encoding` isn't `identity`
+ALTER TABLE clips ADD COLUMN content_encoding TEXT NOT NULL DEFAULT 'identity';
@@ -0,0 +1,15 @@
+-- content is stored once per distinct SHA-256 and shared by every clip that references it.
+-- `content` of clips stored before keeps their content until `httpd --migrate-content` moves it
+CREATE TABLE
+  IF NOT EXISTS blobs (
+    hash TEXT PRIMARY KEY NOT NULL,
+    content BLOB NOT NULL,
+    content_encoding TEXT NOT NULL DEFAULT 'identity',
+    refs BIGINT NOT NULL
+  );
+
+CREATE INDEX IF NOT EXISTS blobs_unreferenced ON blobs (refs) WHERE refs <= 0;
+
+ALTER TABLE clips ADD COLUMN content_hash TEXT REFERENCES blobs (hash);
+
+CREATE INDEX IF NOT EXISTS clips_content_hash ON clips (content_hash);
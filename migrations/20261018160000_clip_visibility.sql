@@ -0,0 +1,5 @@
+-- `public` clips are listed, `unlisted` clips are only reachable with their link and `private`
+-- clips only by their owner. Every existing clip was effectively unlisted
+ALTER TABLE clips ADD COLUMN visibility TEXT NOT NULL DEFAULT 'unlisted';
+
+CREATE INDEX IF NOT EXISTS clips_public ON clips (posted) WHERE visibility = 'public';
@@ -0,0 +1,9 @@
+-- tags of a clip, a clip has many tags and a tag is shared by many clips
+CREATE TABLE
+  IF NOT EXISTS clip_tags (
+    shortcode TEXT NOT NULL REFERENCES clips (shortcode) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (shortcode, tag)
+  );
+
+CREATE INDEX IF NOT EXISTS clip_tags_tag ON clip_tags (tag);
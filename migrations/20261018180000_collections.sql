@@ -0,0 +1,22 @@
+-- collections bundle clips under their own shortcode, clips can be part of many collections
+CREATE TABLE
+  IF NOT EXISTS collections (
+    collection_id TEXT PRIMARY KEY NOT NULL,
+    shortcode TEXT UNIQUE NOT NULL,
+    title TEXT,
+    posted DATETIME NOT NULL,
+    expires DATETIME,
+    password TEXT,
+    owner BLOB
+  );
+
+-- the clips of a collection ordered by `position`
+CREATE TABLE
+  IF NOT EXISTS collection_clips (
+    collection TEXT NOT NULL REFERENCES collections (shortcode) ON DELETE CASCADE,
+    shortcode TEXT NOT NULL REFERENCES clips (shortcode) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    PRIMARY KEY (collection, shortcode)
+  );
+
+CREATE INDEX IF NOT EXISTS collection_clips_shortcode ON collection_clips (shortcode);
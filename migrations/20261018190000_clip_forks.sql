@@ -0,0 +1,4 @@
+-- the clip a clip was forked from, forks outlive their original and just lose the link
+ALTER TABLE clips ADD COLUMN forked_from TEXT REFERENCES clips (shortcode) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS clips_forked_from ON clips (forked_from);
@@ -0,0 +1,7 @@
+-- keys disabled by an admin are rejected like unknown keys, the clips they created are kept
+ALTER TABLE api_keys ADD COLUMN disabled DATETIME;
+
+-- keys generated before the column existed have no creation time
+ALTER TABLE api_keys ADD COLUMN created DATETIME;
+
+CREATE INDEX IF NOT EXISTS clips_owner ON clips (owner);
@@ -0,0 +1,29 @@
+-- reports of abusive clips filed by visitors, open until an admin resolves them
+CREATE TABLE
+  IF NOT EXISTS reports (
+    report_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    shortcode TEXT NOT NULL REFERENCES clips (shortcode) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    contact TEXT,
+    filed DATETIME NOT NULL,
+    resolved DATETIME
+  );
+
+CREATE INDEX IF NOT EXISTS reports_open ON reports (filed) WHERE resolved IS NULL;
+
+-- clips taken down by an admin keep their content but are not served anymore. `takedown` is
+-- `abuse` or `legal`, NULL while the clip is served
+ALTER TABLE clips ADD COLUMN takedown TEXT;
+ALTER TABLE clips ADD COLUMN takedown_note TEXT;
+
+-- every moderation action of an admin, kept when the clip or key is deleted
+CREATE TABLE
+  IF NOT EXISTS moderation_log (
+    entry_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time DATETIME NOT NULL,
+    action TEXT NOT NULL,
+    shortcode TEXT,
+    report_id INTEGER,
+    key_id INTEGER,
+    note TEXT
+  );
@@ -0,0 +1,9 @@
+-- the size of the content in bytes, counted against the storage quota of the owner. Clips
+-- stored before are counted with the size of their stored content, which may be compressed
+ALTER TABLE clips ADD COLUMN size BIGINT NOT NULL DEFAULT 0;
+
+UPDATE clips SET size = COALESCE(
+    (SELECT LENGTH(b.content) FROM blobs b WHERE b.hash = clips.content_hash),
+    LENGTH(clips.content),
+    0
+);
@@ -0,0 +1,28 @@
+-- every write to clips and API keys and every unlock of a protected clip, with who made it.
+-- `key_id` is the API key the request was made with and `target_key_id` the key a key action
+-- is about, entries are kept when the clip or key is deleted
+CREATE TABLE
+  IF NOT EXISTS audit_log (
+    entry_id INTEGER PRIMARY KEY AUTOINCREMENT,
+    time DATETIME NOT NULL,
+    action TEXT NOT NULL,
+    shortcode TEXT,
+    key_id INTEGER,
+    admin BOOLEAN NOT NULL DEFAULT FALSE,
+    ip TEXT,
+    target_key_id INTEGER
+  );
+
+CREATE INDEX IF NOT EXISTS audit_log_shortcode ON audit_log (shortcode);
+CREATE INDEX IF NOT EXISTS audit_log_time ON audit_log (time);
+
+-- the log is append-only
+CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+BEGIN
+  SELECT RAISE(ABORT, 'the audit log is append-only');
+END;
@@ -0,0 +1,12 @@
+-- clips stored before the size was added were counted with the length of their compressed
+-- content. Their size is unknown (-1) until the server decompresses them once. Compressed
+-- clips stored since always have a size larger than their compressed content
+UPDATE clips SET size = -1
+WHERE COALESCE(
+        (SELECT b.content_encoding FROM blobs b WHERE b.hash = clips.content_hash),
+        clips.content_encoding
+    ) = 'zstd'
+    AND size = COALESCE(
+        (SELECT LENGTH(b.content) FROM blobs b WHERE b.hash = clips.content_hash),
+        LENGTH(clips.content)
+    );
@@ -1,11 +1,16 @@
 use std::error::Error;
 
 use clipstash::{
-    domain::clip::field::{Content, Expires, Password, Title},
-    service::ask::{GetClip, NewClip, UpdateClip},
+    domain::clip::field::{Expires, Password, Title, Visibility},
+    web::api::v1::diff::{CLIP_PASSWORD_A_HEADER, CLIP_PASSWORD_B_HEADER},
+    web::api::v1::{
+        ClipResponse, NewClipRequest, UpdateClipRequest, UsageResponse, CLIP_PASSWORD_HEADER,
+    },
     web::api::{ApiKey, API_KEY_HEADER},
-    Clip, ShortCode,
+    web::problem::Problem,
+    ShortCode,
 };
+use serde::de::DeserializeOwned;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -24,6 +29,15 @@ enum Command {
         expires: Option<Expires>,
         #[structopt(short, long, help = "set a custom clip title")]
         title: Option<Title>,
+        #[structopt(long, help = "public, unlisted (default) or private")]
+        visibility: Option<Visibility>,
+        #[structopt(long = "tag", help = "tag the clip, repeat for more tags")]
+        tags: Vec<String>,
+        #[structopt(
+            long,
+            help = "store the clip even if the server warns about secrets in it"
+        )]
+        allow_secrets: bool,
     },
     Update {
         shortcode: ShortCode,
@@ -35,7 +49,29 @@ enum Command {
         expires: Option<Expires>,
         #[structopt(short, long, help = "set a custom clip title")]
         title: Option<Title>,
+        #[structopt(long, help = "public, unlisted or private")]
+        visibility: Option<Visibility>,
+        #[structopt(
+            long,
+            help = "store the clip even if the server warns about secrets in it"
+        )]
+        allow_secrets: bool,
+    },
+    Fork {
+        shortcode: ShortCode,
+        #[structopt(short, long, help = "password of the original clip")]
+        password: Option<String>,
     },
+    Diff {
+        a: ShortCode,
+        b: ShortCode,
+        #[structopt(long, help = "password of the first clip")]
+        password_a: Option<String>,
+        #[structopt(long, help = "password of the second clip")]
+        password_b: Option<String>,
+    },
+    // the storage used by the clips of the API key
+    Usage,
 }
 
 #[derive(StructOpt, Debug)]
@@ -51,41 +87,167 @@ struct Opt {
     api_key: ApiKey,
 }
 
-fn get_clip(addr: &str, ask_svc: GetClip, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
+// a clip and its ETag, which is required to update it
+struct Versioned {
+    clip: ClipResponse,
+    etag: String,
+}
+
+// error responses are problem documents, show them with the request id so failures can be reported
+fn parse_response<T: DeserializeOwned>(
+    response: reqwest::blocking::Response,
+) -> Result<T, Box<dyn Error>> {
+    if response.status().is_success() {
+        return Ok(response.json()?);
+    }
+    Err(problem(response))
+}
+
+// plain text responses, errors are still problem documents
+fn parse_text(response: reqwest::blocking::Response) -> Result<String, Box<dyn Error>> {
+    if response.status().is_success() {
+        return Ok(response.text()?);
+    }
+    Err(problem(response))
+}
+
+fn problem(response: reqwest::blocking::Response) -> Box<dyn Error> {
+    let status = response.status();
+    match response.json::<Problem>() {
+        Ok(problem) => {
+            let mut message = format!("{} ({})", problem.title, problem.status);
+            if let Some(detail) = problem.detail {
+                message.push_str(&format!(": {}", detail));
+            }
+            for error in problem.errors {
+                message.push_str(&format!("\n  {}: {}", error.field, error.message));
+            }
+            if !problem.request_id.is_empty() {
+                message.push_str(&format!("\n  request id: {}", problem.request_id));
+            }
+            message.into()
+        }
+        Err(_) => format!("request failed with status {}", status).into(),
+    }
+}
+
+fn get_clip(
+    addr: &str,
+    shortcode: &ShortCode,
+    password: Password,
+    api_key: &ApiKey,
+) -> Result<Versioned, Box<dyn Error>> {
     let client = reqwest::blocking::Client::builder().build()?; // blocking client will send a
                                                                 // request and wait until there's an answer
-    let addr = format!("{}/api/clip/{}", addr, ask_svc.shortcode.into_inner());
+    let addr = format!("{}/api/v1/clips/{}", addr, shortcode.as_str());
 
     let mut req = client.get(addr);
-    req = match ask_svc.password.into_inner() {
-        Some(pass) => req.header(reqwest::header::COOKIE, format!("password={}", pass)),
+    req = match password.into_inner() {
+        Some(pass) => req.header(CLIP_PASSWORD_HEADER, pass),
         None => req,
     };
     req = req.header(API_KEY_HEADER, api_key.to_base64());
 
-    Ok(req.send()?.json()?)
+    let response = req.send()?;
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .unwrap_or_default()
+        .to_owned();
+
+    Ok(Versioned {
+        clip: parse_response(response)?,
+        etag,
+    })
 }
 
-fn new_clip(addr: &str, ask_svc: NewClip, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
+fn new_clip(
+    addr: &str,
+    clip: NewClipRequest,
+    api_key: &ApiKey,
+) -> Result<ClipResponse, Box<dyn Error>> {
     let client = reqwest::blocking::Client::builder().build()?; // blocking client will send a
                                                                 // request and wait until there's an answer
-    let addr = format!("{}/api/clip", addr);
+    let addr = format!("{}/api/v1/clips", addr);
 
     let mut req = client.post(addr);
     req = req.header(API_KEY_HEADER, api_key.to_base64());
 
-    Ok(req.json(&ask_svc).send()?.json()?)
+    parse_response(req.json(&clip).send()?)
+}
+
+fn fork_clip(
+    addr: &str,
+    shortcode: &ShortCode,
+    password: Password,
+    api_key: &ApiKey,
+) -> Result<ClipResponse, Box<dyn Error>> {
+    let client = reqwest::blocking::Client::builder().build()?;
+    let addr = format!("{}/api/v1/clips/{}/fork", addr, shortcode.as_str());
+
+    let mut req = client.post(addr);
+    req = match password.into_inner() {
+        Some(pass) => req.header(CLIP_PASSWORD_HEADER, pass),
+        None => req,
+    };
+    req = req.header(API_KEY_HEADER, api_key.to_base64());
+
+    parse_response(req.send()?)
 }
 
-fn update_clip(addr: &str, ask_svc: UpdateClip, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
+// the unified diff of the two clips
+fn diff_clips(
+    addr: &str,
+    a: &ShortCode,
+    b: &ShortCode,
+    passwords: (Password, Password),
+    api_key: &ApiKey,
+) -> Result<String, Box<dyn Error>> {
+    let client = reqwest::blocking::Client::builder().build()?;
+    let addr = format!("{}/api/v1/diff", addr);
+
+    let query = [("a", a.as_str()), ("b", b.as_str()), ("format", "unified")];
+    let mut req = client.get(addr).query(&query);
+    for (header, password) in [
+        (CLIP_PASSWORD_A_HEADER, passwords.0),
+        (CLIP_PASSWORD_B_HEADER, passwords.1),
+    ] {
+        if let Some(pass) = password.into_inner() {
+            req = req.header(header, pass);
+        }
+    }
+    req = req.header(API_KEY_HEADER, api_key.to_base64());
+
+    parse_text(req.send()?)
+}
+
+fn get_usage(addr: &str, api_key: &ApiKey) -> Result<UsageResponse, Box<dyn Error>> {
+    let client = reqwest::blocking::Client::builder().build()?;
+    let addr = format!("{}/api/v1/usage", addr);
+
+    let req = client.get(addr).header(API_KEY_HEADER, api_key.to_base64());
+
+    parse_response(req.send()?)
+}
+
+fn update_clip(
+    addr: &str,
+    shortcode: &ShortCode,
+    clip: UpdateClipRequest,
+    etag: &str,
+    api_key: &ApiKey,
+) -> Result<ClipResponse, Box<dyn Error>> {
     let client = reqwest::blocking::Client::builder().build()?; // blocking client will send a
                                                                 // request and wait until there's an answer
-    let addr = format!("{}/api/clip", addr);
+    let addr = format!("{}/api/v1/clips/{}", addr, shortcode.as_str());
 
     let mut req = client.put(addr);
     req = req.header(API_KEY_HEADER, api_key.to_base64());
+    // the update fails when someone else changed the clip in the meantime
+    req = req.header(reqwest::header::IF_MATCH, etag);
 
-    Ok(req.json(&ask_svc).send()?.json()?)
+    parse_response(req.json(&clip).send()?)
 }
 
 fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
@@ -94,14 +256,9 @@ fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
             shortcode,
             password,
         } => {
-            let req =
-                GetClip {
-                    password: Password::new(password.unwrap_or_default())?,
-                    shortcode,
-                };
-
-            let clip = get_clip(opt.addr.as_str(), req, opt.api_key)?;
-            println!("{:#?}", clip);
+            let password = Password::new(password.unwrap_or_default())?;
+            let clip = get_clip(opt.addr.as_str(), &shortcode, password, &opt.api_key)?;
+            println!("{:#?}", clip.clip);
             Ok(())
         }
         Command::New {
@@ -109,14 +266,23 @@ fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
             password,
             expires,
             title,
+            visibility,
+            tags,
+            allow_secrets,
         } => {
-            let req = NewClip {
-                content: Content::new(clip.as_str())?,
-                title: title.unwrap_or_default(),
-                expires: expires.unwrap_or_default(),
-                password: password.unwrap_or_default(),
+            let req = NewClipRequest {
+                content: clip,
+                title: title.unwrap_or_default().into_inner(),
+                expires: expires
+                    .unwrap_or_default()
+                    .into_inner()
+                    .map(|time| time.into_inner()),
+                password: password.unwrap_or_default().into_inner(),
+                visibility: visibility.map(|visibility| visibility.to_string()),
+                tags,
+                allow_secrets,
             };
-            let clip = new_clip(opt.addr.as_str(), req, opt.api_key)?;
+            let clip = new_clip(opt.addr.as_str(), req, &opt.api_key)?;
             println!("{:#?}", clip);
             Ok(())
         }
@@ -126,26 +292,65 @@ fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
             password,
             expires,
             title,
+            visibility,
+            allow_secrets,
         } => {
             let password = password.unwrap_or_default();
-            let svc_req = GetClip {
-                password: password.clone(),
-                shortcode: shortcode.clone(),
-            };
+            let Versioned {
+                clip: old_clip,
+                etag,
+            } = get_clip(
+                opt.addr.as_str(),
+                &shortcode,
+                password.clone(),
+                &opt.api_key,
+            )?;
 
-            let old_clip = get_clip(opt.addr.as_str(), svc_req, opt.api_key.clone())?;
-            let svc_req = UpdateClip {
-                content: Content::new(clip.as_str())?,
-                expires: expires.unwrap_or(old_clip.expires),
-                title: title.unwrap_or(old_clip.title),
-                password,
-                shortcode,
+            // the title, expiration and visibility are kept unless new ones are given
+            let req = UpdateClipRequest {
+                content: clip,
+                title: title.map_or(old_clip.title, |title| title.into_inner()),
+                expires: expires.map_or(old_clip.expires, |expires| {
+                    expires.into_inner().map(|time| time.into_inner())
+                }),
+                password: password.into_inner(),
+                visibility: Some(
+                    visibility.map_or(old_clip.visibility, |visibility| visibility.to_string()),
+                ),
+                allow_secrets,
             };
-
-            let clip = update_clip(opt.addr.as_str(), svc_req, opt.api_key);
+            let clip = update_clip(opt.addr.as_str(), &shortcode, req, &etag, &opt.api_key)?;
             println!("{:#?}", clip);
             Ok(())
         }
+        Command::Fork {
+            shortcode,
+            password,
+        } => {
+            let password = Password::new(password.unwrap_or_default())?;
+            let clip = fork_clip(opt.addr.as_str(), &shortcode, password, &opt.api_key)?;
+            println!("{:#?}", clip);
+            Ok(())
+        }
+        Command::Diff {
+            a,
+            b,
+            password_a,
+            password_b,
+        } => {
+            let passwords = (
+                Password::new(password_a.unwrap_or_default())?,
+                Password::new(password_b.unwrap_or_default())?,
+            );
+            let diff = diff_clips(opt.addr.as_str(), &a, &b, passwords, &opt.api_key)?;
+            print!("{}", diff);
+            Ok(())
+        }
+        Command::Usage => {
+            let usage = get_usage(opt.addr.as_str(), &opt.api_key)?;
+            println!("{:#?}", usage);
+            Ok(())
+        }
     }
 }
 
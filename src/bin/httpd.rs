@@ -1,10 +1,26 @@
 use clipstash::data::AppDatabase;
+use clipstash::domain::limits::{Limits, StorageQuota};
 use clipstash::domain::maintenance::Maintenance;
+use clipstash::domain::secret::{SecretPolicy, SecretScanner};
+use clipstash::service::action;
+use clipstash::web::admin::AdminToken;
+use clipstash::web::feed::BaseUrl;
 use clipstash::web::hitcounter::HitCounter;
+use clipstash::web::metrics::MetricsToken;
 use clipstash::web::renderer::Renderer;
 use dotenv::dotenv;
+use rocket::data::ByteUnit;
 use std::path::PathBuf;
 use structopt::StructOpt;
+use strum::EnumString;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Debug, EnumString)]
+#[strum(serialize_all = "lowercase")]
+enum LogFormat {
+    Pretty,
+    Json,
+}
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "httpd")]
@@ -13,17 +29,136 @@ struct Opt {
     connection_string: String,
     #[structopt(short, long, parse(from_os_str), default_value = "templates/")]
     template_directory: PathBuf,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_METRICS_TOKEN",
+        help = "bearer token required to scrape /metrics"
+    )]
+    metrics_token: Option<String>,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_ADMIN_TOKEN",
+        help = "token of the operator for the /admin pages, they are disabled without it"
+    )]
+    admin_token: Option<String>,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_BASE_URL",
+        default_value = "http://localhost:8000",
+        help = "public address of the instance the links of the feeds start with, e.g. \
+                `https://stash.example`"
+    )]
+    base_url: String,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_SECRET_POLICY",
+        default_value = "warn",
+        possible_values = &["off", "warn", "reject", "redact"],
+        help = "what to do with clips that contain secrets like API keys"
+    )]
+    secret_policy: SecretPolicy,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_SECRET_RULES",
+        parse(from_os_str),
+        help = "JSON file of the detectors of secrets, replaces the built-in ones"
+    )]
+    secret_rules: Option<PathBuf>,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_MAX_CONTENT_SIZE",
+        default_value = "8MiB",
+        help = "largest content of a clip, e.g. `512KiB`"
+    )]
+    max_content_size: ByteUnit,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_MAX_TITLE_LENGTH",
+        default_value = "200",
+        help = "longest title of a clip, in characters"
+    )]
+    max_title_length: usize,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_STORAGE_QUOTA",
+        help = "most content the clips of a single API key can hold, e.g. `100MiB`, unlimited \
+                without it"
+    )]
+    storage_quota: Option<ByteUnit>,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_LOG",
+        default_value = "info,rocket=warn",
+        help = "log filter, e.g. `debug` or `info,clipstash=debug`"
+    )]
+    log_filter: String,
+    #[structopt(
+        long,
+        env = "CLIPSTASH_LOG_FORMAT",
+        default_value = "pretty",
+        possible_values = &["pretty", "json"]
+    )]
+    log_format: LogFormat,
+    #[structopt(
+        long,
+        help = "move the content of clips stored before blobs were added into compressed, \
+                deduplicated blobs, then exit"
+    )]
+    migrate_content: bool,
+}
+
+fn init_tracing(filter: &str, format: LogFormat) {
+    let filter = EnvFilter::try_new(filter).unwrap_or_else(|e| {
+        eprintln!(
+            "invalid log filter `{}`: {}, falling back to `info`",
+            filter, e
+        );
+        EnvFilter::new("info")
+    });
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    match format {
+        LogFormat::Pretty => subscriber.init(),
+        LogFormat::Json => subscriber.json().flatten_event(true).init(),
+    }
 }
 
 fn main() {
     dotenv().ok();
     let opt = Opt::from_args();
+    init_tracing(&opt.log_filter, opt.log_format);
+
+    Limits {
+        max_content_size: opt.max_content_size.as_u64() as usize,
+        max_title_length: opt.max_title_length,
+    }
+    .install();
 
     let rt = tokio::runtime::Runtime::new().expect("failed to spawn tokio runtime");
     let handle = rt.handle().clone();
 
+    let secrets = match &opt.secret_rules {
+        Some(rules) => SecretScanner::load(opt.secret_policy, rules).unwrap_or_else(|e| {
+            tracing::error!(error = %e, rules = %rules.display(), "failed to load the secret rules");
+            std::process::exit(1);
+        }),
+        None => SecretScanner::new(opt.secret_policy, SecretScanner::builtin()),
+    };
     let renderer = Renderer::new(opt.template_directory.clone());
     let database = rt.block_on(async move { AppDatabase::new(&opt.connection_string).await });
+
+    if opt.migrate_content {
+        let pool = database.get_pool().clone();
+        match rt.block_on(async move { action::migrate_content(&pool).await }) {
+            Ok(moved) => tracing::info!(moved, "clip content moved into blobs"),
+            Err(e) => {
+                tracing::error!(error = %e, "failed to move clip content into blobs");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let hit_counter = HitCounter::new(database.get_pool().clone(), handle.clone());
     let maintenance = Maintenance::spawn(database.get_pool().clone(), handle.clone());
 
@@ -33,6 +168,11 @@ fn main() {
             database,
             hit_counter,
             maintenance,
+            metrics_token: MetricsToken(opt.metrics_token),
+            admin_token: AdminToken(opt.admin_token),
+            secrets,
+            quota: StorageQuota(opt.storage_quota.map(|quota| quota.as_u64())),
+            base_url: BaseUrl(opt.base_url),
         };
 
     rt.block_on(async move {
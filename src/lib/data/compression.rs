@@ -0,0 +1,72 @@
+use strum::{Display, EnumString};
+
+use crate::ClipError;
+
+// content below the threshold is stored as is, compressing it saves too little to be worth it
+pub const COMPRESSION_THRESHOLD: usize = 4 * 1024;
+const ZSTD_LEVEL: i32 = 3;
+
+// ContentEncoding is the value of the `content_encoding` column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum ContentEncoding {
+    Identity,
+    Zstd,
+}
+
+// StoredContent is the content of a clip the way it is stored in the `content` column
+#[derive(Debug, Clone)]
+pub struct StoredContent {
+    pub bytes: Vec<u8>,
+    pub encoding: ContentEncoding,
+}
+
+impl StoredContent {
+    // compresses content above the threshold, unless the compressed content is not smaller
+    pub fn compress(content: &str) -> Self {
+        if content.len() >= COMPRESSION_THRESHOLD {
+            match zstd::encode_all(content.as_bytes(), ZSTD_LEVEL) {
+                Ok(bytes) if bytes.len() < content.len() => {
+                    return Self {
+                        bytes,
+                        encoding: ContentEncoding::Zstd,
+                    }
+                }
+                Ok(_) => (),
+                Err(e) => tracing::warn!(error = %e, "failed to compress clip content"),
+            }
+        }
+
+        Self {
+            bytes: content.as_bytes().to_vec(),
+            encoding: ContentEncoding::Identity,
+        }
+    }
+
+    pub fn decompress(self) -> Result<String, ClipError> {
+        let bytes = match self.encoding {
+            ContentEncoding::Identity => self.bytes,
+            ContentEncoding::Zstd => zstd::decode_all(self.bytes.as_slice())
+                .map_err(|e| ClipError::Encoding(e.to_string()))?,
+        };
+        String::from_utf8(bytes).map_err(|e| ClipError::Encoding(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::{ContentEncoding, StoredContent, COMPRESSION_THRESHOLD};
+
+    #[test]
+    fn only_large_content_is_compressed() {
+        let small = StoredContent::compress("short log line");
+        assert_eq!(small.encoding, ContentEncoding::Identity);
+        assert_eq!(small.decompress().unwrap(), "short log line");
+
+        let log = "ERROR connection refused\n".repeat(COMPRESSION_THRESHOLD);
+        let large = StoredContent::compress(&log);
+        assert_eq!(large.encoding, ContentEncoding::Zstd);
+        assert!(large.bytes.len() < log.len() / 10);
+        assert_eq!(large.decompress().unwrap(), log);
+    }
+}
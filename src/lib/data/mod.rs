@@ -1,3 +1,4 @@
+pub mod compression;
 pub mod model; // makes the model module available
 pub mod query;
 
@@ -30,6 +31,9 @@ pub type Transaction<'t> = sqlx::Transaction<'t, Sqlite>;
 pub type AppDatabaseRow = sqlx::sqlite::SqliteRow;
 pub type AppQueryResult = sqlx::sqlite::SqliteQueryResult;
 
+// migrations embedded at compile time, used to check that the database schema is up to date
+pub static MIGRATOR: sqlx::migrate::Migrator = sqlx::migrate!("./migrations");
+
 // request database to implement the sqlx database trait
 // and encapsulates a pool
 pub struct Database<D: sqlx::Database>(sqlx::Pool<D>);
@@ -43,8 +47,8 @@ impl Database<Sqlite> {
         match pool {
             Ok(pool) => Self(pool),
             Err(e) => {
-                eprintln!("{}\n", e);
-                eprintln!("if the database has not been created, run: \n\tsqlx database setup\n");
+                tracing::error!(error = %e, "failed to connect to the database");
+                tracing::error!("if the database has not been created, run: sqlx database setup");
                 panic!("database error");
             }
         }
@@ -1,6 +1,9 @@
+use crate::data::compression::{ContentEncoding, StoredContent};
 use crate::data::DbId;
+use crate::domain::audit::AuditError;
+use crate::domain::report::ReportError;
 use crate::{ClipError, ShortCode, Time};
-use chrono::{NaiveDateTime, Utc};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use std::convert::TryFrom;
 use std::str::FromStr;
 
@@ -8,7 +11,7 @@ use std::str::FromStr;
 // we need to specify fields and data types that are going to
 // correspond with the database fields so the FromRow can perform the
 // automatic conversion
-#[derive(Debug, sqlx::FromRow)]
+#[derive(Debug, Clone, sqlx::FromRow)]
 pub struct Clip {
     // difference between model::Clip and domain::Clip are that the
     // domain::Clip has fields with verification. This type will be used
@@ -26,32 +29,87 @@ pub struct Clip {
     // our database and the program.
     pub(in crate::data) clip_id: String,
     pub(in crate::data) shortcode: String,
-    pub(in crate::data) content: String,
+    // compressed when `content_encoding` isn't `identity`
+    pub(in crate::data) content: Vec<u8>,
+    pub(in crate::data) content_encoding: String,
+    // the blob with the content, clips stored before blobs were added have their own content
+    pub(in crate::data) content_hash: Option<String>,
     pub(in crate::data) title: Option<String>,
     pub(in crate::data) posted: NaiveDateTime,
     pub(in crate::data) expires: Option<NaiveDateTime>,
     pub(in crate::data) password: Option<String>,
     pub(in crate::data) hits: i64,
+    pub(in crate::data) owner: Option<Vec<u8>>,
+    // clips created before the column existed fall back to `posted`
+    pub(in crate::data) updated: Option<NaiveDateTime>,
+    pub(in crate::data) visibility: String,
+    // the tags of the clip separated by spaces, `None` when it has none
+    pub(in crate::data) tags: Option<String>,
+    pub(in crate::data) forked_from: Option<String>,
+    // the number of clips forked from this one
+    pub(in crate::data) forks: i64,
+    pub(in crate::data) takedown: Option<String>,
+    pub(in crate::data) takedown_note: Option<String>,
     // (in crate::data) make it so these fields are only accessible from within the
     // data module and only it can modify data in order to get data to and from the
     // database
 }
 
+impl Clip {
+    pub fn shortcode(&self) -> &str {
+        &self.shortcode
+    }
+
+    // the content as it is stored, raw clips are sent compressed to clients that can decode them
+    pub fn stored_content(&self) -> Result<StoredContent, ClipError> {
+        Ok(StoredContent {
+            bytes: self.content.clone(),
+            encoding: parse_encoding(&self.content_encoding)?,
+        })
+    }
+}
+
+fn parse_encoding(encoding: &str) -> Result<ContentEncoding, ClipError> {
+    ContentEncoding::from_str(encoding)
+        .map_err(|_| ClipError::Encoding(format!("unknown encoding `{}`", encoding)))
+}
+
 impl TryFrom<Clip> for crate::domain::Clip {
     type Error = ClipError;
     fn try_from(value: Clip) -> Result<Self, Self::Error> {
         // these are necessary to transform core types into field types
         use crate::domain::clip::field;
 
+        let content = StoredContent {
+            encoding: parse_encoding(&value.content_encoding)?,
+            bytes: value.content,
+        }
+        .decompress()?;
+
         Ok(Self {
             clip_id: field::ClipId::new(DbId::from_str(value.clip_id.as_str())?),
             shortcode: field::ShortCode::from(value.shortcode),
-            content: field::Content::new(value.content.as_str())?,
+            content: field::Content::stored(content),
             title: field::Title::new(value.title),
             posted: field::Posted::new(Time::from_naive_utc(value.posted)),
+            updated: field::Updated::new(Time::from_naive_utc(
+                value.updated.unwrap_or(value.posted),
+            )),
             expires: field::Expires::new(value.expires.map(Time::from_naive_utc)),
             password: field::Password::new(value.password.unwrap_or_default())?,
             hits: field::Hits::new(u64::try_from(value.hits)?),
+            owner: field::Owner::new(value.owner),
+            visibility: field::Visibility::new(&value.visibility)?,
+            tags: field::Tags::from_str(value.tags.as_deref().unwrap_or_default())?,
+            forked_from: field::ForkedFrom::new(value.forked_from.map(field::ShortCode::from)),
+            forks: field::Forks::new(u64::try_from(value.forks)?),
+            takedown: match value.takedown {
+                Some(reason) => Some(field::Takedown {
+                    reason: field::TakedownReason::stored(&reason),
+                    note: value.takedown_note.unwrap_or_default(),
+                }),
+                None => None,
+            },
         })
     }
 }
@@ -87,10 +145,16 @@ pub struct NewClip {
     pub(in crate::data) clip_id: String,
     pub(in crate::data) shortcode: String,
     pub(in crate::data) content: String,
+    // the size of the content in bytes
+    pub(in crate::data) size: i64,
     pub(in crate::data) title: Option<String>,
     pub(in crate::data) posted: i64,
     pub(in crate::data) expires: Option<i64>,
     pub(in crate::data) password: Option<String>,
+    pub(in crate::data) owner: Option<Vec<u8>>,
+    pub(in crate::data) visibility: String,
+    pub(in crate::data) tags: Vec<String>,
+    pub(in crate::data) forked_from: Option<String>,
 }
 
 impl From<crate::service::ask::NewClip> for NewClip {
@@ -98,31 +162,532 @@ impl From<crate::service::ask::NewClip> for NewClip {
         Self {
             clip_id: DbId::new().into(),
             shortcode: ShortCode::default().into(),
+            size: value.content.as_str().len() as i64,
             content: value.content.into_inner(),
             title: value.title.into_inner(),
             posted: Utc::now().timestamp(),
             expires: value.expires.into_inner().map(|time| time.timestamp()),
             password: value.password.into_inner(),
+            owner: value.owner.into_inner(),
+            visibility: value.visibility.to_string(),
+            tags: value.tags.into_inner(),
+            forked_from: value.forked_from.into_inner().map(ShortCode::into_inner),
         }
     }
 }
 
 pub struct UpdateClip {
     pub(in crate::data) shortcode: String,
+    pub(in crate::data) updated: i64,
     pub(in crate::data) content: String,
+    pub(in crate::data) size: i64,
     pub(in crate::data) title: Option<String>,
     pub(in crate::data) expires: Option<i64>,
     pub(in crate::data) password: Option<String>,
+    // `None` keeps the visibility of the clip
+    pub(in crate::data) visibility: Option<String>,
 }
 
 impl From<crate::service::ask::UpdateClip> for UpdateClip {
     fn from(value: crate::service::ask::UpdateClip) -> Self {
         Self {
-            shortcode: ShortCode::default().into(),
+            shortcode: value.shortcode.into_inner(),
+            updated: Utc::now().timestamp(),
+            size: value.content.as_str().len() as i64,
             content: value.content.into_inner(),
             title: value.title.into_inner(),
             expires: value.expires.into_inner().map(|time| time.timestamp()),
             password: value.password.into_inner(),
+            visibility: value.visibility.map(|visibility| visibility.to_string()),
+        }
+    }
+}
+
+pub struct ListClips {
+    pub(in crate::data) tag: Option<String>,
+    pub(in crate::data) limit: u32,
+    pub(in crate::data) offset: u32,
+}
+
+impl From<crate::service::ask::ListClips> for ListClips {
+    fn from(value: crate::service::ask::ListClips) -> Self {
+        Self {
+            tag: value.tag,
+            limit: value.limit,
+            offset: value.offset,
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Collection {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) posted: NaiveDateTime,
+    pub(in crate::data) expires: Option<NaiveDateTime>,
+    pub(in crate::data) password: Option<String>,
+    pub(in crate::data) owner: Option<Vec<u8>>,
+}
+
+// the clips of the collection are loaded separately, they are left empty
+impl TryFrom<Collection> for crate::domain::collection::Collection {
+    type Error = ClipError;
+    fn try_from(value: Collection) -> Result<Self, Self::Error> {
+        use crate::domain::clip::field;
+
+        Ok(Self {
+            shortcode: field::ShortCode::from(value.shortcode),
+            title: field::Title::new(value.title),
+            posted: field::Posted::new(Time::from_naive_utc(value.posted)),
+            expires: field::Expires::new(value.expires.map(Time::from_naive_utc)),
+            password: field::Password::new(value.password)?,
+            owner: field::Owner::new(value.owner),
+            clips: vec![],
+            locked: vec![],
+        })
+    }
+}
+
+pub struct NewCollection {
+    pub(in crate::data) collection_id: String,
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) posted: i64,
+    pub(in crate::data) expires: Option<i64>,
+    pub(in crate::data) password: Option<String>,
+    pub(in crate::data) owner: Option<Vec<u8>>,
+    pub(in crate::data) clips: Vec<String>,
+}
+
+impl From<crate::service::ask::NewCollection> for NewCollection {
+    fn from(value: crate::service::ask::NewCollection) -> Self {
+        Self {
+            collection_id: DbId::new().into(),
+            shortcode: ShortCode::default().into(),
+            title: value.title.into_inner(),
+            posted: Utc::now().timestamp(),
+            expires: value.expires.into_inner().map(|time| time.timestamp()),
+            password: value.password.into_inner(),
+            owner: value.owner.into_inner(),
+            clips: value.clips.into_iter().map(ShortCode::into_inner).collect(),
+        }
+    }
+}
+
+pub struct UpdateCollection {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) expires: Option<i64>,
+    pub(in crate::data) password: Option<String>,
+    pub(in crate::data) clips: Vec<String>,
+}
+
+impl From<crate::service::ask::UpdateCollection> for UpdateCollection {
+    fn from(value: crate::service::ask::UpdateCollection) -> Self {
+        Self {
+            shortcode: value.shortcode.into_inner(),
+            title: value.title.into_inner(),
+            expires: value.expires.into_inner().map(|time| time.timestamp()),
+            password: value.password.into_inner(),
+            clips: value.clips.into_iter().map(ShortCode::into_inner).collect(),
+        }
+    }
+}
+
+// aggregated views of a clip for a single day, referrer and user agent family
+pub struct NewView {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) day: NaiveDate,
+    // empty when the view had no referrer, NULLs can't be part of the primary key
+    pub(in crate::data) referrer: String,
+    pub(in crate::data) agent: String,
+    pub(in crate::data) views: u32,
+}
+
+impl From<crate::service::ask::ViewCount> for NewView {
+    fn from(value: crate::service::ask::ViewCount) -> Self {
+        Self {
+            shortcode: value.shortcode.into_inner(),
+            day: value.day,
+            referrer: value.referrer.unwrap_or_default(),
+            agent: value.agent,
+            views: value.views,
+        }
+    }
+}
+
+pub struct NewVisitor {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) day: NaiveDate,
+    pub(in crate::data) visitor: String,
+}
+
+impl From<crate::service::ask::Visitor> for NewVisitor {
+    fn from(value: crate::service::ask::Visitor) -> Self {
+        Self {
+            shortcode: value.shortcode.into_inner(),
+            day: value.day,
+            visitor: value.visitor,
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct DailyViews {
+    pub(in crate::data) day: NaiveDate,
+    pub(in crate::data) views: i64,
+    pub(in crate::data) unique_visitors: i64,
+}
+
+impl TryFrom<DailyViews> for crate::domain::stats::DailyViews {
+    type Error = ClipError;
+    fn try_from(value: DailyViews) -> Result<Self, Self::Error> {
+        Ok(Self {
+            day: value.day,
+            views: u64::try_from(value.views)?,
+            unique_visitors: u64::try_from(value.unique_visitors)?,
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ReferrerViews {
+    pub(in crate::data) referrer: String,
+    pub(in crate::data) views: i64,
+}
+
+impl TryFrom<ReferrerViews> for crate::domain::stats::ReferrerViews {
+    type Error = ClipError;
+    fn try_from(value: ReferrerViews) -> Result<Self, Self::Error> {
+        Ok(Self {
+            host: value.referrer,
+            views: u64::try_from(value.views)?,
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct AgentViews {
+    pub(in crate::data) agent: String,
+    pub(in crate::data) views: i64,
+}
+
+impl TryFrom<AgentViews> for crate::domain::stats::AgentViews {
+    type Error = ClipError;
+    fn try_from(value: AgentViews) -> Result<Self, Self::Error> {
+        Ok(Self {
+            family: value.agent,
+            views: u64::try_from(value.views)?,
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ClipSummary {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) posted: NaiveDateTime,
+    pub(in crate::data) expires: Option<NaiveDateTime>,
+    pub(in crate::data) hits: i64,
+    pub(in crate::data) size: i64,
+    pub(in crate::data) visibility: String,
+    pub(in crate::data) protected: bool,
+    pub(in crate::data) owner: Option<i64>,
+    pub(in crate::data) takedown: Option<String>,
+}
+
+impl TryFrom<ClipSummary> for crate::domain::admin::ClipSummary {
+    type Error = ClipError;
+    fn try_from(value: ClipSummary) -> Result<Self, Self::Error> {
+        use crate::domain::clip::field;
+
+        Ok(Self {
+            shortcode: ShortCode::from(value.shortcode),
+            title: value.title,
+            posted: Time::from_naive_utc(value.posted),
+            expires: value.expires.map(Time::from_naive_utc),
+            hits: u64::try_from(value.hits)?,
+            size: u64::try_from(value.size)?,
+            visibility: field::Visibility::new(&value.visibility)?,
+            protected: value.protected,
+            owner: value.owner,
+            takedown: value.takedown.as_deref().map(field::TakedownReason::stored),
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct KeyUsage {
+    pub(in crate::data) id: i64,
+    pub(in crate::data) created: Option<NaiveDateTime>,
+    pub(in crate::data) disabled: Option<NaiveDateTime>,
+    pub(in crate::data) clips: i64,
+    pub(in crate::data) size: i64,
+    pub(in crate::data) hits: i64,
+}
+
+impl TryFrom<KeyUsage> for crate::domain::admin::KeyUsage {
+    type Error = ClipError;
+    fn try_from(value: KeyUsage) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.id,
+            created: value.created.map(Time::from_naive_utc),
+            disabled: value.disabled.map(Time::from_naive_utc),
+            clips: u64::try_from(value.clips)?,
+            size: u64::try_from(value.size)?,
+            hits: u64::try_from(value.hits)?,
+        })
+    }
+}
+
+pub struct Usage {
+    pub(in crate::data) clips: i64,
+    pub(in crate::data) bytes: i64,
+}
+
+// a clip whose size is not known yet, only its stored content is read to count it
+#[derive(Debug, sqlx::FromRow)]
+pub struct UnsizedClip {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) content: Vec<u8>,
+    pub(in crate::data) content_encoding: String,
+}
+
+impl UnsizedClip {
+    pub fn shortcode(&self) -> &str {
+        &self.shortcode
+    }
+
+    // the size of the uncompressed content. Content that can't be decompressed is counted with
+    // its stored size, it can't be served either
+    pub fn size(&self) -> i64 {
+        let content = parse_encoding(&self.content_encoding).and_then(|encoding| {
+            StoredContent {
+                bytes: self.content.clone(),
+                encoding,
+            }
+            .decompress()
+        });
+        match content {
+            Ok(content) => content.len() as i64,
+            Err(e) => {
+                tracing::warn!(error = %e, shortcode = %self.shortcode, "failed to decompress a clip");
+                self.content.len() as i64
+            }
+        }
+    }
+}
+
+// the quota isn't stored, it is set from the configuration of the server
+impl TryFrom<Usage> for crate::domain::limits::Usage {
+    type Error = ClipError;
+    fn try_from(value: Usage) -> Result<Self, Self::Error> {
+        Ok(Self {
+            clips: u64::try_from(value.clips)?,
+            bytes: u64::try_from(value.bytes)?,
+            quota: None,
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct StorageStats {
+    pub(in crate::data) clips: i64,
+    pub(in crate::data) collections: i64,
+    pub(in crate::data) api_keys: i64,
+    pub(in crate::data) disabled_keys: i64,
+    pub(in crate::data) blobs: i64,
+    pub(in crate::data) blob_size: i64,
+    pub(in crate::data) inline_size: i64,
+    pub(in crate::data) shared_blobs: i64,
+}
+
+impl TryFrom<StorageStats> for crate::domain::admin::StorageStats {
+    type Error = ClipError;
+    fn try_from(value: StorageStats) -> Result<Self, Self::Error> {
+        Ok(Self {
+            clips: u64::try_from(value.clips)?,
+            collections: u64::try_from(value.collections)?,
+            api_keys: u64::try_from(value.api_keys)?,
+            disabled_keys: u64::try_from(value.disabled_keys)?,
+            blobs: u64::try_from(value.blobs)?,
+            blob_size: u64::try_from(value.blob_size)?,
+            inline_size: u64::try_from(value.inline_size)?,
+            shared_blobs: u64::try_from(value.shared_blobs)?,
+        })
+    }
+}
+
+pub struct NewReport {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) reason: String,
+    pub(in crate::data) contact: Option<String>,
+    pub(in crate::data) filed: i64,
+}
+
+impl From<crate::service::ask::NewReport> for NewReport {
+    fn from(value: crate::service::ask::NewReport) -> Self {
+        Self {
+            shortcode: value.shortcode.into_inner(),
+            reason: value.reason.into_inner(),
+            contact: value.contact.into_inner(),
+            filed: Utc::now().timestamp(),
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Report {
+    pub(in crate::data) report_id: i64,
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) reason: String,
+    pub(in crate::data) contact: Option<String>,
+    pub(in crate::data) filed: NaiveDateTime,
+}
+
+impl From<Report> for crate::domain::report::Report {
+    fn from(value: Report) -> Self {
+        Self {
+            id: value.report_id,
+            shortcode: ShortCode::from(value.shortcode),
+            title: value.title,
+            reason: value.reason,
+            contact: value.contact,
+            filed: Time::from_naive_utc(value.filed),
+        }
+    }
+}
+
+pub struct NewModerationEntry {
+    pub(in crate::data) time: i64,
+    pub(in crate::data) action: String,
+    pub(in crate::data) shortcode: Option<String>,
+    pub(in crate::data) report_id: Option<i64>,
+    pub(in crate::data) key_id: Option<i64>,
+    pub(in crate::data) note: Option<String>,
+}
+
+impl From<crate::service::ask::Moderation> for NewModerationEntry {
+    fn from(value: crate::service::ask::Moderation) -> Self {
+        Self {
+            time: Utc::now().timestamp(),
+            action: value.action.to_string(),
+            shortcode: value.shortcode.map(ShortCode::into_inner),
+            report_id: value.report,
+            key_id: value.key,
+            note: value.note,
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ModerationEntry {
+    pub(in crate::data) entry_id: i64,
+    pub(in crate::data) time: NaiveDateTime,
+    pub(in crate::data) action: String,
+    pub(in crate::data) shortcode: Option<String>,
+    pub(in crate::data) report_id: Option<i64>,
+    pub(in crate::data) key_id: Option<i64>,
+    pub(in crate::data) note: Option<String>,
+}
+
+impl TryFrom<ModerationEntry> for crate::domain::report::ModerationEntry {
+    type Error = ReportError;
+    fn try_from(value: ModerationEntry) -> Result<Self, Self::Error> {
+        use crate::domain::report::ModerationAction;
+
+        Ok(Self {
+            id: value.entry_id,
+            time: Time::from_naive_utc(value.time),
+            action: ModerationAction::from_str(&value.action)
+                .map_err(|_| ReportError::UnknownAction(value.action.clone()))?,
+            shortcode: value.shortcode.map(ShortCode::from),
+            report: value.report_id,
+            key: value.key_id,
+            note: value.note,
+        })
+    }
+}
+
+pub struct NewAuditEntry {
+    pub(in crate::data) time: i64,
+    pub(in crate::data) action: String,
+    pub(in crate::data) shortcode: Option<String>,
+    // the API key of the actor, its id is looked up when the entry is written
+    pub(in crate::data) key: Option<Vec<u8>>,
+    pub(in crate::data) admin: bool,
+    pub(in crate::data) ip: Option<String>,
+    pub(in crate::data) target_key_id: Option<i64>,
+}
+
+impl From<crate::service::ask::Audit> for NewAuditEntry {
+    fn from(value: crate::service::ask::Audit) -> Self {
+        Self {
+            time: Utc::now().timestamp(),
+            action: value.action.to_string(),
+            shortcode: value.shortcode.map(ShortCode::into_inner),
+            key: value.actor.key.into_inner(),
+            admin: value.actor.admin,
+            ip: value.actor.ip.map(|ip| ip.to_string()),
+            target_key_id: value.target_key,
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub(in crate::data) entry_id: i64,
+    pub(in crate::data) time: NaiveDateTime,
+    pub(in crate::data) action: String,
+    pub(in crate::data) shortcode: Option<String>,
+    pub(in crate::data) key_id: Option<i64>,
+    pub(in crate::data) admin: bool,
+    pub(in crate::data) ip: Option<String>,
+    pub(in crate::data) target_key_id: Option<i64>,
+}
+
+impl TryFrom<AuditEntry> for crate::domain::audit::AuditEntry {
+    type Error = AuditError;
+    fn try_from(value: AuditEntry) -> Result<Self, Self::Error> {
+        use crate::domain::audit::AuditAction;
+
+        Ok(Self {
+            id: value.entry_id,
+            time: Time::from_naive_utc(value.time),
+            action: AuditAction::from_str(&value.action)
+                .map_err(|_| AuditError::UnknownAction(value.action.clone()))?,
+            shortcode: value.shortcode.map(ShortCode::from),
+            key: value.key_id,
+            admin: value.admin,
+            ip: value.ip,
+            target_key: value.target_key_id,
+        })
+    }
+}
+
+pub struct AuditQuery {
+    pub(in crate::data) action: Option<String>,
+    pub(in crate::data) shortcode: Option<String>,
+    pub(in crate::data) key_id: Option<i64>,
+    pub(in crate::data) ip: Option<String>,
+    pub(in crate::data) since: Option<i64>,
+    pub(in crate::data) until: Option<i64>,
+    pub(in crate::data) before: Option<i64>,
+    pub(in crate::data) limit: u32,
+}
+
+impl From<crate::service::ask::AuditQuery> for AuditQuery {
+    fn from(value: crate::service::ask::AuditQuery) -> Self {
+        Self {
+            action: value.action.map(|action| action.to_string()),
+            shortcode: value.shortcode.map(ShortCode::into_inner),
+            key_id: value.key,
+            ip: value.ip,
+            since: value.since.map(|time| time.timestamp()),
+            until: value.until.map(|time| time.timestamp()),
+            before: value.before,
+            limit: value.limit,
         }
     }
 }
@@ -1,8 +1,10 @@
-use sqlx::Row;
+use sha2::{Digest, Sha256};
+use sqlx::{QueryBuilder, Row, Sqlite};
 
+use super::compression::StoredContent;
 use super::model;
 use crate::{
-    data::{DataError, DatabasePool},
+    data::{DataError, DatabasePool, Transaction},
     web::api::ApiKey,
     ShortCode,
 };
@@ -11,114 +13,781 @@ use crate::{
 // wihout having to type it out every time
 type Result<T> = std::result::Result<T, DataError>;
 
-pub async fn increase_hit_count(
+// keeps the number of bound parameters per statement well below the sqlite limit
+const MAX_ROWS_PER_STATEMENT: usize = 1000;
+
+// increases the hit count of every clip in the batch with multi-row UPDATEs, each clip takes
+// three parameters. The caller owns the transaction so the whole batch is committed or rolled
+// back at once
+pub async fn increase_hit_counts(
+    hits: &[(ShortCode, u32)],
+    transaction: &mut Transaction<'_>,
+) -> Result<u64> {
+    let mut updated = 0;
+    for chunk in hits.chunks(MAX_ROWS_PER_STATEMENT / 3) {
+        let mut query =
+            QueryBuilder::<Sqlite>::new("UPDATE clips SET hits = hits + CASE shortcode");
+        for (shortcode, count) in chunk {
+            query
+                .push(" WHEN ")
+                .push_bind(shortcode.as_str())
+                .push(" THEN ")
+                .push_bind(*count);
+        }
+        query.push(" ELSE 0 END WHERE shortcode IN (");
+        let mut separated = query.separated(", ");
+        for (shortcode, _) in chunk {
+            separated.push_bind(shortcode.as_str());
+        }
+        separated.push_unseparated(")");
+
+        updated += query
+            .build()
+            .execute(&mut **transaction)
+            .await?
+            .rows_affected();
+    }
+    Ok(updated)
+}
+
+// adds aggregated views to the clip stats, views of clips that no longer exist are skipped
+pub async fn add_views(views: &[model::NewView], transaction: &mut Transaction<'_>) -> Result<()> {
+    for chunk in views.chunks(MAX_ROWS_PER_STATEMENT) {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "INSERT INTO clip_stats (shortcode, day, referrer, agent, views) SELECT * FROM (",
+        );
+        query.push_values(chunk, |mut row, view| {
+            row.push_bind(view.shortcode.as_str())
+                .push_bind(view.day)
+                .push_bind(view.referrer.as_str())
+                .push_bind(view.agent.as_str())
+                .push_bind(view.views);
+        });
+        query.push(
+            r#") WHERE column1 IN (SELECT shortcode FROM clips)
+            ON CONFLICT (shortcode, day, referrer, agent) DO UPDATE SET views = views + excluded.views"#,
+        );
+        query.build().execute(&mut **transaction).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn add_visitors(
+    visitors: &[model::NewVisitor],
+    transaction: &mut Transaction<'_>,
+) -> Result<()> {
+    for chunk in visitors.chunks(MAX_ROWS_PER_STATEMENT) {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "INSERT INTO clip_visitors (shortcode, day, visitor) SELECT * FROM (",
+        );
+        query.push_values(chunk, |mut row, visitor| {
+            row.push_bind(visitor.shortcode.as_str())
+                .push_bind(visitor.day)
+                .push_bind(visitor.visitor.as_str());
+        });
+        query.push(
+            r#") WHERE column1 IN (SELECT shortcode FROM clips)
+            ON CONFLICT (shortcode, day, visitor) DO NOTHING"#,
+        );
+        query.build().execute(&mut **transaction).await?;
+    }
+
+    Ok(())
+}
+
+// views and unique visitors of a clip per day, most recent days first
+pub async fn get_daily_views(
     shortcode: &ShortCode,
-    hits: u32,
+    days: u32,
     pool: &DatabasePool,
-) -> Result<()> {
-    let shortcode = shortcode.as_str();
-    Ok(sqlx::query!(
-        "UPDATE clips SET hits = hits + ? WHERE shortcode = ?",
-        hits,
-        shortcode
+) -> Result<Vec<model::DailyViews>> {
+    Ok(sqlx::query_as::<_, model::DailyViews>(
+        r#"SELECT
+            s.day AS day,
+            SUM(s.views) AS views,
+            (SELECT COUNT(*) FROM clip_visitors v
+                WHERE v.shortcode = s.shortcode AND v.day = s.day) AS unique_visitors
+           FROM clip_stats s
+           WHERE s.shortcode = ?
+           GROUP BY s.day
+           ORDER BY s.day DESC
+           LIMIT ?"#,
     )
-    .execute(pool)
-    .await
-    .map(|_| ())?)
+    .bind(shortcode.as_str())
+    .bind(days)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_top_referrers(
+    shortcode: &ShortCode,
+    limit: u32,
+    pool: &DatabasePool,
+) -> Result<Vec<model::ReferrerViews>> {
+    Ok(sqlx::query_as::<_, model::ReferrerViews>(
+        r#"SELECT referrer, SUM(views) AS views FROM clip_stats
+           WHERE shortcode = ? AND referrer != ''
+           GROUP BY referrer
+           ORDER BY views DESC, referrer
+           LIMIT ?"#,
+    )
+    .bind(shortcode.as_str())
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_top_agents(
+    shortcode: &ShortCode,
+    limit: u32,
+    pool: &DatabasePool,
+) -> Result<Vec<model::AgentViews>> {
+    Ok(sqlx::query_as::<_, model::AgentViews>(
+        r#"SELECT agent, SUM(views) AS views FROM clip_stats
+           WHERE shortcode = ?
+           GROUP BY agent
+           ORDER BY views DESC, agent
+           LIMIT ?"#,
+    )
+    .bind(shortcode.as_str())
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
 }
 
 // `get_clip` function accepts a generic type M which should be a model::GetClip
 // Into tries to transform any data that is passed into the function into a model::GetClip
 // and returns a compiler error if it fails to do so
-pub async fn get_clip<M: Into<model::GetClip>>(
+//
+// the content comes from the blob the clip references, clips stored before blobs were added
+// still have it in their own `content`
+pub async fn get_clip<'c, M: Into<model::GetClip>, E: sqlx::SqliteExecutor<'c>>(
     model: M,
-    pool: &DatabasePool,
+    executor: E,
 ) -> Result<model::Clip> {
     let model = model.into();
     let shortcode = model.shortcode.as_str();
     Ok(sqlx::query_as!(
         model::Clip,
-        "SELECT * FROM clips WHERE shortcode = ?",
+        r#"SELECT
+            c.clip_id,
+            c.shortcode,
+            COALESCE(b.content, c.content) AS "content!: Vec<u8>",
+            COALESCE(b.content_encoding, c.content_encoding) AS "content_encoding!: String",
+            c.content_hash,
+            c.title,
+            c.posted,
+            c.expires,
+            c.password,
+            c.hits,
+            c.owner,
+            c.updated,
+            c.visibility,
+            (SELECT GROUP_CONCAT(t.tag, ' ') FROM clip_tags t WHERE t.shortcode = c.shortcode)
+                AS "tags?: String",
+            c.forked_from,
+            (SELECT COUNT(*) FROM clips f WHERE f.forked_from = c.shortcode) AS "forks!: i64",
+            c.takedown,
+            c.takedown_note
+           FROM clips c LEFT JOIN blobs b ON b.hash = c.content_hash
+           WHERE c.shortcode = ?"#,
         shortcode,
     )
-    .fetch_one(pool)
+    .fetch_one(executor)
     .await?)
 }
 
+// the hex encoded SHA-256 of content, the key of its blob
+pub fn content_hash(content: &str) -> String {
+    Sha256::digest(content.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// adds a reference to the blob of `content` and returns its hash. Only content that isn't
+// stored yet is compressed and inserted
+async fn acquire_blob(content: &str, transaction: &mut Transaction<'_>) -> Result<String> {
+    let hash = content_hash(content);
+    let existing = sqlx::query!("UPDATE blobs SET refs = refs + 1 WHERE hash = ?", hash)
+        .execute(&mut **transaction)
+        .await?
+        .rows_affected();
+
+    if existing == 0 {
+        let stored = StoredContent::compress(content);
+        let encoding = stored.encoding.to_string();
+        sqlx::query!(
+            "INSERT INTO blobs (hash, content, content_encoding, refs) VALUES (?, ?, ?, 1)",
+            hash,
+            stored.bytes,
+            encoding
+        )
+        .execute(&mut **transaction)
+        .await?;
+    }
+    Ok(hash)
+}
+
+// unreferenced blobs are deleted by `delete_unreferenced_blobs`
+async fn release_blob(hash: &str, transaction: &mut Transaction<'_>) -> Result<()> {
+    sqlx::query!("UPDATE blobs SET refs = refs - 1 WHERE hash = ?", hash)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(())
+}
+
 pub async fn new_clip<M: Into<model::NewClip>>(
     model: M,
-    pool: &DatabasePool,
+    transaction: &mut Transaction<'_>,
 ) -> Result<model::Clip> {
     let model = model.into();
+    let hash = acquire_blob(&model.content, transaction).await?;
     let _ = sqlx::query!(
         r#"INSERT INTO clips (
             clip_id,
             shortcode,
             content,
+            content_hash,
             title,
             posted,
             expires,
             password,
-            hits)
-           VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
+            hits,
+            owner,
+            updated,
+            visibility,
+            forked_from,
+            size)
+           VALUES (?, ?, X'', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
         model.clip_id,
         model.shortcode,
-        model.content,
+        hash,
         model.title,
         model.posted,
         model.expires,
         model.password,
-        0
+        0,
+        model.owner,
+        model.posted,
+        model.visibility,
+        model.forked_from,
+        model.size
     )
-    .execute(pool)
+    .execute(&mut **transaction)
     .await?;
-    get_clip(model.shortcode, pool).await
+    let tags: Vec<_> = model
+        .tags
+        .iter()
+        .map(|tag| (model.shortcode.as_str(), tag.as_str()))
+        .collect();
+    add_tags(&tags, transaction).await?;
+    get_clip(model.shortcode, &mut **transaction).await
+}
+
+// tags `(shortcode, tag)` of clips created within the caller's transaction
+async fn add_tags(tags: &[(&str, &str)], transaction: &mut Transaction<'_>) -> Result<()> {
+    for chunk in tags.chunks(MAX_ROWS_PER_STATEMENT / 2) {
+        let mut query = QueryBuilder::<Sqlite>::new("INSERT INTO clip_tags (shortcode, tag) ");
+        query.push_values(chunk, |mut row, (shortcode, tag)| {
+            row.push_bind(*shortcode).push_bind(*tag);
+        });
+        query.build().execute(&mut **transaction).await?;
+    }
+    Ok(())
+}
+
+// every clip binds 12 parameters
+const MAX_CLIPS_PER_STATEMENT: usize = MAX_ROWS_PER_STATEMENT / 12;
+
+// inserts all clips within the caller's transaction and returns them in the same order
+pub async fn new_clips<M: Into<model::NewClip>>(
+    models: Vec<M>,
+    transaction: &mut Transaction<'_>,
+) -> Result<Vec<model::Clip>> {
+    let models: Vec<model::NewClip> = models.into_iter().map(Into::into).collect();
+    let mut hashes = Vec::with_capacity(models.len());
+    for model in &models {
+        hashes.push(acquire_blob(&model.content, transaction).await?);
+    }
+
+    let rows: Vec<_> = models.iter().zip(hashes.iter()).collect();
+    for chunk in rows.chunks(MAX_CLIPS_PER_STATEMENT) {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"INSERT INTO clips (
+                clip_id, shortcode, content, content_hash, title, posted, expires, password, hits,
+                owner, updated, visibility, forked_from, size
+            ) "#,
+        );
+        query.push_values(chunk, |mut row, (model, hash)| {
+            row.push_bind(model.clip_id.as_str())
+                .push_bind(model.shortcode.as_str())
+                .push("X''")
+                .push_bind(hash.as_str())
+                .push_bind(model.title.as_deref())
+                .push_bind(model.posted)
+                .push_bind(model.expires)
+                .push_bind(model.password.as_deref())
+                .push_bind(0)
+                .push_bind(model.owner.as_deref())
+                .push_bind(model.posted)
+                .push_bind(model.visibility.as_str())
+                .push_bind(model.forked_from.as_deref())
+                .push_bind(model.size);
+        });
+        query.build().execute(&mut **transaction).await?;
+    }
+    let tags: Vec<_> = models
+        .iter()
+        .flat_map(|model| {
+            model
+                .tags
+                .iter()
+                .map(|tag| (model.shortcode.as_str(), tag.as_str()))
+        })
+        .collect();
+    add_tags(&tags, transaction).await?;
+
+    let shortcodes: Vec<&str> = models
+        .iter()
+        .map(|model| model.shortcode.as_str())
+        .collect();
+    let mut clips = get_clips(&shortcodes, &mut **transaction).await?;
+    clips.sort_by_key(|clip| shortcodes.iter().position(|code| *code == clip.shortcode));
+    Ok(clips)
+}
+
+// fetches every existing clip of `shortcodes` in no particular order, callers keep the number
+// of shortcodes below `MAX_ROWS_PER_STATEMENT`
+pub async fn get_clips<'c, E: sqlx::SqliteExecutor<'c>>(
+    shortcodes: &[&str],
+    executor: E,
+) -> Result<Vec<model::Clip>> {
+    if shortcodes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut query = QueryBuilder::<Sqlite>::new(
+        r#"SELECT
+            c.clip_id,
+            c.shortcode,
+            COALESCE(b.content, c.content) AS content,
+            COALESCE(b.content_encoding, c.content_encoding) AS content_encoding,
+            c.content_hash,
+            c.title,
+            c.posted,
+            c.expires,
+            c.password,
+            c.hits,
+            c.owner,
+            c.updated,
+            c.visibility,
+            (SELECT GROUP_CONCAT(t.tag, ' ') FROM clip_tags t WHERE t.shortcode = c.shortcode)
+                AS tags,
+            c.forked_from,
+            (SELECT COUNT(*) FROM clips f WHERE f.forked_from = c.shortcode) AS forks,
+            c.takedown,
+            c.takedown_note
+           FROM clips c LEFT JOIN blobs b ON b.hash = c.content_hash
+           WHERE c.shortcode IN ("#,
+    );
+    let mut separated = query.separated(", ");
+    for shortcode in shortcodes {
+        separated.push_bind(*shortcode);
+    }
+    separated.push_unseparated(")");
+
+    Ok(query
+        .build_query_as::<model::Clip>()
+        .fetch_all(executor)
+        .await?)
 }
 
+// `update_clip` only applies when the clip still matches `current`, so an update based on a
+// stale read never overwrites a concurrent one. Returns `None` when the clip was changed
 pub async fn update_clip<M: Into<model::UpdateClip>>(
     model: M,
-    pool: &DatabasePool,
-) -> Result<model::Clip> {
+    current: &model::Clip,
+    transaction: &mut Transaction<'_>,
+) -> Result<Option<model::Clip>> {
     let model = model.into();
-    let _ = sqlx::query!(
+    let hash = acquire_blob(&model.content, transaction).await?;
+    let current_expires = current.expires.map(|time| time.and_utc().timestamp());
+
+    // clips stored before blobs were added have no hash and are compared by their content
+    let updated = sqlx::query!(
         r#"UPDATE clips SET
-            content = ?,
+            content = X'',
+            content_hash = ?,
             expires = ?,
             password = ?,
-            title = ?
-           WHERE shortcode = ?"#,
-        model.content,
+            title = ?,
+            updated = ?,
+            visibility = COALESCE(?, visibility),
+            size = ?
+           WHERE shortcode = ?
+            AND content_hash IS ?
+            AND (content_hash IS NOT NULL OR CAST(content AS BLOB) = ?)
+            AND title IS ?
+            AND expires IS ?
+            AND password IS ?
+            AND visibility IS ?"#,
+        hash,
         model.expires,
         model.password,
         model.title,
+        model.updated,
+        model.visibility,
+        model.size,
+        model.shortcode,
+        current.content_hash,
+        current.content,
+        current.title,
+        current_expires,
+        current.password,
+        current.visibility
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
+
+    if updated == 0 {
+        return Ok(None);
+    }
+    if let Some(previous) = &current.content_hash {
+        release_blob(previous, transaction).await?;
+    }
+    Ok(Some(get_clip(model.shortcode, &mut **transaction).await?))
+}
+
+// the most recent public clips without a password that haven't expired yet, only the ones
+// tagged with `tag` when it is set
+pub async fn list_public_clips<M: Into<model::ListClips>>(
+    model: M,
+    pool: &DatabasePool,
+) -> Result<Vec<model::Clip>> {
+    let model = model.into();
+    Ok(sqlx::query_as!(
+        model::Clip,
+        r#"SELECT
+            c.clip_id,
+            c.shortcode,
+            COALESCE(b.content, c.content) AS "content!: Vec<u8>",
+            COALESCE(b.content_encoding, c.content_encoding) AS "content_encoding!: String",
+            c.content_hash,
+            c.title,
+            c.posted,
+            c.expires,
+            c.password,
+            c.hits,
+            c.owner,
+            c.updated,
+            c.visibility,
+            (SELECT GROUP_CONCAT(t.tag, ' ') FROM clip_tags t WHERE t.shortcode = c.shortcode)
+                AS "tags?: String",
+            c.forked_from,
+            (SELECT COUNT(*) FROM clips f WHERE f.forked_from = c.shortcode) AS "forks!: i64",
+            c.takedown,
+            c.takedown_note
+           FROM clips c LEFT JOIN blobs b ON b.hash = c.content_hash
+           WHERE c.visibility = 'public'
+            AND c.password IS NULL
+            AND c.takedown IS NULL
+            AND (c.expires IS NULL OR c.expires >= strftime('%s', 'now'))
+            AND (?1 IS NULL OR EXISTS (
+                SELECT 1 FROM clip_tags t WHERE t.shortcode = c.shortcode AND t.tag = ?1))
+           ORDER BY c.posted DESC, c.shortcode
+           LIMIT ?2 OFFSET ?3"#,
+        model.tag,
+        model.limit,
+        model.offset
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+// the tags used most by public clips without a password that haven't expired yet
+pub async fn get_popular_tags(limit: u32, pool: &DatabasePool) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT t.tag FROM clip_tags t JOIN clips c ON c.shortcode = t.shortcode
+           WHERE c.visibility = 'public'
+            AND c.password IS NULL
+            AND c.takedown IS NULL
+            AND (c.expires IS NULL OR c.expires >= strftime('%s', 'now'))
+           GROUP BY t.tag
+           ORDER BY COUNT(*) DESC, t.tag
+           LIMIT ?"#,
+        limit
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn new_collection<M: Into<model::NewCollection>>(
+    model: M,
+    transaction: &mut Transaction<'_>,
+) -> Result<model::Collection> {
+    let model = model.into();
+    sqlx::query!(
+        r#"INSERT INTO collections (
+            collection_id,
+            shortcode,
+            title,
+            posted,
+            expires,
+            password,
+            owner)
+           VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+        model.collection_id,
+        model.shortcode,
+        model.title,
+        model.posted,
+        model.expires,
+        model.password,
+        model.owner
+    )
+    .execute(&mut **transaction)
+    .await?;
+    set_collection_clips(&model.shortcode, &model.clips, transaction).await?;
+    get_collection(&model.shortcode, &mut **transaction).await
+}
+
+pub async fn get_collection<'c, E: sqlx::SqliteExecutor<'c>>(
+    shortcode: &str,
+    executor: E,
+) -> Result<model::Collection> {
+    Ok(sqlx::query_as!(
+        model::Collection,
+        r#"SELECT shortcode, title, posted, expires, password, owner
+           FROM collections WHERE shortcode = ?"#,
+        shortcode
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+// the clips of the collection in order, without the ones an admin took down
+pub async fn get_collection_clips<'c, E: sqlx::SqliteExecutor<'c>>(
+    shortcode: &str,
+    executor: E,
+) -> Result<Vec<model::Clip>> {
+    Ok(sqlx::query_as!(
+        model::Clip,
+        r#"SELECT
+            c.clip_id,
+            c.shortcode,
+            COALESCE(b.content, c.content) AS "content!: Vec<u8>",
+            COALESCE(b.content_encoding, c.content_encoding) AS "content_encoding!: String",
+            c.content_hash,
+            c.title,
+            c.posted,
+            c.expires,
+            c.password,
+            c.hits,
+            c.owner,
+            c.updated,
+            c.visibility,
+            (SELECT GROUP_CONCAT(t.tag, ' ') FROM clip_tags t WHERE t.shortcode = c.shortcode)
+                AS "tags?: String",
+            c.forked_from,
+            (SELECT COUNT(*) FROM clips f WHERE f.forked_from = c.shortcode) AS "forks!: i64",
+            c.takedown,
+            c.takedown_note
+           FROM collection_clips m
+            JOIN clips c ON c.shortcode = m.shortcode
+            LEFT JOIN blobs b ON b.hash = c.content_hash
+           WHERE m.collection = ? AND c.takedown IS NULL
+           ORDER BY m.position"#,
+        shortcode
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+// replaces the clips of the collection, they are kept in the order of `clips`
+async fn set_collection_clips(
+    collection: &str,
+    clips: &[String],
+    transaction: &mut Transaction<'_>,
+) -> Result<()> {
+    sqlx::query!(
+        "DELETE FROM collection_clips WHERE collection = ?",
+        collection
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    let members: Vec<_> = clips.iter().enumerate().collect();
+    for chunk in members.chunks(MAX_ROWS_PER_STATEMENT / 3) {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "INSERT INTO collection_clips (collection, shortcode, position) ",
+        );
+        query.push_values(chunk, |mut row, (position, shortcode)| {
+            row.push_bind(collection)
+                .push_bind(shortcode.as_str())
+                .push_bind(*position as i64);
+        });
+        query.build().execute(&mut **transaction).await?;
+    }
+    Ok(())
+}
+
+pub async fn update_collection<M: Into<model::UpdateCollection>>(
+    model: M,
+    transaction: &mut Transaction<'_>,
+) -> Result<model::Collection> {
+    let model = model.into();
+    sqlx::query!(
+        r#"UPDATE collections SET title = ?, expires = ?, password = ? WHERE shortcode = ?"#,
+        model.title,
+        model.expires,
+        model.password,
         model.shortcode
     )
-    .execute(pool)
+    .execute(&mut **transaction)
     .await?;
-    get_clip(model.shortcode, pool).await
+    set_collection_clips(&model.shortcode, &model.clips, transaction).await?;
+    get_collection(&model.shortcode, &mut **transaction).await
 }
 
-pub async fn save_api_key(api_key: ApiKey, pool: &DatabasePool) -> Result<ApiKey> {
+// the clips of the collection are kept
+pub async fn delete_collection(shortcode: &str, transaction: &mut Transaction<'_>) -> Result<u64> {
+    Ok(
+        sqlx::query!("DELETE FROM collections WHERE shortcode = ?", shortcode)
+            .execute(&mut **transaction)
+            .await?
+            .rows_affected(),
+    )
+}
+
+// the oldest other clips of `owner` with the same content that haven't expired yet
+pub async fn get_duplicates(
+    hash: &str,
+    owner: &[u8],
+    shortcode: &str,
+    pool: &DatabasePool,
+) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT shortcode FROM clips
+           WHERE content_hash = ?
+            AND owner = ?
+            AND shortcode != ?
+            AND (expires IS NULL OR expires >= strftime('%s', 'now'))
+           ORDER BY posted, shortcode
+           LIMIT 10"#,
+        hash,
+        owner,
+        shortcode
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+// clips that still store their content themselves instead of referencing a blob, ordered by
+// shortcode and starting after `after` so callers can page through them
+pub async fn get_inline_clips(
+    after: &str,
+    limit: u32,
+    pool: &DatabasePool,
+) -> Result<Vec<model::Clip>> {
+    Ok(sqlx::query_as!(
+        model::Clip,
+        r#"SELECT
+            clip_id,
+            shortcode,
+            content AS "content: Vec<u8>",
+            content_encoding,
+            content_hash,
+            title,
+            posted,
+            expires,
+            password,
+            hits,
+            owner,
+            updated,
+            visibility,
+            (SELECT GROUP_CONCAT(t.tag, ' ') FROM clip_tags t WHERE t.shortcode = clips.shortcode)
+                AS "tags?: String",
+            forked_from,
+            (SELECT COUNT(*) FROM clips f WHERE f.forked_from = clips.shortcode) AS "forks!: i64",
+            takedown,
+            takedown_note
+           FROM clips
+           WHERE content_hash IS NULL AND shortcode > ?
+           ORDER BY shortcode
+           LIMIT ?"#,
+        after,
+        limit
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+// moves the content of a clip stored inline into its blob, returns whether the clip was
+// moved. Clips that were changed since they were read are left alone, the caller rolls back
+// the transaction in that case
+pub async fn move_to_blob(
+    clip: &model::Clip,
+    content: &str,
+    transaction: &mut Transaction<'_>,
+) -> Result<bool> {
+    let hash = acquire_blob(content, transaction).await?;
+    let moved = sqlx::query!(
+        r#"UPDATE clips SET content = X'', content_encoding = 'identity', content_hash = ?
+           WHERE shortcode = ? AND content_hash IS NULL AND CAST(content AS BLOB) = ?"#,
+        hash,
+        clip.shortcode,
+        clip.content
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
+    Ok(moved > 0)
+}
+
+pub async fn save_api_key(api_key: ApiKey, transaction: &mut Transaction<'_>) -> Result<ApiKey> {
     let bytes = api_key.clone().into_inner();
-    sqlx::query!("INSERT INTO api_keys (api_key) VALUES (?)", bytes)
-        .execute(pool)
-        .await
-        .map(|_| ())?;
+    let created = chrono::Utc::now().timestamp();
+    sqlx::query!(
+        "INSERT INTO api_keys (api_key, created) VALUES (?, ?)",
+        bytes,
+        created
+    )
+    .execute(&mut **transaction)
+    .await
+    .map(|_| ())?;
 
     Ok(api_key)
 }
 
+// the id of the key shown to the admin and in the audit log
+pub async fn get_api_key_id<'c, E: sqlx::SqliteExecutor<'c>>(
+    api_key: &[u8],
+    executor: E,
+) -> Result<Option<i64>> {
+    Ok(sqlx::query_scalar!(
+        r#"SELECT rowid AS "rowid!" FROM api_keys WHERE api_key = ?"#,
+        api_key
+    )
+    .fetch_optional(executor)
+    .await?)
+}
+
 pub enum RevocationStatus {
     Revoked,
     NotFound,
 }
 
-pub async fn revoke_api_key(api_key: ApiKey, pool: &DatabasePool) -> Result<RevocationStatus> {
+pub async fn revoke_api_key(
+    api_key: ApiKey,
+    transaction: &mut Transaction<'_>,
+) -> Result<RevocationStatus> {
     let bytes = api_key.clone().into_inner();
 
     Ok(
         sqlx::query!("DELETE FROM api_keys WHERE api_key == ?", bytes)
-            .execute(pool)
+            .execute(&mut **transaction)
             .await
             .map(|result| match result.rows_affected() {
                 0 => RevocationStatus::NotFound,
@@ -131,7 +800,7 @@ pub async fn is_api_key_valid(api_key: ApiKey, pool: &DatabasePool) -> Result<bo
     let bytes = api_key.clone().into_inner();
 
     Ok(
-        sqlx::query("SELECT COUNT(api_key) FROM api_keys WHERE api_key = ?")
+        sqlx::query("SELECT COUNT(api_key) FROM api_keys WHERE api_key = ? AND disabled IS NULL")
             .bind(bytes)
             .fetch_one(pool)
             .await
@@ -142,22 +811,416 @@ pub async fn is_api_key_valid(api_key: ApiKey, pool: &DatabasePool) -> Result<bo
     )
 }
 
-pub async fn delete_expired(pool: &DatabasePool) -> Result<u64> {
+// disabling a key twice keeps the time it was first disabled, returns whether the key exists
+pub async fn disable_api_key(id: i64, transaction: &mut Transaction<'_>) -> Result<bool> {
+    let disabled = chrono::Utc::now().timestamp();
+    Ok(sqlx::query!(
+        "UPDATE api_keys SET disabled = COALESCE(disabled, ?) WHERE rowid = ?",
+        disabled,
+        id
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+// the number and size of the clips of `owner`, without the clip `except` when it is set
+pub async fn get_usage<'c, E: sqlx::SqliteExecutor<'c>>(
+    owner: &[u8],
+    except: Option<&str>,
+    executor: E,
+) -> Result<model::Usage> {
+    Ok(sqlx::query_as!(
+        model::Usage,
+        r#"SELECT
+            COUNT(*) AS "clips!: i64",
+            COALESCE(SUM(size), 0) AS "bytes!: i64"
+           FROM clips
+           WHERE owner = ? AND shortcode IS NOT ?"#,
+        owner,
+        except
+    )
+    .fetch_one(executor)
+    .await?)
+}
+
+// clips whose size is unknown, they were stored compressed before sizes were counted. Only the
+// clips of `owner` when it is set
+pub async fn get_unsized_clips<'c, E: sqlx::SqliteExecutor<'c>>(
+    owner: Option<&[u8]>,
+    limit: u32,
+    executor: E,
+) -> Result<Vec<model::UnsizedClip>> {
+    Ok(sqlx::query_as!(
+        model::UnsizedClip,
+        r#"SELECT
+            c.shortcode,
+            COALESCE(b.content, c.content) AS "content!: Vec<u8>",
+            COALESCE(b.content_encoding, c.content_encoding) AS "content_encoding!: String"
+           FROM clips c LEFT JOIN blobs b ON b.hash = c.content_hash
+           WHERE c.size < 0 AND (?1 IS NULL OR c.owner = ?1)
+           LIMIT ?2"#,
+        owner,
+        limit
+    )
+    .fetch_all(executor)
+    .await?)
+}
+
+pub async fn set_clip_size(
+    shortcode: &str,
+    size: i64,
+    transaction: &mut Transaction<'_>,
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE clips SET size = ? WHERE shortcode = ?",
+        size,
+        shortcode
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+pub enum ClipOrder {
+    Recent,
+    Largest,
+    MostHit,
+}
+
+// clips of every owner and visibility, including the ones with a password. The size is the
+// size of the stored content and the owner the id of the API key
+pub async fn get_clip_summaries(
+    order: ClipOrder,
+    limit: u32,
+    pool: &DatabasePool,
+) -> Result<Vec<model::ClipSummary>> {
+    let order = match order {
+        ClipOrder::Recent => "c.posted DESC",
+        ClipOrder::Largest => "size DESC",
+        ClipOrder::MostHit => "c.hits DESC",
+    };
+    Ok(sqlx::query_as::<_, model::ClipSummary>(&format!(
+        r#"SELECT
+            c.shortcode,
+            c.title,
+            c.posted,
+            c.expires,
+            c.hits,
+            LENGTH(COALESCE(b.content, c.content)) AS size,
+            c.visibility,
+            c.password IS NOT NULL AS protected,
+            k.rowid AS owner,
+            c.takedown
+           FROM clips c
+           LEFT JOIN blobs b ON b.hash = c.content_hash
+           LEFT JOIN api_keys k ON k.api_key = c.owner
+           ORDER BY {}, c.shortcode
+           LIMIT ?"#,
+        order
+    ))
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+// the API keys with the clips they created, the ones with the most clips first
+pub async fn get_key_usage(limit: u32, pool: &DatabasePool) -> Result<Vec<model::KeyUsage>> {
+    Ok(sqlx::query_as::<_, model::KeyUsage>(
+        r#"SELECT
+            k.rowid AS id,
+            k.created,
+            k.disabled,
+            COUNT(c.shortcode) AS clips,
+            COALESCE(SUM(MAX(c.size, 0)), 0) AS size,
+            COALESCE(SUM(c.hits), 0) AS hits
+           FROM api_keys k
+           LEFT JOIN clips c ON c.owner = k.api_key
+           GROUP BY k.rowid
+           ORDER BY clips DESC, id
+           LIMIT ?"#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn get_storage_stats(pool: &DatabasePool) -> Result<model::StorageStats> {
+    Ok(sqlx::query_as::<_, model::StorageStats>(
+        r#"SELECT
+            (SELECT COUNT(*) FROM clips) AS clips,
+            (SELECT COUNT(*) FROM collections) AS collections,
+            (SELECT COUNT(*) FROM api_keys) AS api_keys,
+            (SELECT COUNT(*) FROM api_keys WHERE disabled IS NOT NULL) AS disabled_keys,
+            (SELECT COUNT(*) FROM blobs) AS blobs,
+            (SELECT COALESCE(SUM(LENGTH(content)), 0) FROM blobs) AS blob_size,
+            (SELECT COALESCE(SUM(LENGTH(content)), 0) FROM clips) AS inline_size,
+            (SELECT COALESCE(SUM(refs - 1), 0) FROM blobs WHERE refs > 1) AS shared_blobs"#,
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+// the content of the clip is released, its blob is deleted by `delete_unreferenced_blobs`.
+// Returns the number of deleted clips
+pub async fn delete_clip(shortcode: &str, transaction: &mut Transaction<'_>) -> Result<u64> {
+    let hash = sqlx::query_scalar::<_, Option<String>>(
+        "SELECT content_hash FROM clips WHERE shortcode = ?",
+    )
+    .bind(shortcode)
+    .fetch_optional(&mut **transaction)
+    .await?
+    .flatten();
+    if let Some(hash) = hash {
+        release_blob(&hash, transaction).await?;
+    }
+
+    Ok(
+        sqlx::query!("DELETE FROM clips WHERE shortcode = ?", shortcode)
+            .execute(&mut **transaction)
+            .await?
+            .rows_affected(),
+    )
+}
+
+pub async fn new_report<M: Into<model::NewReport>>(model: M, pool: &DatabasePool) -> Result<i64> {
+    let model = model.into();
+    Ok(sqlx::query!(
+        "INSERT INTO reports (shortcode, reason, contact, filed) VALUES (?, ?, ?, ?)",
+        model.shortcode,
+        model.reason,
+        model.contact,
+        model.filed
+    )
+    .execute(pool)
+    .await?
+    .last_insert_rowid())
+}
+
+// the reports no admin resolved yet, the oldest first
+pub async fn get_open_reports(limit: u32, pool: &DatabasePool) -> Result<Vec<model::Report>> {
+    Ok(sqlx::query_as::<_, model::Report>(
+        r#"SELECT r.report_id, r.shortcode, c.title, r.reason, r.contact, r.filed
+           FROM reports r JOIN clips c ON c.shortcode = r.shortcode
+           WHERE r.resolved IS NULL
+           ORDER BY r.filed, r.report_id
+           LIMIT ?"#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+// resolves the open reports of a clip, returns the number of resolved reports
+pub async fn resolve_reports(shortcode: &str, transaction: &mut Transaction<'_>) -> Result<u64> {
+    let resolved = chrono::Utc::now().timestamp();
+    Ok(sqlx::query!(
+        "UPDATE reports SET resolved = ? WHERE shortcode = ? AND resolved IS NULL",
+        resolved,
+        shortcode
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected())
+}
+
+// resolves a single report without acting on the clip, returns the shortcode of the clip when
+// the report was open
+pub async fn dismiss_report(id: i64, transaction: &mut Transaction<'_>) -> Result<Option<String>> {
+    let resolved = chrono::Utc::now().timestamp();
+    Ok(sqlx::query_scalar::<_, String>(
+        r#"UPDATE reports SET resolved = ? WHERE report_id = ? AND resolved IS NULL
+           RETURNING shortcode"#,
+    )
+    .bind(resolved)
+    .bind(id)
+    .fetch_optional(&mut **transaction)
+    .await?)
+}
+
+// taking a clip down again replaces the reason and note, returns the number of changed clips
+pub async fn take_down_clip(
+    shortcode: &str,
+    reason: &str,
+    note: &str,
+    transaction: &mut Transaction<'_>,
+) -> Result<u64> {
+    Ok(sqlx::query!(
+        "UPDATE clips SET takedown = ?, takedown_note = ? WHERE shortcode = ?",
+        reason,
+        note,
+        shortcode
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected())
+}
+
+// returns the number of clips that were taken down and are served again
+pub async fn restore_clip(shortcode: &str, transaction: &mut Transaction<'_>) -> Result<u64> {
+    Ok(sqlx::query!(
+        r#"UPDATE clips SET takedown = NULL, takedown_note = NULL
+           WHERE shortcode = ? AND takedown IS NOT NULL"#,
+        shortcode
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected())
+}
+
+pub async fn add_moderation_entry<M: Into<model::NewModerationEntry>>(
+    model: M,
+    transaction: &mut Transaction<'_>,
+) -> Result<()> {
+    let model = model.into();
+    sqlx::query!(
+        r#"INSERT INTO moderation_log (time, action, shortcode, report_id, key_id, note)
+           VALUES (?, ?, ?, ?, ?, ?)"#,
+        model.time,
+        model.action,
+        model.shortcode,
+        model.report_id,
+        model.key_id,
+        model.note
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+// the most recent moderation actions first
+pub async fn get_moderation_log(
+    limit: u32,
+    pool: &DatabasePool,
+) -> Result<Vec<model::ModerationEntry>> {
+    Ok(sqlx::query_as::<_, model::ModerationEntry>(
+        r#"SELECT entry_id, time, action, shortcode, report_id, key_id, note
+           FROM moderation_log
+           ORDER BY entry_id DESC
+           LIMIT ?"#,
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+// the id of the key of the actor is looked up here, entries of unknown keys have none
+pub async fn add_audit_entry<M: Into<model::NewAuditEntry>>(
+    model: M,
+    transaction: &mut Transaction<'_>,
+) -> Result<()> {
+    let model = model.into();
+    sqlx::query!(
+        r#"INSERT INTO audit_log (time, action, shortcode, key_id, admin, ip, target_key_id)
+           VALUES (?, ?, ?, (SELECT rowid FROM api_keys WHERE api_key = ?), ?, ?, ?)"#,
+        model.time,
+        model.action,
+        model.shortcode,
+        model.key,
+        model.admin,
+        model.ip,
+        model.target_key_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+// the entries matching every filter that is set, the most recent first
+pub async fn get_audit_log<M: Into<model::AuditQuery>>(
+    model: M,
+    pool: &DatabasePool,
+) -> Result<Vec<model::AuditEntry>> {
+    let model = model.into();
+    Ok(sqlx::query_as::<_, model::AuditEntry>(
+        r#"SELECT entry_id, time, action, shortcode, key_id, admin, ip, target_key_id
+           FROM audit_log
+           WHERE (?1 IS NULL OR action = ?1)
+            AND (?2 IS NULL OR shortcode = ?2)
+            AND (?3 IS NULL OR key_id = ?3 OR target_key_id = ?3)
+            AND (?4 IS NULL OR ip = ?4)
+            AND (?5 IS NULL OR time >= ?5)
+            AND (?6 IS NULL OR time < ?6)
+            AND (?7 IS NULL OR entry_id < ?7)
+           ORDER BY entry_id DESC
+           LIMIT ?8"#,
+    )
+    .bind(model.action)
+    .bind(model.shortcode)
+    .bind(model.key_id)
+    .bind(model.ip)
+    .bind(model.since)
+    .bind(model.until)
+    .bind(model.before)
+    .bind(model.limit)
+    .fetch_all(pool)
+    .await?)
+}
+
+pub async fn ping(pool: &DatabasePool) -> Result<()> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+// versions of the migrations that were successfully applied to the database
+pub async fn applied_migrations(pool: &DatabasePool) -> Result<Vec<i64>> {
+    Ok(
+        sqlx::query("SELECT version FROM _sqlx_migrations WHERE success = TRUE ORDER BY version")
+            .fetch_all(pool)
+            .await?
+            .iter()
+            .map(|row| row.get(0))
+            .collect(),
+    )
+}
+
+pub async fn delete_expired(transaction: &mut Transaction<'_>) -> Result<u64> {
+    // specific to sqlite - `strftime` gets the current time
+    sqlx::query!(
+        r#"UPDATE blobs SET refs = refs - (
+            SELECT COUNT(*) FROM clips
+            WHERE clips.content_hash = blobs.hash AND strftime('%s', 'now') > clips.expires)
+           WHERE hash IN (
+            SELECT content_hash FROM clips WHERE strftime('%s', 'now') > expires)"#
+    )
+    .execute(&mut **transaction)
+    .await?;
+
     Ok(
-        // specific to sqlite - `strftime` gets the current time
         sqlx::query!(r#"DELETE FROM clips WHERE strftime('%s', 'now') > expires"#)
-            .execute(pool)
+            .execute(&mut **transaction)
             .await?
             .rows_affected(),
     )
 }
 
+// the clips of expired collections are kept, they only expire with the collection when they are
+// read through it
+pub async fn delete_expired_collections(transaction: &mut Transaction<'_>) -> Result<u64> {
+    Ok(
+        sqlx::query!(r#"DELETE FROM collections WHERE strftime('%s', 'now') > expires"#)
+            .execute(&mut **transaction)
+            .await?
+            .rows_affected(),
+    )
+}
+
+// deletes the blobs no clip refers to anymore
+pub async fn delete_unreferenced_blobs(transaction: &mut Transaction<'_>) -> Result<u64> {
+    Ok(sqlx::query!("DELETE FROM blobs WHERE refs <= 0")
+        .execute(&mut **transaction)
+        .await?
+        .rows_affected())
+}
+
 #[cfg(test)]
 pub mod test {
     use chrono::Utc;
 
     use crate::data::test::*;
     use crate::data::*;
+    use crate::ShortCode;
 
     use crate::test::new_async_runtime;
 
@@ -172,10 +1235,15 @@ pub mod test {
             shortcode: shortcode.into(),
             clip_id: DbId::new().into(),
             content: format!("content for clip '{}'", shortcode),
+            size: 0,
             title: None,
             posted: Utc::now().timestamp(),
             expires: None,
             password: None,
+            owner: None,
+            visibility: "unlisted".to_owned(),
+            tags: vec![],
+            forked_from: None,
         }
     }
 
@@ -185,12 +1253,226 @@ pub mod test {
         let db = new_db(rt.handle());
         let pool = db.get_pool();
 
-        let clip = rt.block_on(async move { super::new_clip(model_new_clip("1"), pool).await });
+        let clip = rt.block_on(async move {
+            let mut transaction = pool.begin().await.unwrap();
+            let clip = super::new_clip(model_new_clip("1"), &mut transaction).await;
+            transaction.commit().await.unwrap();
+            clip
+        });
 
         assert!(clip.is_ok());
 
         let clip = clip.unwrap();
         assert!(clip.shortcode == "1");
-        assert!(clip.content == format!("content for clip '1'"));
+        assert!(clip.content == b"content for clip '1'");
+    }
+
+    #[test]
+    fn sizes_of_older_compressed_clips_are_counted_uncompressed() {
+        let rt = new_async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            let log = "WARN retrying request\n".repeat(1000);
+            let mut transaction = pool.begin().await.unwrap();
+            for shortcode in ["old", "new"] {
+                let mut model = model_new_clip(shortcode);
+                model.content = log.clone();
+                model.size = log.len() as i64;
+                model.owner = Some(b"key".to_vec());
+                super::new_clip(model, &mut transaction).await.unwrap();
+            }
+            transaction.commit().await.unwrap();
+
+            // the first size migration counted the compressed content of older clips
+            sqlx::query(
+                r#"UPDATE clips SET size =
+                    (SELECT LENGTH(b.content) FROM blobs b WHERE b.hash = clips.content_hash)
+                   WHERE shortcode = 'old'"#,
+            )
+            .execute(pool)
+            .await
+            .unwrap();
+            sqlx::query(include_str!(
+                "../../../migrations/20261019000000_clip_size_uncompressed.sql"
+            ))
+            .execute(pool)
+            .await
+            .unwrap();
+
+            let pending = super::get_unsized_clips(Some(b"key"), 10, pool)
+                .await
+                .unwrap();
+            assert_eq!(pending.len(), 1);
+            assert_eq!(pending[0].shortcode(), "old");
+            assert_eq!(pending[0].size(), log.len() as i64);
+
+            let mut transaction = pool.begin().await.unwrap();
+            super::set_clip_size("old", pending[0].size(), &mut transaction)
+                .await
+                .unwrap();
+            transaction.commit().await.unwrap();
+            let usage = super::get_usage(b"key", None, pool).await.unwrap();
+            assert_eq!(usage.bytes, 2 * log.len() as i64);
+        });
+    }
+
+    #[test]
+    fn identical_content_shares_a_blob() {
+        let rt = new_async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            // clips written before blobs were added have their content inline
+            let log = "WARN retrying request\n".repeat(1000);
+            sqlx::query(
+                r#"INSERT INTO clips (clip_id, shortcode, content, posted, expires, hits)
+                   VALUES (?, 'old', ?, 0, 1, 0)"#,
+            )
+            .bind(String::from(DbId::new()))
+            .bind(log.as_str())
+            .execute(pool)
+            .await
+            .unwrap();
+
+            let mut transaction = pool.begin().await.unwrap();
+            let mut model = model_new_clip("new");
+            model.content = log.clone();
+            super::new_clip(model, &mut transaction).await.unwrap();
+            transaction.commit().await.unwrap();
+
+            let clips = super::get_inline_clips("", 10, pool).await.unwrap();
+            assert_eq!(clips.len(), 1);
+            let mut transaction = pool.begin().await.unwrap();
+            assert!(super::move_to_blob(&clips[0], &log, &mut transaction)
+                .await
+                .unwrap());
+            transaction.commit().await.unwrap();
+            assert!(super::get_inline_clips("", 10, pool)
+                .await
+                .unwrap()
+                .is_empty());
+
+            let blobs: Vec<(i64, String)> =
+                sqlx::query_as("SELECT refs, content_encoding FROM blobs")
+                    .fetch_all(pool)
+                    .await
+                    .unwrap();
+            assert_eq!(blobs, vec![(2, "zstd".to_owned())]);
+
+            let clip = super::get_clip(model_get_clip("old"), pool).await.unwrap();
+            let clip = crate::Clip::try_from(clip).unwrap();
+            assert_eq!(clip.content.as_str(), log);
+
+            // the blob stays while the new clip refers to it
+            let mut transaction = pool.begin().await.unwrap();
+            assert_eq!(super::delete_expired(&mut transaction).await.unwrap(), 1);
+            assert_eq!(
+                super::delete_unreferenced_blobs(&mut transaction)
+                    .await
+                    .unwrap(),
+                0
+            );
+            sqlx::query("UPDATE clips SET expires = 1")
+                .execute(&mut *transaction)
+                .await
+                .unwrap();
+            assert_eq!(super::delete_expired(&mut transaction).await.unwrap(), 1);
+            assert_eq!(
+                super::delete_unreferenced_blobs(&mut transaction)
+                    .await
+                    .unwrap(),
+                1
+            );
+            transaction.commit().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn hit_counts_increase_in_batched_statements() {
+        let rt = new_async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            let mut transaction = pool.begin().await.unwrap();
+            for shortcode in ["1", "2", "3"] {
+                super::new_clip(model_new_clip(shortcode), &mut transaction)
+                    .await
+                    .unwrap();
+            }
+            transaction.commit().await.unwrap();
+
+            let hits = vec![
+                (ShortCode::from("1"), 3),
+                (ShortCode::from("2"), 5),
+                (ShortCode::from("missing"), 7),
+            ];
+            let mut transaction = pool.begin().await.unwrap();
+            let updated = super::increase_hit_counts(&hits, &mut transaction)
+                .await
+                .unwrap();
+            transaction.commit().await.unwrap();
+            assert_eq!(updated, 2);
+
+            for (shortcode, expected) in [("1", 3), ("2", 5), ("3", 0)] {
+                let clip = super::get_clip(model_get_clip(shortcode), pool)
+                    .await
+                    .unwrap();
+                assert_eq!(clip.hits, expected);
+            }
+
+            // more clips than the parameters of one statement allow are split over several
+            let mut hits = (0..12_000)
+                .map(|i| (ShortCode::from(format!("missing{}", i)), 1))
+                .collect::<Vec<_>>();
+            hits.push((ShortCode::from("3"), 2));
+            let mut transaction = pool.begin().await.unwrap();
+            let updated = super::increase_hit_counts(&hits, &mut transaction)
+                .await
+                .unwrap();
+            transaction.commit().await.unwrap();
+            assert_eq!(updated, 1);
+            let clip = super::get_clip(model_get_clip("3"), pool).await.unwrap();
+            assert_eq!(clip.hits, 2);
+        });
+    }
+
+    #[test]
+    fn audit_entries_cannot_be_changed() {
+        use crate::domain::audit::{Actor, AuditAction};
+        use crate::service::ask;
+
+        let rt = new_async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool();
+
+        rt.block_on(async move {
+            let mut transaction = pool.begin().await.unwrap();
+            let entry = ask::Audit {
+                shortcode: Some(ShortCode::from("1")),
+                ..ask::Audit::new(AuditAction::Create, &Actor::default())
+            };
+            super::add_audit_entry(entry, &mut transaction)
+                .await
+                .unwrap();
+            transaction.commit().await.unwrap();
+
+            assert!(sqlx::query("UPDATE audit_log SET shortcode = '2'")
+                .execute(pool)
+                .await
+                .is_err());
+            assert!(sqlx::query("DELETE FROM audit_log")
+                .execute(pool)
+                .await
+                .is_err());
+            let query = ask::AuditQuery {
+                limit: 10,
+                ..Default::default()
+            };
+            assert_eq!(super::get_audit_log(query, pool).await.unwrap().len(), 1);
+        });
     }
 }
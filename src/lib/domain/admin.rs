@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::clip::field::{TakedownReason, Visibility};
+use crate::{ShortCode, Time};
+
+// ClipSummary describes a clip to the operator of the instance, without its content
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipSummary {
+    pub shortcode: ShortCode,
+    pub title: Option<String>,
+    pub posted: Time,
+    pub expires: Option<Time>,
+    pub hits: u64,
+    // the size of the stored content, compressed content counts with its compressed size
+    pub size: u64,
+    pub visibility: Visibility,
+    pub protected: bool,
+    // the id of the API key that created the clip
+    pub owner: Option<i64>,
+    pub takedown: Option<TakedownReason>,
+}
+
+// KeyUsage is what the clips created with an API key take up. Keys are only shown by their
+// id, the keys themselves never leave the server
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyUsage {
+    pub id: i64,
+    pub created: Option<Time>,
+    pub disabled: Option<Time>,
+    pub clips: u64,
+    pub size: u64,
+    pub hits: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub clips: u64,
+    pub collections: u64,
+    pub api_keys: u64,
+    pub disabled_keys: u64,
+    pub blobs: u64,
+    pub blob_size: u64,
+    // content of clips stored before blobs were added
+    pub inline_size: u64,
+    // the references to blobs that are shared with another clip
+    pub shared_blobs: u64,
+}
+
+// Dashboard is everything the admin page shows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dashboard {
+    pub recent: Vec<ClipSummary>,
+    pub largest: Vec<ClipSummary>,
+    pub most_hit: Vec<ClipSummary>,
+    pub keys: Vec<KeyUsage>,
+    pub storage: StorageStats,
+}
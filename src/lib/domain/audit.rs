@@ -0,0 +1,66 @@
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use thiserror::Error;
+
+use crate::domain::clip::field::Owner;
+use crate::{ShortCode, Time};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Unlock,
+    GenerateKey,
+    RevokeKey,
+    // an admin disabled the key, owners revoke their own
+    DisableKey,
+    TakeDown,
+    Restore,
+    DismissReport,
+    CreateCollection,
+    UpdateCollection,
+    DeleteCollection,
+}
+
+#[derive(Debug, Error)]
+pub enum AuditError {
+    #[error("unknown audit action `{0}`")]
+    UnknownAction(String),
+}
+
+// Actor is who made a request: the API key it was made with, if any, whether it came from the
+// admin pages and the address of the client when it is known
+#[derive(Debug, Clone, Default)]
+pub struct Actor {
+    pub key: Owner,
+    pub admin: bool,
+    pub ip: Option<IpAddr>,
+}
+
+impl Actor {
+    pub fn admin(self) -> Self {
+        Self {
+            admin: true,
+            ..self
+        }
+    }
+}
+
+// AuditEntry records an action and who made it. `key` is the id of the API key the action was
+// made with and `target_key` the id of the key a key action is about
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub time: Time,
+    pub action: AuditAction,
+    pub shortcode: Option<ShortCode>,
+    pub key: Option<i64>,
+    pub admin: bool,
+    pub ip: Option<String>,
+    pub target_key: Option<i64>,
+}
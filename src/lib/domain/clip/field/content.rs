@@ -1,13 +1,19 @@
 use crate::domain::clip::ClipError;
+use crate::domain::limits::Limits;
 use rocket::form::{self, FromFormField, ValueField};
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
 
 // The String property of Content type is private
 // it would have a pub keyword in front of it if it would be public
 // we can access the Component from anywhere
 // we can access the String only from the impl block for the struct
+//
+// try_from makes serde validate the content the same way `Content::new` does
+#[serde(try_from = "String")]
+#[schema(value_type = String, example = "cargo build failed: ...")]
 pub struct Content(String);
 
 // The reason we create a new type for every field in the structure
@@ -16,13 +22,22 @@ pub struct Content(String);
 impl Content {
     // `new` method
     pub fn new(content: &str) -> Result<Self, ClipError> {
-        if !content.trim().is_empty() {
+        let max = Limits::current().max_content_size;
+        if content.len() > max {
+            Err(ClipError::ContentTooLarge(max))
+        } else if !content.trim().is_empty() {
             Ok(Self(content.to_owned()))
         } else {
             Err(ClipError::EmptyContent)
         }
     }
 
+    // content read from the database was checked when it was stored, clips stored before the
+    // limit was lowered stay readable
+    pub fn stored(content: String) -> Self {
+        Self(content)
+    }
+
     // into_inner is commonly used in the Rust ecosystem to transform the content
     // of a struct into the returnable type
     // will remove the instance of the Content after execution
@@ -36,6 +51,13 @@ impl Content {
     }
 }
 
+impl TryFrom<String> for Content {
+    type Error = ClipError;
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value.as_str())
+    }
+}
+
 #[rocket::async_trait]
 impl<'r> FromFormField<'r> for Content {
     fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
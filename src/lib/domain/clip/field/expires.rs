@@ -3,8 +3,9 @@ use crate::domain::time::Time;
 use rocket::form::{self, FromFormField, ValueField};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use utoipa::ToSchema;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
 pub struct Expires(Option<Time>);
 
 impl Expires {
@@ -15,6 +16,22 @@ impl Expires {
     pub fn into_inner(self) -> Option<Time> {
         self.0
     }
+
+    // the expiration date the way it is entered in forms, `YYYY-MM-DD`
+    pub fn date(&self) -> Option<String> {
+        self.0
+            .as_ref()
+            .map(|time| time.clone().into_inner().format("%Y-%m-%d").to_string())
+    }
+
+    // the earlier of the two expirations, never expiring is the latest
+    pub fn earliest(self, other: Expires) -> Self {
+        match (&self.0, &other.0) {
+            (Some(a), Some(b)) if b.timestamp() < a.timestamp() => other,
+            (None, _) => other,
+            _ => self,
+        }
+    }
 }
 
 impl Default for Expires {
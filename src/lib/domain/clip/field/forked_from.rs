@@ -0,0 +1,41 @@
+use super::ShortCode;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// the clip a clip was forked from, `None` for clips that were written from scratch or whose
+// original was deleted
+#[derive(Debug, Clone, Default, Deserialize, Serialize, ToSchema)]
+#[schema(value_type = Option<String>, example = "aB3dE9x")]
+pub struct ForkedFrom(Option<ShortCode>);
+
+impl ForkedFrom {
+    pub fn new<T: Into<Option<ShortCode>>>(shortcode: T) -> Self {
+        Self(shortcode.into())
+    }
+
+    pub fn into_inner(self) -> Option<ShortCode> {
+        self.0
+    }
+
+    pub fn as_shortcode(&self) -> Option<&ShortCode> {
+        self.0.as_ref()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for ForkedFrom {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        let shortcode = field.value.trim();
+        if shortcode.is_empty() {
+            Ok(Self(None))
+        } else {
+            Ok(Self(Some(ShortCode::from(shortcode))))
+        }
+    }
+
+    // the home page only sends the field when forking a clip
+    fn default() -> Option<Self> {
+        Some(<Self as Default>::default())
+    }
+}
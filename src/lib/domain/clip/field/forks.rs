@@ -0,0 +1,13 @@
+use derive_more::Constructor;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// the number of clips forked from a clip
+#[derive(Clone, Constructor, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct Forks(u64);
+
+impl Forks {
+    pub fn into_inner(self) -> u64 {
+        self.0
+    }
+}
@@ -1,8 +1,9 @@
 // Constructor creates a public `new` method which returns the structures inner value
 use derive_more::Constructor; // returns the structure with the inner value
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Clone, Constructor, Debug, Serialize, Deserialize)]
+#[derive(Clone, Constructor, Debug, Serialize, Deserialize, ToSchema)]
 pub struct Hits(u64);
 
 impl Hits {
@@ -13,6 +13,9 @@ pub use title::Title;
 mod posted;
 pub use posted::Posted;
 
+mod updated;
+pub use updated::Updated;
+
 mod expires;
 pub use expires::Expires;
 
@@ -21,3 +24,21 @@ pub use password::Password;
 
 mod hits;
 pub use hits::Hits;
+
+mod owner;
+pub use owner::Owner;
+
+mod visibility;
+pub use visibility::Visibility;
+
+mod tags;
+pub use tags::Tags;
+
+mod forked_from;
+pub use forked_from::ForkedFrom;
+
+mod forks;
+pub use forks::Forks;
+
+mod takedown;
+pub use takedown::{Takedown, TakedownReason};
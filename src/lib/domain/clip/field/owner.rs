@@ -0,0 +1,26 @@
+// Owner holds the raw API key that created the clip. It is never sent to the users
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Owner(Option<Vec<u8>>);
+
+impl Owner {
+    pub fn new<T: Into<Option<Vec<u8>>>>(owner: T) -> Self {
+        Self(owner.into())
+    }
+
+    pub fn into_inner(self) -> Option<Vec<u8>> {
+        self.0
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        self.0.as_deref()
+    }
+
+    pub fn has_owner(&self) -> bool {
+        self.0.is_some()
+    }
+
+    // clips without an owner are not owned by anyone
+    pub fn is_owned_by(&self, key: &[u8]) -> bool {
+        self.0.as_deref() == Some(key)
+    }
+}
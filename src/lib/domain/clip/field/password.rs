@@ -2,10 +2,11 @@ use crate::domain::clip::ClipError;
 use rocket::form::{self, FromFormField, ValueField};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use utoipa::ToSchema;
 
 // PartialEq will provide access to the == operation
 // PartialOrd is necessary to use the PartialEq
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, PartialOrd, Default)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, PartialOrd, Default, ToSchema)]
 pub struct Password(Option<String>);
 
 impl Password {
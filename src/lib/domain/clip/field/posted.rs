@@ -1,8 +1,9 @@
 use crate::domain::time::Time;
 use derive_more::Constructor;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Clone, Constructor, Debug, Serialize, Deserialize)]
+#[derive(Clone, Constructor, Debug, Serialize, Deserialize, ToSchema)]
 pub struct Posted(Time);
 
 impl Posted {
@@ -4,10 +4,22 @@ use rocket::request::FromParam;
 use rocket::{UriDisplayPath, UriDisplayQuery};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use utoipa::ToSchema;
 
 #[derive(
-    Debug, Clone, Deserialize, Serialize, From, UriDisplayQuery, UriDisplayPath, Hash, Eq, PartialEq,
+    Debug,
+    Clone,
+    Deserialize,
+    Serialize,
+    From,
+    UriDisplayQuery,
+    UriDisplayPath,
+    Hash,
+    Eq,
+    PartialEq,
+    ToSchema,
 )]
+#[schema(value_type = String, example = "aB3dE9x")]
 pub struct ShortCode(String);
 
 impl ShortCode {
@@ -0,0 +1,98 @@
+use crate::domain::clip::ClipError;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+pub const MAX_TAGS: usize = 10;
+pub const MAX_TAG_LENGTH: usize = 32;
+
+// Tags are short lowercase labels like `rust` or `ci-logs`. They are kept sorted and without
+// duplicates so the same set of tags is always stored and shown the same way
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(try_from = "Vec<String>")]
+#[schema(value_type = Vec<String>, example = json!(["rust", "ci-logs"]))]
+pub struct Tags(Vec<String>);
+
+impl Tags {
+    pub fn new<I: IntoIterator<Item = String>>(tags: I) -> Result<Self, ClipError> {
+        let mut valid = vec![];
+        for tag in tags {
+            valid.push(Self::tag(&tag)?);
+        }
+        valid.sort();
+        valid.dedup();
+
+        if valid.len() > MAX_TAGS {
+            return Err(ClipError::InvalidTags(format!(
+                "a clip can have at most {} tags",
+                MAX_TAGS
+            )));
+        }
+        Ok(Self(valid))
+    }
+
+    // validates a single tag and returns it normalized, `#Rust` becomes `rust`
+    pub fn tag(tag: &str) -> Result<String, ClipError> {
+        let tag = tag.trim().trim_start_matches('#').to_lowercase();
+        if tag.is_empty() || tag.len() > MAX_TAG_LENGTH {
+            return Err(ClipError::InvalidTags(format!(
+                "tags must be between 1 and {} characters long",
+                MAX_TAG_LENGTH
+            )));
+        }
+        if !tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        {
+            return Err(ClipError::InvalidTags(format!(
+                "`{}` may only contain letters, digits, `-`, `_` and `.`",
+                tag
+            )));
+        }
+        Ok(tag)
+    }
+
+    pub fn into_inner(self) -> Vec<String> {
+        self.0
+    }
+
+    pub fn as_slice(&self) -> &[String] {
+        self.0.as_slice()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl TryFrom<Vec<String>> for Tags {
+    type Error = ClipError;
+    fn try_from(value: Vec<String>) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+// tags are written separated by commas or spaces, e.g. `rust, ci-logs`
+impl FromStr for Tags {
+    type Err = ClipError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(
+            s.split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|tag| !tag.is_empty())
+                .map(ToOwned::to_owned),
+        )
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for Tags {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::from_str(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+
+    // forms without the field create clips without tags
+    fn default() -> Option<Self> {
+        Some(<Self as Default>::default())
+    }
+}
@@ -0,0 +1,46 @@
+use crate::domain::report::ReportError;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use strum::{Display, EnumString};
+
+// TakedownReason is why an admin disabled a clip. Legal takedowns are answered with
+// 451 Unavailable For Legal Reasons, the others with 410 Gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum TakedownReason {
+    Abuse,
+    Legal,
+}
+
+impl TakedownReason {
+    pub fn new(reason: &str) -> Result<Self, ReportError> {
+        Self::from_str(reason.trim()).map_err(|_| {
+            ReportError::InvalidTakedown(format!("`{}` is not one of abuse or legal", reason))
+        })
+    }
+
+    // the database only holds reasons checked by `new`, anything else still keeps the clip down
+    pub fn stored(reason: &str) -> Self {
+        Self::from_str(reason).unwrap_or(Self::Abuse)
+    }
+
+    pub fn is_legal(&self) -> bool {
+        *self == Self::Legal
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for TakedownReason {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::new(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+}
+
+// Takedown is set on the clips an admin disabled, the note is shown instead of the content
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Takedown {
+    pub reason: TakedownReason,
+    pub note: String,
+}
@@ -1,9 +1,13 @@
 use crate::domain::clip::ClipError;
+use crate::domain::limits::Limits;
 use rocket::form::{self, FromFormField, ValueField};
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+// try_from makes serde check the length the same way `Title::parse` does
+#[serde(try_from = "Option<String>")]
 pub struct Title(Option<String>);
 
 impl Title {
@@ -27,11 +31,32 @@ impl Title {
         }
     }
 
+    // parse checks the length of a title entered by a user, `new` accepts titles that are
+    // already stored
+    pub fn parse<T: Into<Option<String>>>(title: T) -> Result<Self, ClipError> {
+        let title = Self::new(title);
+        let max = Limits::current().max_title_length;
+        match &title.0 {
+            Some(text) if text.chars().count() > max => Err(ClipError::InvalidTitle(format!(
+                "the title can be at most {} characters long",
+                max
+            ))),
+            _ => Ok(title),
+        }
+    }
+
     pub fn into_inner(self) -> Option<String> {
         self.0
     }
 }
 
+impl TryFrom<Option<String>> for Title {
+    type Error = ClipError;
+    fn try_from(value: Option<String>) -> Result<Self, Self::Error> {
+        Self::parse(value)
+    }
+}
+
 impl Default for Title {
     fn default() -> Self {
         Self::new(None)
@@ -41,13 +66,14 @@ impl Default for Title {
 impl FromStr for Title {
     type Err = ClipError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self::new(s.to_string()))
+        Self::parse(s.to_string())
     }
 }
 
 #[rocket::async_trait]
 impl<'r> FromFormField<'r> for Title {
     fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
-        Ok(Self::new(field.value.to_owned()))
+        Ok(Self::parse(field.value.to_owned())
+            .map_err(|e| form::Error::validation(format!("{}", e)))?)
     }
 }
@@ -0,0 +1,13 @@
+use crate::domain::time::Time;
+use derive_more::Constructor;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Constructor, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Updated(Time);
+
+impl Updated {
+    pub fn into_inner(self) -> Time {
+        self.0
+    }
+}
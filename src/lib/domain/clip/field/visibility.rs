@@ -0,0 +1,65 @@
+use crate::domain::clip::ClipError;
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use strum::{Display, EnumString};
+use utoipa::ToSchema;
+
+// Visibility decides who can find a clip. Public clips appear in listings, unlisted clips can
+// only be opened with their link and private clips only by the API key that owns them
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Display,
+    EnumString,
+    ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Visibility {
+    Public,
+    // clips have always been reachable by anyone with the link, it stays the default
+    #[default]
+    Unlisted,
+    Private,
+}
+
+impl Visibility {
+    pub fn new(visibility: &str) -> Result<Self, ClipError> {
+        Self::from_str(visibility.trim()).map_err(|_| {
+            ClipError::InvalidVisibility(format!(
+                "`{}` is not one of public, unlisted or private",
+                visibility
+            ))
+        })
+    }
+
+    pub fn is_public(&self) -> bool {
+        *self == Self::Public
+    }
+
+    pub fn is_private(&self) -> bool {
+        *self == Self::Private
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for Visibility {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        if field.value.trim().is_empty() {
+            return Ok(<Self as Default>::default());
+        }
+        Ok(Self::new(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+
+    // forms without the field create unlisted clips
+    fn default() -> Option<Self> {
+        Some(<Self as Default>::default())
+    }
+}
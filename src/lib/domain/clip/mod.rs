@@ -1,7 +1,11 @@
 pub mod field;
 
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::domain::secret::Finding;
 
 #[derive(Debug, Error)]
 pub enum ClipError {
@@ -11,6 +15,8 @@ pub enum ClipError {
     InvalidTitle(String),
     #[error("no content")]
     EmptyContent,
+    #[error("the content is larger than {0} bytes")]
+    ContentTooLarge(usize),
     #[error("invalid date: {0}")]
     InvalidDate(String),
     #[error("failed to parse date: {0}")]
@@ -19,17 +25,171 @@ pub enum ClipError {
     Id(#[from] uuid::Error),
     #[error("invalid hits")]
     Hits(#[from] std::num::TryFromIntError),
+    #[error("failed to decode the content: {0}")]
+    Encoding(String),
+    #[error("invalid visibility: {0}")]
+    InvalidVisibility(String),
+    #[error("invalid tags: {0}")]
+    InvalidTags(String),
+    #[error("invalid collection: {0}")]
+    InvalidCollection(String),
+    #[error("the content contains possible secrets: {}", describe_secrets(.0))]
+    Secrets(Vec<Finding>),
+}
+
+fn describe_secrets(findings: &[Finding]) -> String {
+    findings
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl ClipError {
+    // the clip field the error is about, reported to API users with validation errors
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::InvalidPassword(_) => "password",
+            Self::InvalidTitle(_) => "title",
+            Self::EmptyContent
+            | Self::ContentTooLarge(_)
+            | Self::Encoding(_)
+            | Self::Secrets(_) => "content",
+            Self::InvalidDate(_) | Self::DateParse(_) => "expires",
+            Self::Id(_) => "clip_id",
+            Self::Hits(_) => "hits",
+            Self::InvalidVisibility(_) => "visibility",
+            Self::InvalidTags(_) => "tags",
+            Self::InvalidCollection(_) => "clips",
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Clip {
     #[serde(skip)] // don't send internal id to the user
     pub clip_id: field::ClipId,
+    #[schema(inline)]
     pub shortcode: field::ShortCode,
+    #[schema(inline)]
     pub content: field::Content,
+    #[schema(inline)]
     pub title: field::Title,
+    #[schema(inline)]
     pub posted: field::Posted,
+    #[schema(inline)]
+    pub updated: field::Updated,
+    #[schema(inline)]
     pub expires: field::Expires,
+    #[schema(inline)]
     pub password: field::Password,
+    #[schema(inline)]
     pub hits: field::Hits,
+    #[serde(skip)] // the owner's API key must never leave the server
+    pub owner: field::Owner,
+    #[serde(default)]
+    #[schema(inline)]
+    pub visibility: field::Visibility,
+    #[serde(default)]
+    #[schema(inline)]
+    pub tags: field::Tags,
+    #[serde(default)]
+    #[schema(inline)]
+    pub forked_from: field::ForkedFrom,
+    #[serde(default)]
+    #[schema(inline)]
+    pub forks: field::Forks,
+    // only set on clips an admin took down, they are not served anymore
+    #[serde(skip)]
+    pub takedown: Option<field::Takedown>,
+}
+
+impl Clip {
+    // version is a hash of everything that can be changed by an update, hits are not part of it.
+    // It is used as the ETag of the clip to detect concurrent edits. The password itself is left
+    // out so the ETag can't be used to guess it, the update time covers a changed password
+    pub fn version(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.content.as_str());
+        for field in [
+            self.title.clone().into_inner(),
+            self.expires
+                .clone()
+                .into_inner()
+                .map(|time| time.timestamp().to_string()),
+            Some(self.password.has_password().to_string()),
+            Some(self.visibility.to_string()),
+            Some(self.tags.as_slice().join(",")),
+            Some(self.updated.clone().into_inner().into_inner().to_rfc3339()),
+        ] {
+            // fields are separated and tagged so moving text between them changes the hash
+            match field {
+                Some(value) => {
+                    hasher.update(b"\0s");
+                    hasher.update(value);
+                }
+                None => hasher.update(b"\0n"),
+            }
+        }
+
+        hasher.finalize()[..16]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    // private clips are only visible to the API key that owns them
+    pub fn is_visible_to(&self, requester: &field::Owner) -> bool {
+        if !self.visibility.is_private() {
+            return true;
+        }
+        match requester.clone().into_inner() {
+            Some(key) => self.owner.is_owned_by(&key),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use chrono::Utc;
+
+    fn new_clip() -> Clip {
+        let now = crate::domain::time::Time::from(Utc::now());
+        Clip {
+            clip_id: field::ClipId::default(),
+            shortcode: field::ShortCode::new(),
+            content: field::Content::new("build log").unwrap(),
+            title: field::Title::default(),
+            posted: field::Posted::new(now.clone()),
+            updated: field::Updated::new(now),
+            expires: field::Expires::default(),
+            password: field::Password::new("hunter2".to_owned()).unwrap(),
+            hits: field::Hits::new(0),
+            owner: field::Owner::default(),
+            visibility: field::Visibility::default(),
+            tags: field::Tags::default(),
+            forked_from: field::ForkedFrom::default(),
+            forks: field::Forks::default(),
+            takedown: None,
+        }
+    }
+
+    #[test]
+    fn versions_cover_tags_but_not_the_password() {
+        let clip = new_clip();
+
+        let mut tagged = clip.clone();
+        tagged.tags = field::Tags::new(["ci".to_owned()]).unwrap();
+        assert_ne!(tagged.version(), clip.version());
+
+        let mut other_password = clip.clone();
+        other_password.password = field::Password::new("swordfish".to_owned()).unwrap();
+        assert_eq!(other_password.version(), clip.version());
+
+        let mut unprotected = clip.clone();
+        unprotected.password = field::Password::default();
+        assert_ne!(unprotected.version(), clip.version());
+    }
 }
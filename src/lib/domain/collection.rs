@@ -0,0 +1,25 @@
+use serde::Serialize;
+
+use crate::domain::clip::field;
+use crate::{Clip, ShortCode};
+
+// the largest number of clips a collection can hold
+pub const MAX_COLLECTION_SIZE: usize = 100;
+
+// Collection bundles clips under a single shortcode, e.g. the logs of an incident. Its expiration
+// and password apply to the clips read through it, the clips themselves are left as they are
+#[derive(Debug, Clone, Serialize)]
+pub struct Collection {
+    pub shortcode: ShortCode,
+    pub title: field::Title,
+    pub posted: field::Posted,
+    pub expires: field::Expires,
+    #[serde(skip)]
+    pub password: field::Password,
+    #[serde(skip)] // the owner's API key must never leave the server
+    pub owner: field::Owner,
+    // the clips of the collection in order, without the ones the reader can't open
+    pub clips: Vec<Clip>,
+    // clips protected by a password of their own, they are opened one by one
+    pub locked: Vec<ShortCode>,
+}
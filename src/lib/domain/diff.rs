@@ -0,0 +1,457 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{Clip, ShortCode};
+
+// diffs with more changed lines are shown as a replacement of everything that differs, finding
+// the shortest edit script takes quadratic memory in the number of changes
+pub const MAX_EDIT_DISTANCE: usize = 2000;
+
+// lines around each change in the unified format
+pub const CONTEXT_LINES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// Word is a run of characters with the same change within a changed line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Word {
+    pub change: Change,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Line {
+    pub change: Change,
+    // line numbers in the first and second clip starting at 1, `None` where the line is missing
+    pub old: Option<usize>,
+    pub new: Option<usize>,
+    pub text: String,
+    // the line split into changed and unchanged words, only set for replaced lines of word diffs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<Word>,
+}
+
+// Row is a line of the side-by-side view, replaced lines are shown next to each other
+#[derive(Debug, Clone, Serialize)]
+pub struct Row {
+    pub left: Option<Line>,
+    pub right: Option<Line>,
+}
+
+// Diff is the line-level difference between the content of two clips
+#[derive(Debug, Clone, Serialize)]
+pub struct Diff {
+    pub a: ShortCode,
+    pub b: ShortCode,
+    pub lines: Vec<Line>,
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl Diff {
+    // `words` also splits replaced lines into the words that changed
+    pub fn new(a: &Clip, b: &Clip, words: bool) -> Self {
+        Self::between(
+            a.shortcode.clone(),
+            a.content.as_str(),
+            b.shortcode.clone(),
+            b.content.as_str(),
+            words,
+        )
+    }
+
+    fn between(a: ShortCode, old: &str, b: ShortCode, new: &str, words: bool) -> Self {
+        let old: Vec<&str> = old.lines().collect();
+        let new: Vec<&str> = new.lines().collect();
+
+        let mut lines = Vec::with_capacity(old.len().max(new.len()));
+        let (mut i, mut j) = (0, 0);
+        for change in edits(&old, &new) {
+            let (text, old_line, new_line) = match change {
+                Change::Equal => (old[i], Some(i + 1), Some(j + 1)),
+                Change::Delete => (old[i], Some(i + 1), None),
+                Change::Insert => (new[j], None, Some(j + 1)),
+            };
+            match change {
+                Change::Equal => (i, j) = (i + 1, j + 1),
+                Change::Delete => i += 1,
+                Change::Insert => j += 1,
+            }
+            lines.push(Line {
+                change,
+                old: old_line,
+                new: new_line,
+                text: text.to_owned(),
+                words: vec![],
+            });
+        }
+        if words {
+            split_words(&mut lines);
+        }
+
+        Self {
+            a,
+            b,
+            insertions: count(&lines, Change::Insert),
+            deletions: count(&lines, Change::Delete),
+            lines,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.insertions == 0 && self.deletions == 0
+    }
+
+    // the lines paired up for the side-by-side view, deleted lines are shown next to the lines
+    // inserted in their place
+    pub fn rows(&self) -> Vec<Row> {
+        let mut rows = vec![];
+        for (deleted, inserted, equal) in self.blocks() {
+            for n in 0..deleted.len().max(inserted.len()) {
+                rows.push(Row {
+                    left: deleted.get(n).cloned(),
+                    right: inserted.get(n).cloned(),
+                });
+            }
+            if let Some(line) = equal {
+                rows.push(Row {
+                    left: Some(line.clone()),
+                    right: Some(line.clone()),
+                });
+            }
+        }
+        rows
+    }
+
+    // the deleted and inserted lines before every unchanged line, and the ones at the end
+    fn blocks(&self) -> Vec<(Vec<Line>, Vec<Line>, Option<&Line>)> {
+        let mut blocks = vec![];
+        let (mut deleted, mut inserted) = (vec![], vec![]);
+        for line in &self.lines {
+            match line.change {
+                Change::Delete => deleted.push(line.clone()),
+                Change::Insert => inserted.push(line.clone()),
+                Change::Equal => blocks.push((
+                    std::mem::take(&mut deleted),
+                    std::mem::take(&mut inserted),
+                    Some(line),
+                )),
+            }
+        }
+        if !deleted.is_empty() || !inserted.is_empty() {
+            blocks.push((deleted, inserted, None));
+        }
+        blocks
+    }
+
+    // the diff in the unified format of `diff -u`, with `CONTEXT_LINES` lines around changes
+    pub fn unified(&self) -> String {
+        let mut out = format!("--- a/{}\n+++ b/{}\n", self.a.as_str(), self.b.as_str());
+        for (start, end) in self.hunks() {
+            let hunk = &self.lines[start..end];
+            let before = &self.lines[..start];
+            out.push_str(&format!(
+                "@@ -{} +{} @@\n",
+                range(before, hunk, |line| line.old),
+                range(before, hunk, |line| line.new)
+            ));
+            for line in hunk {
+                let prefix = match line.change {
+                    Change::Equal => ' ',
+                    Change::Delete => '-',
+                    Change::Insert => '+',
+                };
+                out.push(prefix);
+                out.push_str(&line.text);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    // ranges of lines with changes and their context, changes closer than twice the context
+    // share a hunk
+    fn hunks(&self) -> Vec<(usize, usize)> {
+        let mut hunks: Vec<(usize, usize)> = vec![];
+        for (n, line) in self.lines.iter().enumerate() {
+            if line.change == Change::Equal {
+                continue;
+            }
+            let start = n.saturating_sub(CONTEXT_LINES);
+            let end = (n + 1 + CONTEXT_LINES).min(self.lines.len());
+            match hunks.last_mut() {
+                Some(last) if start <= last.1 => last.1 = end,
+                _ => hunks.push((start, end)),
+            }
+        }
+        hunks
+    }
+}
+
+fn count(lines: &[Line], change: Change) -> usize {
+    lines.iter().filter(|line| line.change == change).count()
+}
+
+// `start,length` of a hunk in one of the clips. Empty hunks start at the line before them
+fn range(before: &[Line], hunk: &[Line], number: fn(&Line) -> Option<usize>) -> String {
+    let length = hunk.iter().filter(|line| number(line).is_some()).count();
+    let preceding = before.iter().filter(|line| number(line).is_some()).count();
+    let start = if length == 0 {
+        preceding
+    } else {
+        preceding + 1
+    };
+    format!("{},{}", start, length)
+}
+
+// pairs every deleted line with the line inserted in its place and diffs their words
+fn split_words(lines: &mut [Line]) {
+    let mut n = 0;
+    while n < lines.len() {
+        let deleted = lines[n..]
+            .iter()
+            .take_while(|line| line.change == Change::Delete)
+            .count();
+        let inserted = lines[n + deleted..]
+            .iter()
+            .take_while(|line| line.change == Change::Insert)
+            .count();
+
+        for pair in 0..deleted.min(inserted) {
+            let (old, new) = (n + pair, n + deleted + pair);
+            let (old_words, new_words) = diff_words(&lines[old].text, &lines[new].text);
+            lines[old].words = old_words;
+            lines[new].words = new_words;
+        }
+        n += (deleted + inserted).max(1);
+    }
+}
+
+// the words of both lines, the first without inserted and the second without deleted words
+fn diff_words(old: &str, new: &str) -> (Vec<Word>, Vec<Word>) {
+    let old_tokens = tokens(old);
+    let new_tokens = tokens(new);
+    let (mut old_words, mut new_words) = (vec![], vec![]);
+    let (mut i, mut j) = (0, 0);
+    for change in edits(&old_tokens, &new_tokens) {
+        match change {
+            Change::Equal => {
+                push_word(&mut old_words, change, old_tokens[i]);
+                push_word(&mut new_words, change, new_tokens[j]);
+                (i, j) = (i + 1, j + 1);
+            }
+            Change::Delete => {
+                push_word(&mut old_words, change, old_tokens[i]);
+                i += 1;
+            }
+            Change::Insert => {
+                push_word(&mut new_words, change, new_tokens[j]);
+                j += 1;
+            }
+        }
+    }
+    (old_words, new_words)
+}
+
+// runs of whitespace and of everything else, so the words can be joined back into the line
+fn tokens(line: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some((_, c)) = chars.next() {
+        if let Some(&(next, n)) = chars.peek() {
+            if c.is_whitespace() != n.is_whitespace() {
+                tokens.push(&line[start..next]);
+                start = next;
+            }
+        }
+    }
+    if start < line.len() {
+        tokens.push(&line[start..]);
+    }
+    tokens
+}
+
+fn push_word(words: &mut Vec<Word>, change: Change, text: &str) {
+    match words.last_mut() {
+        Some(last) if last.change == change => last.text.push_str(text),
+        _ => words.push(Word {
+            change,
+            text: text.to_owned(),
+        }),
+    }
+}
+
+// the shortest edit script turning `a` into `b`. The common prefix and suffix are skipped
+// before the middle is diffed with Myers' algorithm
+fn edits<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Change> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (a_middle, b_middle) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut changes = vec![Change::Equal; prefix];
+    match myers(a_middle, b_middle) {
+        Some(middle) => changes.extend(middle),
+        None => {
+            changes.extend(std::iter::repeat_n(Change::Delete, a_middle.len()));
+            changes.extend(std::iter::repeat_n(Change::Insert, b_middle.len()));
+        }
+    }
+    changes.extend(std::iter::repeat_n(Change::Equal, suffix));
+    changes
+}
+
+// `None` when the sequences differ in more than `MAX_EDIT_DISTANCE` lines
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Option<Vec<Change>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let offset = max + 1;
+    // the furthest x reached on every diagonal `k = x - y`, indexed by `k + offset`
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    // `v` before every round, only the diagonals that round can reach
+    let mut trace = vec![];
+
+    for d in 0..=max.min(MAX_EDIT_DISTANCE as isize) {
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let from_above =
+                k == -d || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize]);
+            let mut x = if from_above {
+                v[(offset + k + 1) as usize]
+            } else {
+                v[(offset + k - 1) as usize] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+            if x >= n && y >= m {
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+    }
+    None
+}
+
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Change> {
+    let mut changes = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = at(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            changes.push(Change::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            changes.push(if x == prev_x {
+                Change::Insert
+            } else {
+                Change::Delete
+            });
+        }
+        (x, y) = (prev_x, prev_y);
+    }
+    changes.reverse();
+    changes
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    fn changes(a: &str, b: &str) -> String {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        edits(&a, &b)
+            .into_iter()
+            .map(|change| match change {
+                Change::Equal => '=',
+                Change::Delete => '-',
+                Change::Insert => '+',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_the_shortest_edit_script() {
+        assert_eq!(changes("", ""), "");
+        assert_eq!(changes("abc", "abc"), "===");
+        assert_eq!(changes("", "ab"), "++");
+        assert_eq!(changes("ab", ""), "--");
+        // the example of Myers' paper, 5 edits is the shortest script
+        let script = changes("abcabba", "cbabac");
+        assert_eq!(script.chars().filter(|c| *c != '=').count(), 5);
+        assert_eq!(script.chars().filter(|c| *c != '+').count(), 7);
+        assert_eq!(script.chars().filter(|c| *c != '-').count(), 6);
+        assert_eq!(changes("axc", "ayc"), "=-+=");
+    }
+
+    #[test]
+    fn unified_diffs_have_hunks_with_context() {
+        let diff = |a: &str, b: &str| Diff::between("a".into(), a, "b".into(), b, false);
+
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12";
+        let new = "1\n2\nthree\n4\n5\n6\n7\n8\n9\n10\n11\n12\n13";
+        assert_eq!(
+            diff(old, new).unified(),
+            "--- a/a\n+++ b/b\n\
+             @@ -1,6 +1,6 @@\n 1\n 2\n-3\n+three\n 4\n 5\n 6\n\
+             @@ -10,3 +10,4 @@\n 10\n 11\n 12\n+13\n"
+        );
+        assert_eq!(
+            diff("", "x").unified(),
+            "--- a/a\n+++ b/b\n@@ -0,0 +1,1 @@\n+x\n"
+        );
+        assert!(diff(old, old).is_empty());
+    }
+
+    #[test]
+    fn replaced_lines_are_split_into_words() {
+        let (old, new) = diff_words("let x = 1;", "let y = 1;");
+        fn text(words: &[Word]) -> Vec<(Change, &str)> {
+            words
+                .iter()
+                .map(|word| (word.change, word.text.as_str()))
+                .collect()
+        }
+        assert_eq!(
+            text(&old),
+            vec![
+                (Change::Equal, "let "),
+                (Change::Delete, "x"),
+                (Change::Equal, " = 1;"),
+            ]
+        );
+        assert_eq!(
+            text(&new),
+            vec![
+                (Change::Equal, "let "),
+                (Change::Insert, "y"),
+                (Change::Equal, " = 1;"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,273 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use rocket::http::RawStr;
+
+use crate::Clip;
+
+// the number of clips in a feed and the characters of the content shown in each entry
+pub const FEED_SIZE: u32 = 50;
+pub const EXCERPT_LENGTH: usize = 280;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl FeedFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Atom => "atom",
+            Self::Rss => "rss",
+        }
+    }
+}
+
+// Entry is a clip as it is shown in a feed, links are absolute so feed readers can follow them
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub title: String,
+    pub link: String,
+    pub posted: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+    pub excerpt: String,
+}
+
+impl Entry {
+    pub fn new(clip: &Clip, base: &str) -> Self {
+        let shortcode = clip.shortcode.as_str();
+        Self {
+            title: clip
+                .title
+                .clone()
+                .into_inner()
+                .unwrap_or_else(|| shortcode.to_owned()),
+            link: format!("{}/clip/{}", base, shortcode),
+            posted: clip.posted.clone().into_inner().into_inner(),
+            updated: clip.updated.clone().into_inner().into_inner(),
+            excerpt: excerpt(clip.content.as_str()),
+        }
+    }
+}
+
+// the start of the content on a single line, feed readers show summaries as a paragraph
+fn excerpt(content: &str) -> String {
+    let line = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(EXCERPT_LENGTH) {
+        Some((end, _)) => format!("{}…", line[..end].trim_end()),
+        None => line,
+    }
+}
+
+// escapes text for XML elements and attributes. Control characters are not allowed in XML 1.0,
+// so they are dropped
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Feed lists the most recent public clips, of a single tag when it is set
+#[derive(Debug, Clone)]
+pub struct Feed {
+    base: String,
+    tag: Option<String>,
+    entries: Vec<Entry>,
+}
+
+impl Feed {
+    // `base` is the scheme and host of the server, without a trailing slash
+    pub fn new(base: &str, tag: Option<String>, clips: &[Clip]) -> Self {
+        let base = base.trim_end_matches('/').to_owned();
+        Self {
+            entries: clips.iter().map(|clip| Entry::new(clip, &base)).collect(),
+            base,
+            tag,
+        }
+    }
+
+    fn title(&self) -> String {
+        match &self.tag {
+            Some(tag) => format!("ClipStash - {}", tag),
+            None => "ClipStash".to_owned(),
+        }
+    }
+
+    fn description(&self) -> String {
+        match &self.tag {
+            Some(tag) => format!("Recent public clips tagged {}", tag),
+            None => "Recent public clips".to_owned(),
+        }
+    }
+
+    // the page showing the same clips
+    fn page(&self) -> String {
+        match &self.tag {
+            Some(tag) => format!("{}/tag/{}", self.base, RawStr::new(tag).percent_encode()),
+            None => format!("{}/", self.base),
+        }
+    }
+
+    pub fn link(&self, format: FeedFormat) -> String {
+        match &self.tag {
+            Some(tag) => format!(
+                "{}/tag/{}/feed.{}",
+                self.base,
+                RawStr::new(tag).percent_encode(),
+                format.extension()
+            ),
+            None => format!("{}/feed.{}", self.base, format.extension()),
+        }
+    }
+
+    // the time of the latest change, an empty feed never changed
+    fn updated(&self) -> DateTime<Utc> {
+        self.entries
+            .iter()
+            .map(|entry| entry.updated)
+            .max()
+            .unwrap_or(DateTime::<Utc>::UNIX_EPOCH)
+    }
+
+    pub fn render(&self, format: FeedFormat) -> String {
+        match format {
+            FeedFormat::Atom => self.atom(),
+            FeedFormat::Rss => self.rss(),
+        }
+    }
+
+    pub fn atom(&self) -> String {
+        let time = |time: DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let link = escape(&self.link(FeedFormat::Atom));
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        xml.push_str(&format!("  <title>{}</title>\n", escape(&self.title())));
+        xml.push_str(&format!(
+            "  <subtitle>{}</subtitle>\n",
+            escape(&self.description())
+        ));
+        xml.push_str(&format!("  <id>{}</id>\n", link));
+        xml.push_str(&format!("  <link rel=\"self\" href=\"{}\"/>\n", link));
+        xml.push_str(&format!(
+            "  <link rel=\"alternate\" type=\"text/html\" href=\"{}\"/>\n",
+            escape(&self.page())
+        ));
+        xml.push_str(&format!("  <updated>{}</updated>\n", time(self.updated())));
+        xml.push_str("  <author><name>ClipStash</name></author>\n");
+        for entry in &self.entries {
+            let link = escape(&entry.link);
+            xml.push_str("  <entry>\n");
+            xml.push_str(&format!("    <title>{}</title>\n", escape(&entry.title)));
+            xml.push_str(&format!("    <id>{}</id>\n", link));
+            xml.push_str(&format!("    <link href=\"{}\"/>\n", link));
+            xml.push_str(&format!(
+                "    <published>{}</published>\n",
+                time(entry.posted)
+            ));
+            xml.push_str(&format!("    <updated>{}</updated>\n", time(entry.updated)));
+            xml.push_str(&format!(
+                "    <summary type=\"text\">{}</summary>\n",
+                escape(&entry.excerpt)
+            ));
+            xml.push_str("  </entry>\n");
+        }
+        xml.push_str("</feed>\n");
+        xml
+    }
+
+    pub fn rss(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+        xml.push_str("  <channel>\n");
+        xml.push_str(&format!("    <title>{}</title>\n", escape(&self.title())));
+        xml.push_str(&format!("    <link>{}</link>\n", escape(&self.page())));
+        xml.push_str(&format!(
+            "    <description>{}</description>\n",
+            escape(&self.description())
+        ));
+        xml.push_str(&format!(
+            "    <atom:link rel=\"self\" type=\"application/rss+xml\" href=\"{}\"/>\n",
+            escape(&self.link(FeedFormat::Rss))
+        ));
+        xml.push_str(&format!(
+            "    <lastBuildDate>{}</lastBuildDate>\n",
+            self.updated().to_rfc2822()
+        ));
+        for entry in &self.entries {
+            let link = escape(&entry.link);
+            xml.push_str("    <item>\n");
+            xml.push_str(&format!("      <title>{}</title>\n", escape(&entry.title)));
+            xml.push_str(&format!("      <link>{}</link>\n", link));
+            xml.push_str(&format!(
+                "      <guid isPermaLink=\"true\">{}</guid>\n",
+                link
+            ));
+            xml.push_str(&format!(
+                "      <pubDate>{}</pubDate>\n",
+                entry.posted.to_rfc2822()
+            ));
+            // readers show descriptions as HTML, the excerpt is escaped once for HTML and once
+            // for XML so markup in a clip is shown as text
+            xml.push_str(&format!(
+                "      <description>{}</description>\n",
+                escape(&escape(&entry.excerpt))
+            ));
+            xml.push_str("    </item>\n");
+        }
+        xml.push_str("  </channel>\n");
+        xml.push_str("</rss>\n");
+        xml
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    fn text_is_escaped_for_xml() {
+        assert_eq!(
+            escape(r#"<a href="x">Tom & Jerry's</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&apos;s&lt;/a&gt;"
+        );
+        assert_eq!(
+            escape("tab\tnew\nline\u{0}\u{1b}[0m\u{FFFF}"),
+            "tab\tnew\nline[0m"
+        );
+    }
+
+    #[test]
+    fn tags_are_percent_encoded_in_links() {
+        let feed = Feed::new("https://stash.example/", Some("c#/x".to_owned()), &[]);
+        assert_eq!(feed.page(), "https://stash.example/tag/c%23%2Fx");
+        assert_eq!(
+            feed.link(FeedFormat::Atom),
+            "https://stash.example/tag/c%23%2Fx/feed.atom"
+        );
+    }
+
+    #[test]
+    fn excerpts_are_a_single_shortened_line() {
+        assert_eq!(excerpt("  first\n\n  second\tthird "), "first second third");
+
+        let long = "é".repeat(EXCERPT_LENGTH + 1);
+        let short = excerpt(&long);
+        assert_eq!(short.chars().count(), EXCERPT_LENGTH + 1);
+        assert!(short.ends_with('…'));
+        assert_eq!(
+            excerpt(&"é".repeat(EXCERPT_LENGTH)),
+            "é".repeat(EXCERPT_LENGTH)
+        );
+    }
+}
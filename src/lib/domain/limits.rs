@@ -0,0 +1,60 @@
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+// the largest content and title of a clip unless the server is configured otherwise
+pub const DEFAULT_MAX_CONTENT_SIZE: usize = 8 * 1024 * 1024;
+pub const DEFAULT_MAX_TITLE_LENGTH: usize = 200;
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+
+// Limits bound the size of a single clip. They are checked by the field types, so they are set
+// once for the whole process when the server starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    // in bytes
+    pub max_content_size: usize,
+    // in characters
+    pub max_title_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_content_size: DEFAULT_MAX_CONTENT_SIZE,
+            max_title_length: DEFAULT_MAX_TITLE_LENGTH,
+        }
+    }
+}
+
+impl Limits {
+    // returns false when the limits were already installed or read, they can't change anymore
+    pub fn install(self) -> bool {
+        LIMITS.set(self).is_ok()
+    }
+
+    pub fn current() -> Self {
+        *LIMITS.get_or_init(Self::default)
+    }
+}
+
+// StorageQuota is the most content, in bytes, the clips of a single API key can hold. Content is
+// counted before it is compressed, the same way clips are limited by `max_content_size`. `None`
+// doesn't limit the storage
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StorageQuota(pub Option<u64>);
+
+// Usage is the storage used by the clips of an API key
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Usage {
+    pub clips: u64,
+    // the size of the content of the clips before compression, in bytes
+    pub bytes: u64,
+    pub quota: Option<u64>,
+}
+
+impl Usage {
+    pub fn remaining(&self) -> Option<u64> {
+        self.quota.map(|quota| quota.saturating_sub(self.bytes))
+    }
+}
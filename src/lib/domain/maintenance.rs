@@ -1,23 +1,38 @@
 use std::time::Duration;
 
 use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
 
 use crate::{data::DatabasePool, service};
 
-pub struct Maintenance;
+pub struct Maintenance {
+    task: JoinHandle<()>,
+}
 
 impl Maintenance {
     pub fn spawn(pool: DatabasePool, handle: Handle) -> Self {
-        handle.spawn(async move {
+        let task = handle.spawn(async move {
             let mut interval = tokio::time::interval(Duration::from_secs(10));
             loop {
                 interval.tick().await;
-                if let Err(e) = service::action::delete_expired(&pool).await {
-                    eprintln!("failed to delete expired clips: {}", e);
+                match service::action::delete_expired(&pool).await {
+                    Ok(0) => (),
+                    Ok(deleted) => tracing::info!(deleted, "deleted expired clips"),
+                    Err(e) => tracing::error!(error = %e, "failed to delete expired clips"),
+                }
+                match service::action::fill_clip_sizes(&pool).await {
+                    Ok(0) => (),
+                    Ok(filled) => tracing::info!(filled, "counted the size of older clips"),
+                    Err(e) => tracing::error!(error = %e, "failed to count the size of clips"),
                 }
             }
         });
 
-        Self
+        Self { task }
+    }
+
+    // the task only stops when it panics or when the runtime shuts down
+    pub fn is_alive(&self) -> bool {
+        !self.task.is_finished()
     }
 }
@@ -1,5 +1,14 @@
+pub mod admin;
+pub mod audit;
 pub mod clip;
+pub mod collection;
+pub mod diff;
+pub mod feed;
+pub mod limits;
 pub mod maintenance;
+pub mod report;
+pub mod secret;
+pub mod stats;
 pub mod time;
 
 pub use clip::Clip;
@@ -0,0 +1,141 @@
+use rocket::form::{self, FromFormField, ValueField};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use thiserror::Error;
+
+use crate::{ShortCode, Time};
+
+pub const MAX_REASON_LENGTH: usize = 2000;
+pub const MAX_CONTACT_LENGTH: usize = 200;
+
+#[derive(Debug, Error)]
+pub enum ReportError {
+    #[error("invalid reason: {0}")]
+    InvalidReason(String),
+    #[error("invalid contact: {0}")]
+    InvalidContact(String),
+    #[error("invalid takedown: {0}")]
+    InvalidTakedown(String),
+    #[error("unknown moderation action `{0}`")]
+    UnknownAction(String),
+}
+
+impl ReportError {
+    // the field of the report or takedown form the error is about
+    pub fn field(&self) -> &'static str {
+        match self {
+            Self::InvalidReason(_) => "reason",
+            Self::InvalidContact(_) => "contact",
+            Self::InvalidTakedown(_) => "takedown",
+            Self::UnknownAction(_) => "action",
+        }
+    }
+}
+
+// Reason is why a visitor reports a clip, e.g. that it leaks credentials or is spam
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Reason(String);
+
+impl Reason {
+    pub fn new(reason: &str) -> Result<Self, ReportError> {
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(ReportError::InvalidReason(
+                "please describe what is wrong with the clip".to_owned(),
+            ));
+        }
+        if reason.chars().count() > MAX_REASON_LENGTH {
+            return Err(ReportError::InvalidReason(format!(
+                "the reason can be at most {} characters long",
+                MAX_REASON_LENGTH
+            )));
+        }
+        Ok(Self(reason.to_owned()))
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for Reason {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::new(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+}
+
+// Contact is how the admin can reach the reporter, reports can be anonymous
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Contact(Option<String>);
+
+impl Contact {
+    pub fn new(contact: &str) -> Result<Self, ReportError> {
+        let contact = contact.trim();
+        if contact.chars().count() > MAX_CONTACT_LENGTH {
+            return Err(ReportError::InvalidContact(format!(
+                "the contact can be at most {} characters long",
+                MAX_CONTACT_LENGTH
+            )));
+        }
+        Ok(Self((!contact.is_empty()).then(|| contact.to_owned())))
+    }
+
+    pub fn into_inner(self) -> Option<String> {
+        self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for Contact {
+    fn from_value(field: ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::new(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+
+    fn default() -> Option<Self> {
+        Some(<Self as Default>::default())
+    }
+}
+
+// Report is a complaint about a clip, open until an admin takes the clip down or dismisses it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub id: i64,
+    pub shortcode: ShortCode,
+    // the title of the reported clip
+    pub title: Option<String>,
+    pub reason: String,
+    pub contact: Option<String>,
+    pub filed: Time,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Display, EnumString)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ModerationAction {
+    TakeDown,
+    Restore,
+    DeleteClip,
+    DismissReport,
+    DisableKey,
+}
+
+// ModerationEntry records an action of an admin. Entries are kept when the clip, report or
+// key they are about is deleted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationEntry {
+    pub id: i64,
+    pub time: Time,
+    pub action: ModerationAction,
+    pub shortcode: Option<ShortCode>,
+    pub report: Option<i64>,
+    pub key: Option<i64>,
+    pub note: Option<String>,
+}
+
+// ModerationQueue is what the admin reviews, the open reports and the recent actions
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationQueue {
+    pub reports: Vec<Report>,
+    pub log: Vec<ModerationEntry>,
+}
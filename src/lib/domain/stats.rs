@@ -0,0 +1,34 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::ShortCode;
+
+// ClipStats is the view analytics of a single clip, only visible to the clip owner
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ClipStats {
+    pub shortcode: ShortCode,
+    pub hits: u64,
+    pub daily: Vec<DailyViews>,
+    pub referrers: Vec<ReferrerViews>,
+    pub agents: Vec<AgentViews>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DailyViews {
+    pub day: NaiveDate,
+    pub views: u64,
+    pub unique_visitors: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReferrerViews {
+    pub host: String,
+    pub views: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AgentViews {
+    pub family: String,
+    pub views: u64,
+}
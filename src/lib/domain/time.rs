@@ -2,8 +2,9 @@ use chrono::{DateTime, NaiveDateTime, Utc};
 use derive_more::From;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use utoipa::ToSchema;
 
-#[derive(Clone, Debug, From, Serialize, Deserialize)]
+#[derive(Clone, Debug, From, Serialize, Deserialize, ToSchema)]
 pub struct Time(DateTime<Utc>);
 
 impl Time {
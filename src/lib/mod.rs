@@ -13,24 +13,67 @@ pub use domain::time::Time;
 pub use service::ServiceError;
 
 use data::AppDatabase;
+use domain::limits::{Limits, StorageQuota};
 use domain::maintenance::Maintenance;
+use domain::secret::SecretScanner;
+use rocket::figment::providers::{Env, Format, Toml};
+use rocket::figment::{Figment, Profile};
 use rocket::fs::FileServer;
 use rocket::{Build, Rocket};
+use web::admin::{AdminSessions, AdminToken};
+use web::feed::BaseUrl;
 use web::hitcounter::HitCounter;
+use web::metrics::{MetricsToken, RequestMetrics, RequestTimer};
 use web::renderer::Renderer;
+use web::trace::{traced, RequestTracing};
 
 // build a rocket server
 pub fn build_a_rocket(config: RocketConfig) -> Rocket<Build> {
-    rocket::build()
+    rocket::custom(figment())
         .manage::<AppDatabase>(config.database)
         .manage::<Renderer>(config.renderer)
         .manage::<HitCounter>(config.hit_counter)
         .manage::<Maintenance>(config.maintenance)
-        .mount("/api/clip", web::api::routes())
-        .mount("/", web::http::routes())
+        .manage::<MetricsToken>(config.metrics_token)
+        .manage::<AdminToken>(config.admin_token)
+        .manage::<SecretScanner>(config.secrets)
+        .manage::<StorageQuota>(config.quota)
+        .manage::<BaseUrl>(config.base_url)
+        .manage::<RequestMetrics>(RequestMetrics::default())
+        .manage::<AdminSessions>(AdminSessions::default())
+        .attach(RequestTimer)
+        .attach(RequestTracing)
+        .attach(web::api::Deprecation)
+        .mount(web::api::LEGACY_API_BASE, traced(web::api::routes()))
+        .mount("/api/v1", traced(web::api::v1::routes()))
+        .mount("/api", traced(web::openapi::routes()))
+        .mount("/", traced(web::http::routes()))
+        .mount("/", traced(web::metrics::routes()))
+        .mount("/", traced(web::admin::routes()))
+        .mount("/", traced(web::audit::routes()))
+        .mount("/", traced(web::feed::routes()))
+        .mount("/", traced(web::health::routes()))
         .mount("/static", FileServer::from("static"))
         .register("/", web::http::catcher::catchers())
-        .register("/api/clip", web::api::catcher::catchers())
+        .register("/api", web::api::catcher::catchers())
+}
+
+// the data limits of rocket follow the max content size, bodies are refused before they are
+// read. Encoded forms and JSON take up to three times the room of the content. These are only
+// defaults, limits set in Rocket.toml or ROCKET_LIMITS are merged last and win
+fn figment() -> Figment {
+    let content = Limits::current().max_content_size as u64;
+    let encoded = content * 3 + 64 * 1024;
+    Figment::from(rocket::Config::default())
+        .merge(("limits.form", encoded))
+        .merge(("limits.json", encoded))
+        .merge((format!("limits.{}", web::api::v1::CLIP_LIMIT), content))
+        .merge(Toml::file(Env::var_or("ROCKET_CONFIG", "Rocket.toml")).nested())
+        .merge(Env::prefixed("ROCKET_").ignore(&["PROFILE"]).global())
+        .select(Profile::from_env_or(
+            "ROCKET_PROFILE",
+            rocket::Config::DEFAULT_PROFILE,
+        ))
 }
 
 // RocketConfig represents the server configuration
@@ -39,6 +82,11 @@ pub struct RocketConfig {
     pub database: AppDatabase,
     pub hit_counter: HitCounter,
     pub maintenance: Maintenance,
+    pub metrics_token: MetricsToken,
+    pub admin_token: AdminToken,
+    pub secrets: SecretScanner,
+    pub quota: StorageQuota,
+    pub base_url: BaseUrl,
 }
 
 #[cfg(test)]
@@ -1,7 +1,18 @@
-use crate::data::{query, DatabasePool, Transaction};
+use crate::data::compression::StoredContent;
+use crate::data::{model, query, DatabasePool, Transaction, MIGRATOR};
+use crate::domain::admin::{ClipSummary, Dashboard};
+use crate::domain::audit::{Actor, AuditAction, AuditEntry};
+use crate::domain::clip::field::{Content, ForkedFrom, Owner, Password, Visibility};
+use crate::domain::collection::{Collection, MAX_COLLECTION_SIZE};
+use crate::domain::diff::Diff;
+use crate::domain::limits::{StorageQuota, Usage};
+use crate::domain::report::{ModerationAction, ModerationQueue};
+use crate::domain::secret::{self, SecretPolicy, SecretScanner};
+use crate::domain::stats::ClipStats;
 use crate::service::ask;
+use crate::service::metrics::METRICS;
 use crate::web::api::ApiKey;
-use crate::{Clip, ServiceError, ShortCode};
+use crate::{Clip, ClipError, ServiceError, ShortCode};
 use std::convert::TryInto;
 
 pub async fn begin_transaction(pool: &DatabasePool) -> Result<Transaction<'_>, ServiceError> {
@@ -12,20 +23,85 @@ pub async fn end_transaction(transaction: Transaction<'_>) -> Result<(), Service
     Ok(transaction.commit().await?)
 }
 
-pub async fn increase_hit_count(
-    shortcode: &ShortCode,
-    hits: u32,
+// applies a batch of hits within a single transaction, returns the number of updated clips
+pub async fn increase_hit_counts(
+    hits: &[(ShortCode, u32)],
     pool: &DatabasePool,
-) -> Result<(), ServiceError> {
-    Ok(query::increase_hit_count(shortcode, hits, pool).await?)
+) -> Result<u64, ServiceError> {
+    let mut transaction = begin_transaction(pool).await?;
+    let updated = query::increase_hit_counts(hits, &mut transaction).await?;
+    end_transaction(transaction).await?;
+    Ok(updated)
 }
 
-pub async fn get_clip(req: ask::GetClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
-    let user_password = req.password.clone();
-    let clip: Clip = query::get_clip(req, pool).await?.try_into()?;
+// writes everything the hit counter aggregated within a single transaction
+pub async fn commit_hits(batch: &ask::HitBatch, pool: &DatabasePool) -> Result<(), ServiceError> {
+    let views: Vec<model::NewView> = batch.views.iter().cloned().map(Into::into).collect();
+    let visitors: Vec<model::NewVisitor> = batch.visitors.iter().cloned().map(Into::into).collect();
+
+    let mut transaction = begin_transaction(pool).await?;
+    query::increase_hit_counts(&batch.hits, &mut transaction).await?;
+    query::add_views(&views, &mut transaction).await?;
+    query::add_visitors(&visitors, &mut transaction).await?;
+    end_transaction(transaction).await
+}
+
+// view analytics are only available to the API key that created the clip
+pub async fn get_clip_stats(
+    req: ask::GetClipStats,
+    pool: &DatabasePool,
+) -> Result<ClipStats, ServiceError> {
+    let clip: Clip = query::get_clip(req.shortcode.clone(), pool)
+        .await?
+        .try_into()?;
+
+    match req.owner.into_inner() {
+        Some(key) if clip.owner.is_owned_by(&key) => (),
+        _ => {
+            return Err(ServiceError::PermissionError(
+                "Only the owner of the clip can view its stats".to_owned(),
+            ))
+        }
+    }
+
+    let daily = query::get_daily_views(&req.shortcode, 30, pool).await?;
+    let referrers = query::get_top_referrers(&req.shortcode, 10, pool).await?;
+    let agents = query::get_top_agents(&req.shortcode, 10, pool).await?;
+
+    Ok(ClipStats {
+        shortcode: req.shortcode,
+        hits: clip.hits.into_inner(),
+        daily: daily
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?,
+        referrers: referrers
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?,
+        agents: agents
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?,
+    })
+}
 
+// private clips of other owners are reported as missing, their shortcodes are not public.
+// Clips an admin took down are not served to anyone
+fn check_visible(clip: Clip, requester: &Owner) -> Result<Clip, ServiceError> {
+    if !clip.is_visible_to(requester) {
+        return Err(ServiceError::NotFound);
+    }
+    match clip.takedown {
+        Some(takedown) => Err(ServiceError::TakenDown(takedown)),
+        None => Ok(clip),
+    }
+}
+
+// protected clips are only returned with the right password
+fn unlock(clip: Clip, password: &Password) -> Result<Clip, ServiceError> {
     if clip.password.has_password() {
-        if clip.password == user_password {
+        if clip.password == *password {
             Ok(clip)
         } else {
             Err(ServiceError::PermissionError("Invalid password".to_owned()))
@@ -35,34 +111,887 @@ pub async fn get_clip(req: ask::GetClip, pool: &DatabasePool) -> Result<Clip, Se
     }
 }
 
-pub async fn new_clip(req: ask::NewClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
+// reading a protected clip with its password is recorded as an unlock, in the transaction of
+// the read so no protected content is returned without an entry
+async fn audit_unlock(
+    clip: &Clip,
+    actor: &Actor,
+    transaction: &mut Transaction<'_>,
+) -> Result<(), ServiceError> {
+    if !clip.password.has_password() {
+        return Ok(());
+    }
+    let entry = ask::Audit {
+        shortcode: Some(clip.shortcode.clone()),
+        ..ask::Audit::new(AuditAction::Unlock, actor)
+    };
+    Ok(query::add_audit_entry(entry, transaction).await?)
+}
+
+pub async fn get_clip(
+    req: ask::GetClip,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<Clip, ServiceError> {
+    let user_password = req.password.clone();
+    let requester = req.requester.clone();
+
+    let mut transaction = begin_transaction(pool).await?;
+    let clip: Clip = query::get_clip(req, &mut *transaction).await?.try_into()?;
+    let clip = unlock(check_visible(clip, &requester)?, &user_password)?;
+    audit_unlock(&clip, actor, &mut transaction).await?;
+    end_transaction(transaction).await?;
+    Ok(clip)
+}
+
+// the clip along with its content as it is stored, so raw clips can be sent still compressed
+pub async fn get_raw_clip(
+    req: ask::GetClip,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<(Clip, StoredContent), ServiceError> {
+    let user_password = req.password.clone();
+    let requester = req.requester.clone();
+
+    let mut transaction = begin_transaction(pool).await?;
+    let model = query::get_clip(req, &mut *transaction).await?;
+    let stored = model.stored_content()?;
+    let clip = unlock(
+        check_visible(model.try_into()?, &requester)?,
+        &user_password,
+    )?;
+    audit_unlock(&clip, actor, &mut transaction).await?;
+    end_transaction(transaction).await?;
+    Ok((clip, stored))
+}
+
+pub async fn diff_clips(
+    req: ask::GetDiff,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<Diff, ServiceError> {
+    let a = get_clip(req.a, actor, pool).await?;
+    let b = get_clip(req.b, actor, pool).await?;
+    Ok(Diff::new(&a, &b, req.words))
+}
+
+// fetches several clips with one query, every clip is unlocked with its own password.
+// The outer error is for failures of the whole batch, the inner ones for single clips
+pub async fn get_clips(
+    reqs: Vec<ask::GetClip>,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<Vec<Result<Clip, ServiceError>>, ServiceError> {
+    let shortcodes: Vec<&str> = reqs.iter().map(|req| req.shortcode.as_str()).collect();
+
+    let mut transaction = begin_transaction(pool).await?;
+    let clips: Vec<Clip> = query::get_clips(&shortcodes, &mut *transaction)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<_, _>>()?;
+
+    let results: Vec<Result<Clip, ServiceError>> = reqs
+        .into_iter()
+        .map(|req| {
+            let clip = clips
+                .iter()
+                .find(|clip| clip.shortcode == req.shortcode)
+                .cloned()
+                .ok_or(ServiceError::NotFound)?;
+            unlock(check_visible(clip, &req.requester)?, &req.password)
+        })
+        .collect();
+    for clip in results.iter().flatten() {
+        audit_unlock(clip, actor, &mut transaction).await?;
+    }
+    end_transaction(transaction).await?;
+    Ok(results)
+}
+
+// private clips can only be read by their owner, clips from the web have none
+fn check_owner(visibility: Visibility, owner: &Owner) -> Result<(), ClipError> {
+    if visibility.is_private() && !owner.has_owner() {
+        return Err(ClipError::InvalidVisibility(
+            "only clips created with an API key can be private".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+// a fork only links to clips its owner can see. The link is dropped when the original is gone,
+// e.g. because it expired while the fork was being written
+async fn check_fork(req: &mut ask::NewClip, pool: &DatabasePool) -> Result<(), ServiceError> {
+    let Some(original) = req.forked_from.as_shortcode() else {
+        return Ok(());
+    };
+    let found = query::get_clip(original.clone(), pool).await;
+    let visible = match found.map_err(ServiceError::from) {
+        Ok(clip) => Clip::try_from(clip)?.is_visible_to(&req.owner),
+        Err(ServiceError::NotFound) => false,
+        Err(e) => return Err(e),
+    };
+    if !visible {
+        req.forked_from = ForkedFrom::default();
+    }
+    Ok(())
+}
+
+// the policy of the server decides whether a clip with secrets is refused or stored with the
+// secrets redacted. `warn` only refuses it until the author confirms
+pub fn check_secrets(
+    content: &mut Content,
+    allow_secrets: bool,
+    scanner: &SecretScanner,
+) -> Result<(), ServiceError> {
+    let findings = scanner.scan(content.as_str());
+    if findings.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(
+        secrets = findings.len(),
+        policy = %scanner.policy(),
+        "possible secrets in a clip"
+    );
+
+    match scanner.policy() {
+        SecretPolicy::Off => Ok(()),
+        SecretPolicy::Warn if allow_secrets => Ok(()),
+        SecretPolicy::Warn | SecretPolicy::Reject => Err(ClipError::Secrets(findings).into()),
+        SecretPolicy::Redact => {
+            *content = Content::new(&secret::redact(content.as_str(), &findings))?;
+            Ok(())
+        }
+    }
+}
+
+// clips stored compressed before sizes were counted get their size once their content is
+// decompressed, `owner` limits it to the clips of an API key. Returns the number of counted clips
+async fn fill_sizes(
+    owner: Option<&[u8]>,
+    limit: u32,
+    transaction: &mut Transaction<'_>,
+) -> Result<u64, ServiceError> {
+    let clips = query::get_unsized_clips(owner, limit, &mut **transaction).await?;
+    for clip in &clips {
+        query::set_clip_size(clip.shortcode(), clip.size(), transaction).await?;
+    }
+    Ok(clips.len() as u64)
+}
+
+// the usage of an API key includes every clip, the unknown sizes are counted first
+async fn fill_owner_sizes(
+    owner: &[u8],
+    transaction: &mut Transaction<'_>,
+) -> Result<(), ServiceError> {
+    while fill_sizes(Some(owner), 100, transaction).await? > 0 {}
+    Ok(())
+}
+
+// counts a batch of the clips without a size, run by the maintenance task
+pub async fn fill_clip_sizes(pool: &DatabasePool) -> Result<u64, ServiceError> {
+    let mut transaction = begin_transaction(pool).await?;
+    let filled = fill_sizes(None, 100, &mut transaction).await?;
+    end_transaction(transaction).await?;
+    Ok(filled)
+}
+
+// clips of an API key are refused once their content would take more than the quota, clips
+// without an owner aren't counted. `except` is the clip being replaced by an update
+async fn check_quota(
+    owner: &Owner,
+    added: u64,
+    except: Option<&str>,
+    quota: StorageQuota,
+    transaction: &mut Transaction<'_>,
+) -> Result<(), ServiceError> {
+    let (Some(owner), StorageQuota(Some(quota))) = (owner.as_bytes(), quota) else {
+        return Ok(());
+    };
+
+    fill_owner_sizes(owner, transaction).await?;
+    let usage: Usage = query::get_usage(owner, except, &mut **transaction)
+        .await?
+        .try_into()?;
+    let needed = usage.bytes.saturating_add(added);
+    if needed > quota {
+        return Err(ServiceError::QuotaExceeded(format!(
+            "the clips of the API key would take {} bytes, the quota is {} bytes",
+            needed, quota
+        )));
+    }
+    Ok(())
+}
+
+pub async fn new_clip(
+    mut req: ask::NewClip,
+    actor: &Actor,
+    secrets: &SecretScanner,
+    quota: StorageQuota,
+    pool: &DatabasePool,
+) -> Result<Clip, ServiceError> {
+    check_secrets(&mut req.content, req.allow_secrets, secrets)?;
+    store_clip(req, actor, quota, pool).await
+}
+
+async fn store_clip(
+    mut req: ask::NewClip,
+    actor: &Actor,
+    quota: StorageQuota,
+    pool: &DatabasePool,
+) -> Result<Clip, ServiceError> {
+    check_owner(req.visibility, &req.owner)?;
+    check_fork(&mut req, pool).await?;
+
     // .await - wait for the db query to finish
     // try_into - try to convert from the data::Clip into the domain::Clip
-    Ok(query::new_clip(req, pool).await?.try_into()?)
+    let mut transaction = begin_transaction(pool).await?;
+    let size = req.content.as_str().len() as u64;
+    check_quota(&req.owner, size, None, quota, &mut transaction).await?;
+    let clip = query::new_clip(req, &mut transaction).await?;
+    let entry = ask::Audit {
+        shortcode: Some(ShortCode::from(clip.shortcode())),
+        ..ask::Audit::new(AuditAction::Create, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await?;
+
+    let clip = clip.try_into()?;
+    METRICS.clip_created();
+    Ok(clip)
+}
+
+// the fork is a new unlisted clip of the requester, without the expiration, password and tags
+// of the original. Its content is scanned again, the original may have been stored before the
+// scanner existed or with secrets its author allowed
+pub async fn fork_clip(
+    req: ask::ForkClip,
+    actor: &Actor,
+    secrets: &SecretScanner,
+    quota: StorageQuota,
+    pool: &DatabasePool,
+) -> Result<Clip, ServiceError> {
+    let original = get_clip(
+        ask::GetClip {
+            shortcode: req.shortcode.clone(),
+            password: req.password,
+            requester: req.requester.clone(),
+        },
+        actor,
+        pool,
+    )
+    .await?;
+
+    let req = ask::NewClip {
+        content: original.content,
+        title: original.title,
+        expires: Default::default(),
+        password: Default::default(),
+        owner: req.requester,
+        visibility: Default::default(),
+        tags: Default::default(),
+        forked_from: ForkedFrom::new(req.shortcode),
+        allow_secrets: false,
+    };
+    new_clip(req, actor, secrets, quota, pool).await
+}
+
+// other clips of the clip's owner with the same content, oldest first. Clips from the web have
+// no owner and clips of other owners are never reported, their shortcodes are not public
+pub async fn find_duplicates(
+    clip: &Clip,
+    pool: &DatabasePool,
+) -> Result<Vec<ShortCode>, ServiceError> {
+    let Some(owner) = clip.owner.clone().into_inner() else {
+        return Ok(vec![]);
+    };
+
+    let hash = query::content_hash(clip.content.as_str());
+    Ok(
+        query::get_duplicates(&hash, &owner, clip.shortcode.as_str(), pool)
+            .await?
+            .into_iter()
+            .map(ShortCode::from)
+            .collect(),
+    )
+}
+
+// creates all clips in a single transaction, either every clip is stored or none. The clips
+// were already checked with `check_secrets`, so the findings are reported for each clip
+pub async fn new_clips(
+    reqs: Vec<ask::NewClip>,
+    actor: &Actor,
+    quota: StorageQuota,
+    pool: &DatabasePool,
+) -> Result<Vec<Clip>, ServiceError> {
+    let mut reqs = reqs;
+    for req in reqs.iter_mut() {
+        check_owner(req.visibility, &req.owner)?;
+        check_fork(req, pool).await?;
+    }
+
+    let mut transaction = begin_transaction(pool).await?;
+    // every clip of a batch has the same owner
+    if let Some(first) = reqs.first() {
+        let size = reqs
+            .iter()
+            .map(|req| req.content.as_str().len() as u64)
+            .sum();
+        check_quota(&first.owner, size, None, quota, &mut transaction).await?;
+    }
+    let clips = query::new_clips(reqs, &mut transaction).await?;
+    for clip in &clips {
+        let entry = ask::Audit {
+            shortcode: Some(ShortCode::from(clip.shortcode())),
+            ..ask::Audit::new(AuditAction::Create, actor)
+        };
+        query::add_audit_entry(entry, &mut transaction).await?;
+    }
+    end_transaction(transaction).await?;
+
+    let clips = clips
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<Vec<Clip>, _>>()?;
+    METRICS.clips_created(clips.len() as u64);
+    Ok(clips)
 }
 
-pub async fn update_clip(req: ask::UpdateClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
+pub async fn update_clip(
+    mut req: ask::UpdateClip,
+    actor: &Actor,
+    secrets: &SecretScanner,
+    quota: StorageQuota,
+    pool: &DatabasePool,
+) -> Result<Clip, ServiceError> {
+    let current = query::get_clip(req.shortcode.clone(), pool).await?;
+    let clip = check_visible(Clip::try_from(current.clone())?, &req.requester)?;
+    if let Some(visibility) = req.visibility {
+        check_owner(visibility, &clip.owner)?;
+    }
+
+    if let Some(expected) = &req.expected_versions {
+        let version = clip.version();
+        if !expected.contains(&version) {
+            return Err(ServiceError::PreconditionFailed);
+        }
+    }
+    check_secrets(&mut req.content, req.allow_secrets, secrets)?;
+
     // .await - wait for the db query to finish
     // try_into - try to convert from the data::Clip into the domain::Clip
-    Ok(query::update_clip(req, pool).await?.try_into()?)
+    let mut transaction = begin_transaction(pool).await?;
+    // the new content replaces the current one in the storage of the owner
+    let size = req.content.as_str().len() as u64;
+    let except = Some(req.shortcode.as_str());
+    check_quota(&clip.owner, size, except, quota, &mut transaction).await?;
+    let shortcode = req.shortcode.clone();
+    match query::update_clip(req, &current, &mut transaction).await? {
+        Some(clip) => {
+            let entry = ask::Audit {
+                shortcode: Some(shortcode),
+                ..ask::Audit::new(AuditAction::Update, actor)
+            };
+            query::add_audit_entry(entry, &mut transaction).await?;
+            end_transaction(transaction).await?;
+            Ok(clip.try_into()?)
+        }
+        // dropping the transaction rolls back the reference to the new content
+        None => Err(ServiceError::PreconditionFailed),
+    }
+}
+
+// a clip can be edited by its owner and, when it has a password, by anyone who knows it
+pub async fn get_clip_for_edit(
+    req: ask::EditClip,
+    pool: &DatabasePool,
+) -> Result<Clip, ServiceError> {
+    let clip: Clip = query::get_clip(req.shortcode, pool).await?.try_into()?;
+    let clip = check_visible(clip, &req.requester)?;
+
+    let owner = match req.requester.into_inner() {
+        Some(key) => clip.owner.is_owned_by(&key),
+        None => false,
+    };
+    let unlocked = clip.password.has_password() && clip.password == req.password;
+    if owner || unlocked {
+        Ok(clip)
+    } else {
+        Err(ServiceError::PermissionError(
+            "Only the owner of the clip or someone with its password can edit it".to_owned(),
+        ))
+    }
+}
+
+pub async fn list_public_clips(
+    req: ask::ListClips,
+    pool: &DatabasePool,
+) -> Result<Vec<Clip>, ServiceError> {
+    Ok(query::list_public_clips(req, pool)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<_, _>>()?)
+}
+
+// clips can only be added to a collection by their owner, clips posted from the web by anyone
+async fn check_members(
+    clips: &[ShortCode],
+    requester: &Owner,
+    pool: &DatabasePool,
+) -> Result<(), ServiceError> {
+    if clips.len() > MAX_COLLECTION_SIZE {
+        return Err(ClipError::InvalidCollection(format!(
+            "a collection can hold at most {} clips",
+            MAX_COLLECTION_SIZE
+        ))
+        .into());
+    }
+
+    let shortcodes: Vec<&str> = clips.iter().map(ShortCode::as_str).collect();
+    for (i, shortcode) in shortcodes.iter().enumerate() {
+        if shortcodes[..i].contains(shortcode) {
+            return Err(ClipError::InvalidCollection(format!(
+                "`{}` is part of the collection more than once",
+                shortcode
+            ))
+            .into());
+        }
+    }
+    let found = query::get_clips(&shortcodes, pool).await?;
+    for shortcode in clips {
+        let allowed = match found
+            .iter()
+            .find(|clip| clip.shortcode() == shortcode.as_str())
+        {
+            Some(clip) => {
+                let clip = Clip::try_from(clip.clone())?;
+                clip.is_visible_to(requester)
+                    && match requester.clone().into_inner() {
+                        Some(key) => !clip.owner.has_owner() || clip.owner.is_owned_by(&key),
+                        None => !clip.owner.has_owner(),
+                    }
+            }
+            None => false,
+        };
+        if !allowed {
+            return Err(ClipError::InvalidCollection(format!(
+                "`{}` does not exist or can't be added by you",
+                shortcode.as_str()
+            ))
+            .into());
+        }
+    }
+    Ok(())
+}
+
+pub async fn new_collection(
+    req: ask::NewCollection,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<Collection, ServiceError> {
+    check_members(&req.clips, &req.owner, pool).await?;
+
+    let mut transaction = begin_transaction(pool).await?;
+    let collection: Collection = query::new_collection(req, &mut transaction)
+        .await?
+        .try_into()?;
+    let entry = ask::Audit {
+        shortcode: Some(collection.shortcode.clone()),
+        ..ask::Audit::new(AuditAction::CreateCollection, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await?;
+    Ok(collection)
+}
+
+// only the clips the requester can open with the password of the collection are returned
+pub async fn get_collection(
+    req: ask::GetCollection,
+    pool: &DatabasePool,
+) -> Result<Collection, ServiceError> {
+    let mut collection: Collection = query::get_collection(req.shortcode.as_str(), pool)
+        .await?
+        .try_into()?;
+    if collection.password.has_password() && collection.password != req.password {
+        return Err(ServiceError::PermissionError("Invalid password".to_owned()));
+    }
+
+    // the clips are read with the password and the expiration of the collection, their own rows
+    // are not changed
+    for clip in query::get_collection_clips(req.shortcode.as_str(), pool).await? {
+        let mut clip: Clip = clip.try_into()?;
+        if !clip.is_visible_to(&req.requester) {
+            continue;
+        }
+        if clip.password.has_password() && clip.password != req.password {
+            collection.locked.push(clip.shortcode);
+        } else {
+            if !clip.password.has_password() {
+                clip.password = collection.password.clone();
+            }
+            clip.expires = clip.expires.earliest(collection.expires.clone());
+            collection.clips.push(clip);
+        }
+    }
+    Ok(collection)
 }
 
-pub async fn generate_api_key(pool: &DatabasePool) -> Result<ApiKey, ServiceError> {
-    let api_key = ApiKey::default();
-    Ok(query::save_api_key(api_key, pool).await?)
+// collections can only be changed by the API key that created them
+async fn check_collection_owner(
+    shortcode: &ShortCode,
+    requester: &Owner,
+    pool: &DatabasePool,
+) -> Result<(), ServiceError> {
+    let collection: Collection = query::get_collection(shortcode.as_str(), pool)
+        .await?
+        .try_into()?;
+    match requester.clone().into_inner() {
+        Some(key) if collection.owner.is_owned_by(&key) => Ok(()),
+        _ => Err(ServiceError::PermissionError(
+            "Only the owner of the collection can change it".to_owned(),
+        )),
+    }
+}
+
+pub async fn update_collection(
+    req: ask::UpdateCollection,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<Collection, ServiceError> {
+    check_collection_owner(&req.shortcode, &req.requester, pool).await?;
+    check_members(&req.clips, &req.requester, pool).await?;
+
+    let shortcode = req.shortcode.clone();
+    let mut transaction = begin_transaction(pool).await?;
+    let collection = query::update_collection(req, &mut transaction).await?;
+    let entry = ask::Audit {
+        shortcode: Some(shortcode),
+        ..ask::Audit::new(AuditAction::UpdateCollection, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await?;
+    Ok(collection.try_into()?)
+}
+
+pub async fn delete_collection(
+    req: ask::DeleteCollection,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<(), ServiceError> {
+    check_collection_owner(&req.shortcode, &req.requester, pool).await?;
+
+    let mut transaction = begin_transaction(pool).await?;
+    query::delete_collection(req.shortcode.as_str(), &mut transaction).await?;
+    let entry = ask::Audit {
+        shortcode: Some(req.shortcode),
+        ..ask::Audit::new(AuditAction::DeleteCollection, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await
+}
+
+// the tags of public clips, the most used first
+pub async fn popular_tags(limit: u32, pool: &DatabasePool) -> Result<Vec<String>, ServiceError> {
+    Ok(query::get_popular_tags(limit, pool).await?)
+}
+
+pub async fn get_usage(
+    owner: &Owner,
+    quota: StorageQuota,
+    pool: &DatabasePool,
+) -> Result<Usage, ServiceError> {
+    let Some(owner) = owner.as_bytes() else {
+        return Err(ServiceError::NotFound);
+    };
+    let mut transaction = begin_transaction(pool).await?;
+    fill_owner_sizes(owner, &mut transaction).await?;
+    let mut usage: Usage = query::get_usage(owner, None, &mut *transaction)
+        .await?
+        .try_into()?;
+    end_transaction(transaction).await?;
+    usage.quota = quota.0;
+    Ok(usage)
+}
+
+pub async fn generate_api_key(actor: &Actor, pool: &DatabasePool) -> Result<ApiKey, ServiceError> {
+    let mut transaction = begin_transaction(pool).await?;
+    let api_key = query::save_api_key(ApiKey::default(), &mut transaction).await?;
+    let entry = ask::Audit {
+        target_key: query::get_api_key_id(&api_key.clone().into_inner(), &mut *transaction).await?,
+        ..ask::Audit::new(AuditAction::GenerateKey, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await?;
+    Ok(api_key)
 }
 
 pub async fn revoke_api_key(
     api_key: ApiKey,
+    actor: &Actor,
     pool: &DatabasePool,
 ) -> Result<query::RevocationStatus, ServiceError> {
-    Ok(query::revoke_api_key(api_key, pool).await?)
+    let mut transaction = begin_transaction(pool).await?;
+    // the entry is written first, the id of the key is gone once it is revoked
+    let entry = ask::Audit {
+        target_key: query::get_api_key_id(&api_key.clone().into_inner(), &mut *transaction).await?,
+        ..ask::Audit::new(AuditAction::RevokeKey, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    let status = query::revoke_api_key(api_key, &mut transaction).await?;
+    end_transaction(transaction).await?;
+    Ok(status)
 }
 
 pub async fn is_api_key_valid(api_key: ApiKey, pool: &DatabasePool) -> Result<bool, ServiceError> {
-    Ok(query::is_api_key_valid(api_key, pool).await?)
+    let valid = query::is_api_key_valid(api_key, pool).await?;
+    if !valid {
+        METRICS.api_key_failure();
+    }
+    Ok(valid)
 }
 
+// disabled keys are rejected by `is_api_key_valid`, the clips they created are kept
+pub async fn disable_api_key(
+    id: i64,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<(), ServiceError> {
+    let mut transaction = begin_transaction(pool).await?;
+    if !query::disable_api_key(id, &mut transaction).await? {
+        return Err(ServiceError::NotFound);
+    }
+    let entry = ask::Moderation {
+        key: Some(id),
+        ..ask::Moderation::new(ModerationAction::DisableKey)
+    };
+    query::add_moderation_entry(entry, &mut transaction).await?;
+    let entry = ask::Audit {
+        target_key: Some(id),
+        ..ask::Audit::new(AuditAction::DisableKey, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await
+}
+
+async fn clip_summaries(
+    order: query::ClipOrder,
+    limit: u32,
+    pool: &DatabasePool,
+) -> Result<Vec<ClipSummary>, ServiceError> {
+    Ok(query::get_clip_summaries(order, limit, pool)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<_, _>>()?)
+}
+
+// the clips and API keys of the instance for its operator, `limit` of each list
+pub async fn get_dashboard(limit: u32, pool: &DatabasePool) -> Result<Dashboard, ServiceError> {
+    use query::ClipOrder;
+
+    Ok(Dashboard {
+        recent: clip_summaries(ClipOrder::Recent, limit, pool).await?,
+        largest: clip_summaries(ClipOrder::Largest, limit, pool).await?,
+        most_hit: clip_summaries(ClipOrder::MostHit, limit, pool).await?,
+        keys: query::get_key_usage(limit, pool)
+            .await?
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?,
+        storage: query::get_storage_stats(pool).await?.try_into()?,
+    })
+}
+
+// deletes a clip of any owner, its content is deleted when no other clip shares it
+pub async fn delete_clip(
+    shortcode: ShortCode,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<(), ServiceError> {
+    let mut transaction = begin_transaction(pool).await?;
+    if query::delete_clip(shortcode.as_str(), &mut transaction).await? == 0 {
+        return Err(ServiceError::NotFound);
+    }
+    query::delete_unreferenced_blobs(&mut transaction).await?;
+    let entry = ask::Audit {
+        shortcode: Some(shortcode.clone()),
+        ..ask::Audit::new(AuditAction::Delete, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    let entry = ask::Moderation {
+        shortcode: Some(shortcode),
+        ..ask::Moderation::new(ModerationAction::DeleteClip)
+    };
+    query::add_moderation_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await
+}
+
+// clips can be reported by anyone who can open them, reports of protected clips don't need
+// the password
+pub async fn file_report(req: ask::NewReport, pool: &DatabasePool) -> Result<i64, ServiceError> {
+    let clip: Clip = query::get_clip(req.shortcode.clone(), pool)
+        .await?
+        .try_into()?;
+    check_visible(clip, &req.requester)?;
+    Ok(query::new_report(req, pool).await?)
+}
+
+// the open reports and the most recent moderation actions, `limit` of each
+pub async fn get_moderation_queue(
+    limit: u32,
+    pool: &DatabasePool,
+) -> Result<ModerationQueue, ServiceError> {
+    Ok(ModerationQueue {
+        reports: query::get_open_reports(limit, pool)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        log: query::get_moderation_log(limit, pool)
+            .await?
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+// the clip keeps its content for the record, its open reports are resolved
+pub async fn take_down_clip(
+    req: ask::TakeDown,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<(), ServiceError> {
+    let shortcode = req.shortcode.as_str();
+    let reason = req.reason.to_string();
+
+    let mut transaction = begin_transaction(pool).await?;
+    if query::take_down_clip(shortcode, &reason, &req.note, &mut transaction).await? == 0 {
+        return Err(ServiceError::NotFound);
+    }
+    query::resolve_reports(shortcode, &mut transaction).await?;
+    let entry = ask::Moderation {
+        shortcode: Some(req.shortcode.clone()),
+        note: Some(format!("{}: {}", reason, req.note)),
+        ..ask::Moderation::new(ModerationAction::TakeDown)
+    };
+    query::add_moderation_entry(entry, &mut transaction).await?;
+    let entry = ask::Audit {
+        shortcode: Some(req.shortcode),
+        ..ask::Audit::new(AuditAction::TakeDown, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await
+}
+
+// serves a clip that was taken down again
+pub async fn restore_clip(
+    shortcode: ShortCode,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<(), ServiceError> {
+    let mut transaction = begin_transaction(pool).await?;
+    if query::restore_clip(shortcode.as_str(), &mut transaction).await? == 0 {
+        return Err(ServiceError::NotFound);
+    }
+    let entry = ask::Moderation {
+        shortcode: Some(shortcode.clone()),
+        ..ask::Moderation::new(ModerationAction::Restore)
+    };
+    query::add_moderation_entry(entry, &mut transaction).await?;
+    let entry = ask::Audit {
+        shortcode: Some(shortcode),
+        ..ask::Audit::new(AuditAction::Restore, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await
+}
+
+// resolves a report without acting on the clip
+pub async fn dismiss_report(
+    id: i64,
+    actor: &Actor,
+    pool: &DatabasePool,
+) -> Result<(), ServiceError> {
+    let mut transaction = begin_transaction(pool).await?;
+    let Some(shortcode) = query::dismiss_report(id, &mut transaction).await? else {
+        return Err(ServiceError::NotFound);
+    };
+    let shortcode = ShortCode::from(shortcode);
+    let entry = ask::Moderation {
+        shortcode: Some(shortcode.clone()),
+        report: Some(id),
+        ..ask::Moderation::new(ModerationAction::DismissReport)
+    };
+    query::add_moderation_entry(entry, &mut transaction).await?;
+    let entry = ask::Audit {
+        shortcode: Some(shortcode),
+        ..ask::Audit::new(AuditAction::DismissReport, actor)
+    };
+    query::add_audit_entry(entry, &mut transaction).await?;
+    end_transaction(transaction).await
+}
+
+// moves the content of clips stored before blobs were added into blobs, which compresses and
+// deduplicates it. Returns the number of moved clips
+pub async fn migrate_content(pool: &DatabasePool) -> Result<u64, ServiceError> {
+    let mut moved = 0;
+    let mut after = String::new();
+    loop {
+        let clips = query::get_inline_clips(&after, 100, pool).await?;
+        let Some(last) = clips.last() else {
+            return Ok(moved);
+        };
+        after = last.shortcode().to_owned();
+
+        for clip in &clips {
+            let content = clip.stored_content()?.decompress()?;
+            let mut transaction = begin_transaction(pool).await?;
+            // clips changed in the meantime were moved by the update
+            if query::move_to_blob(clip, &content, &mut transaction).await? {
+                end_transaction(transaction).await?;
+                moved += 1;
+            }
+        }
+    }
+}
+
+pub async fn get_audit_log(
+    req: ask::AuditQuery,
+    pool: &DatabasePool,
+) -> Result<Vec<AuditEntry>, ServiceError> {
+    Ok(query::get_audit_log(req, pool)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<_, _>>()?)
+}
+
+// deletes expired clips and the content no clip refers to anymore
 pub async fn delete_expired(pool: &DatabasePool) -> Result<u64, ServiceError> {
-    Ok(query::delete_expired(pool).await?)
+    let mut transaction = begin_transaction(pool).await?;
+    query::delete_expired_collections(&mut transaction).await?;
+    let deleted = query::delete_expired(&mut transaction).await?;
+    let collected = query::delete_unreferenced_blobs(&mut transaction).await?;
+    end_transaction(transaction).await?;
+
+    if collected > 0 {
+        tracing::debug!(collected, "deleted unreferenced blobs");
+    }
+    METRICS.clips_expired(deleted);
+    Ok(deleted)
+}
+
+pub async fn ping_database(pool: &DatabasePool) -> Result<(), ServiceError> {
+    Ok(query::ping(pool).await?)
+}
+
+// versions of the migrations known to this build that are not applied to the database
+pub async fn pending_migrations(pool: &DatabasePool) -> Result<Vec<i64>, ServiceError> {
+    let applied = query::applied_migrations(pool).await?;
+    Ok(MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect())
 }
@@ -1,11 +1,17 @@
 use crate::domain::clip::field;
-use crate::ShortCode;
+use crate::domain::{audit, report};
+use crate::{ShortCode, Time};
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetClip {
     pub shortcode: ShortCode,
     pub password: field::Password,
+    // the API key of the requester, private clips are only returned to their owner
+    #[serde(skip)]
+    pub requester: field::Owner,
 }
 
 impl GetClip {
@@ -13,6 +19,7 @@ impl GetClip {
         Self {
             shortcode: ShortCode::from(shortcode),
             password: field::Password::default(),
+            requester: field::Owner::default(),
         }
     }
 }
@@ -22,6 +29,7 @@ impl From<ShortCode> for GetClip {
         Self {
             shortcode: value,
             password: field::Password::default(),
+            requester: field::Owner::default(),
         }
     }
 }
@@ -32,19 +40,239 @@ impl From<&str> for GetClip {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct NewClip {
+    #[schema(inline)]
     pub content: field::Content,
+    #[schema(inline)]
     pub title: field::Title,
+    #[schema(inline)]
     pub expires: field::Expires,
+    #[schema(inline)]
     pub password: field::Password,
+    #[serde(skip)] // set by the server from the API key used to create the clip
+    pub owner: field::Owner,
+    #[serde(default)]
+    #[schema(inline)]
+    pub visibility: field::Visibility,
+    #[serde(default)]
+    #[schema(inline)]
+    pub tags: field::Tags,
+    #[serde(default)]
+    #[schema(inline)]
+    pub forked_from: field::ForkedFrom,
+    // the author was warned about secrets in the content and stores it anyway, only the `warn`
+    // policy of the server lets them
+    #[serde(default)]
+    pub allow_secrets: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct UpdateClip {
+    #[schema(inline)]
     pub content: field::Content,
+    #[schema(inline)]
     pub title: field::Title,
+    #[schema(inline)]
     pub expires: field::Expires,
+    #[schema(inline)]
     pub password: field::Password,
+    #[schema(inline)]
     pub shortcode: field::ShortCode,
+    // `None` keeps the visibility of the clip
+    #[serde(default)]
+    #[schema(inline)]
+    pub visibility: Option<field::Visibility>,
+    // the update only applies when the clip is at one of these versions (`Clip::version`),
+    // `None` skips the check
+    #[serde(skip)]
+    pub expected_versions: Option<Vec<String>>,
+    // the API key of the requester, only the owner can update a private clip
+    #[serde(skip)]
+    pub requester: field::Owner,
+    // see `NewClip::allow_secrets`
+    #[serde(default)]
+    pub allow_secrets: bool,
+}
+
+// lists the most recent public clips without a password, newest first. Only clips tagged
+// with `tag` are listed when it is set
+#[derive(Debug)]
+pub struct ListClips {
+    pub tag: Option<String>,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+#[derive(Debug)]
+pub struct NewCollection {
+    pub title: field::Title,
+    pub expires: field::Expires,
+    pub password: field::Password,
+    // set by the server from the API key used to create the collection
+    pub owner: field::Owner,
+    // the clips in the order they are shown
+    pub clips: Vec<ShortCode>,
+}
+
+#[derive(Debug)]
+pub struct GetCollection {
+    pub shortcode: ShortCode,
+    pub password: field::Password,
+    // the API key of the requester, private clips are only shown to their owner
+    pub requester: field::Owner,
+}
+
+// an update replaces the title, expiration, password and clips of the collection
+#[derive(Debug)]
+pub struct UpdateCollection {
+    pub shortcode: ShortCode,
+    pub title: field::Title,
+    pub expires: field::Expires,
+    pub password: field::Password,
+    pub clips: Vec<ShortCode>,
+    // only the API key that created the collection can change it
+    pub requester: field::Owner,
+}
+
+#[derive(Debug)]
+pub struct DeleteCollection {
+    pub shortcode: ShortCode,
+    pub requester: field::Owner,
+}
+
+// opens a clip for editing from the web, authorized by the password of the clip or the API key
+// of its owner
+#[derive(Debug, Clone)]
+pub struct EditClip {
+    pub shortcode: ShortCode,
+    pub password: field::Password,
+    pub requester: field::Owner,
+}
+
+// diffs the content of two clips, each one is unlocked with its own password
+#[derive(Debug)]
+pub struct GetDiff {
+    pub a: GetClip,
+    pub b: GetClip,
+    // also diff the words of replaced lines
+    pub words: bool,
+}
+
+// forks a clip, the fork starts with the content and title of the original
+#[derive(Debug)]
+pub struct ForkClip {
+    pub shortcode: ShortCode,
+    pub password: field::Password,
+    // owns the fork, the original must be visible to it
+    pub requester: field::Owner,
+}
+
+// requests the view analytics of a clip, `owner` is the API key of the requester
+#[derive(Debug)]
+pub struct GetClipStats {
+    pub shortcode: ShortCode,
+    pub owner: field::Owner,
+}
+
+// views of a clip for a single day, referrer host and user agent family
+#[derive(Debug, Clone)]
+pub struct ViewCount {
+    pub shortcode: ShortCode,
+    pub day: NaiveDate,
+    pub referrer: Option<String>,
+    pub agent: String,
+    pub views: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Visitor {
+    pub shortcode: ShortCode,
+    pub day: NaiveDate,
+    // hashed, never the raw address of the visitor
+    pub visitor: String,
+}
+
+// HitBatch is everything the hit counter aggregated between two flushes
+#[derive(Debug, Default)]
+pub struct HitBatch {
+    pub hits: Vec<(ShortCode, u32)>,
+    pub views: Vec<ViewCount>,
+    pub visitors: Vec<Visitor>,
+}
+
+// reports a clip the requester can open to the admin
+#[derive(Debug)]
+pub struct NewReport {
+    pub shortcode: ShortCode,
+    pub reason: report::Reason,
+    pub contact: report::Contact,
+    pub requester: field::Owner,
+}
+
+// takes a clip down and resolves its open reports
+#[derive(Debug)]
+pub struct TakeDown {
+    pub shortcode: ShortCode,
+    pub reason: field::TakedownReason,
+    // shown instead of the content of the clip
+    pub note: String,
+}
+
+// an entry of the moderation log, written along with the action it records
+#[derive(Debug, Clone)]
+pub struct Moderation {
+    pub action: report::ModerationAction,
+    pub shortcode: Option<ShortCode>,
+    pub report: Option<i64>,
+    pub key: Option<i64>,
+    pub note: Option<String>,
+}
+
+impl Moderation {
+    pub fn new(action: report::ModerationAction) -> Self {
+        Self {
+            action,
+            shortcode: None,
+            report: None,
+            key: None,
+            note: None,
+        }
+    }
+}
+
+// an entry of the audit log, written in the transaction of the action it records
+#[derive(Debug, Clone)]
+pub struct Audit {
+    pub action: audit::AuditAction,
+    pub actor: audit::Actor,
+    pub shortcode: Option<ShortCode>,
+    // the id of the API key a key action is about
+    pub target_key: Option<i64>,
+}
+
+impl Audit {
+    pub fn new(action: audit::AuditAction, actor: &audit::Actor) -> Self {
+        Self {
+            action,
+            actor: actor.clone(),
+            shortcode: None,
+            target_key: None,
+        }
+    }
+}
+
+// selects entries of the audit log, the newest first. Filters that aren't set match every entry
+#[derive(Debug, Default)]
+pub struct AuditQuery {
+    pub action: Option<audit::AuditAction>,
+    pub shortcode: Option<ShortCode>,
+    // entries made with the API key or about it
+    pub key: Option<i64>,
+    pub ip: Option<String>,
+    pub since: Option<Time>,
+    pub until: Option<Time>,
+    // only entries older than this one, to page through the log
+    pub before: Option<i64>,
+    pub limit: u32,
 }
@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// ServiceMetrics are counters updated by the service actions and exported by the web layer
+#[derive(Debug)]
+pub struct ServiceMetrics {
+    clips_created: AtomicU64,
+    clips_expired: AtomicU64,
+    api_key_failures: AtomicU64,
+}
+
+// the service layer is made of free functions, so the counters live for the whole process
+pub static METRICS: ServiceMetrics = ServiceMetrics {
+    clips_created: AtomicU64::new(0),
+    clips_expired: AtomicU64::new(0),
+    api_key_failures: AtomicU64::new(0),
+};
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ServiceStats {
+    pub clips_created: u64,
+    pub clips_expired: u64,
+    pub api_key_failures: u64,
+}
+
+impl ServiceMetrics {
+    pub fn clip_created(&self) {
+        self.clips_created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn clips_created(&self, count: u64) {
+        self.clips_created.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn clips_expired(&self, count: u64) {
+        self.clips_expired.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn api_key_failure(&self) {
+        self.api_key_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> ServiceStats {
+        ServiceStats {
+            clips_created: self.clips_created.load(Ordering::Relaxed),
+            clips_expired: self.clips_expired.load(Ordering::Relaxed),
+            api_key_failures: self.api_key_failures.load(Ordering::Relaxed),
+        }
+    }
+}
@@ -1,27 +1,40 @@
 pub mod action;
-pub mod ask; // service layer models // service layer models
+pub mod ask; // service layer models
+pub mod metrics;
 
+use crate::domain::audit::AuditError;
+use crate::domain::clip::field::Takedown;
+use crate::domain::report::ReportError;
 use crate::{ClipError, DataError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ServiceError {
     #[error("clip error: {0}")]
     Clip(#[from] ClipError),
+    #[error("report error: {0}")]
+    Report(#[from] ReportError),
+    #[error("audit error: {0}")]
+    Audit(#[from] AuditError),
     #[error("database error: {0}")]
     Data(DataError),
     #[error("not found")]
     NotFound,
     #[error("insufficient permission to access the clip")]
     PermissionError(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("the clip was changed since it was read")]
+    PreconditionFailed,
+    #[error("the clip was taken down")]
+    TakenDown(Takedown),
+    #[error("storage quota exceeded: {0}")]
+    QuotaExceeded(String),
 }
 
 impl From<DataError> for ServiceError {
     fn from(err: DataError) -> Self {
         match err {
-            DataError::Database(d) => match d {
-                sqlx::Error::RowNotFound => Self::NotFound,
-                other => Self::Data(DataError::Database(other)),
-            },
+            DataError::Database(d) => d.into(),
         }
     }
 }
@@ -30,6 +43,9 @@ impl From<sqlx::Error> for ServiceError {
     fn from(value: sqlx::Error) -> Self {
         match value {
             sqlx::Error::RowNotFound => Self::NotFound,
+            sqlx::Error::Database(e) if e.is_unique_violation() => {
+                Self::Conflict(e.message().to_owned())
+            }
             other => Self::Data(DataError::Database(other)),
         }
     }
@@ -0,0 +1,565 @@
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use rocket::form::{Contextual, Form};
+use rocket::http::{Cookie, CookieJar, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::content::RawHtml;
+use rocket::response::{status, Redirect};
+use rocket::{uri, Either, State};
+
+use super::metrics::constant_time_eq;
+use super::{ctx, form, renderer::Renderer, PageError, ADMIN_COOKIE};
+use crate::data::AppDatabase;
+use crate::domain::audit::Actor;
+use crate::service::{action, ask, ServiceError};
+use crate::ShortCode;
+
+// the number of clips in each list of the dashboard and of API keys
+const DASHBOARD_SIZE: u32 = 20;
+// the number of open reports and of moderation log entries on the reports page
+const QUEUE_SIZE: u32 = 50;
+
+// AdminToken is the credential of the operator of the instance. The admin pages are disabled
+// when it isn't set
+#[derive(Debug, Clone, Default)]
+pub struct AdminToken(pub Option<String>);
+
+#[derive(Debug)]
+pub enum AdminError {
+    Disabled,
+    Unauthorized,
+}
+
+// AdminSessions are the sessions started on the login page. The cookie holds a random session
+// id instead of the token, a session ends on logout or when the server restarts
+#[derive(Debug, Default)]
+pub struct AdminSessions(Mutex<HashSet<String>>);
+
+impl AdminSessions {
+    fn start(&self) -> String {
+        let id: [u8; 32] = rand::random();
+        let id = id
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        self.0
+            .lock()
+            .expect("admin sessions lock poisoned")
+            .insert(id.clone());
+        id
+    }
+
+    fn is_active(&self, id: &str) -> bool {
+        self.0
+            .lock()
+            .expect("admin sessions lock poisoned")
+            .contains(id)
+    }
+
+    fn end(&self, id: &str) {
+        self.0
+            .lock()
+            .expect("admin sessions lock poisoned")
+            .remove(id);
+    }
+}
+
+// Admin is a request guard that checks the admin token sent as a bearer token, or the session
+// cookie set by the login page
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = AdminError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected = match req.rocket().state::<AdminToken>() {
+            Some(AdminToken(Some(token))) => token,
+            _ => return Outcome::Error((Status::NotFound, AdminError::Disabled)),
+        };
+
+        let bearer = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "));
+        let authorized = match bearer {
+            Some(token) => constant_time_eq(token.as_bytes(), expected.as_bytes()),
+            None => match (
+                req.cookies().get(ADMIN_COOKIE),
+                req.rocket().state::<AdminSessions>(),
+            ) {
+                (Some(cookie), Some(sessions)) => sessions.is_active(cookie.value()),
+                _ => false,
+            },
+        };
+
+        if authorized {
+            Outcome::Success(Admin)
+        } else {
+            Outcome::Error((Status::Unauthorized, AdminError::Unauthorized))
+        }
+    }
+}
+
+fn render_login(renderer: &Renderer<'_>, errors: &[&str]) -> status::Custom<RawHtml<String>> {
+    status::Custom(
+        Status::Unauthorized,
+        RawHtml(renderer.render(ctx::AdminLogin::default(), errors)),
+    )
+}
+
+#[rocket::get("/admin")]
+pub async fn dashboard(
+    admin: Result<Admin, AdminError>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    match admin {
+        Ok(_) => (),
+        Err(AdminError::Disabled) => return Err(PageError::NotFound("not found".to_owned())),
+        Err(AdminError::Unauthorized) => return Ok(render_login(renderer, &[])),
+    }
+
+    match action::get_dashboard(DASHBOARD_SIZE, database.get_pool()).await {
+        Ok(dashboard) => {
+            let context = ctx::AdminDashboard::new(dashboard);
+            Ok(status::Custom(
+                Status::Ok,
+                RawHtml(renderer.render(context, &[])),
+            ))
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to load the admin dashboard");
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
+}
+
+#[rocket::post("/admin/login", data = "<form>")]
+pub async fn login(
+    cookies: &CookieJar<'_>,
+    form: Form<Contextual<'_, form::AdminLogin>>,
+    admin_token: &State<AdminToken>,
+    sessions: &State<AdminSessions>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<Either<Redirect, status::Custom<RawHtml<String>>>, PageError> {
+    let expected = match &admin_token.0 {
+        Some(token) => token,
+        None => return Err(PageError::NotFound("not found".to_owned())),
+    };
+    let provided = form
+        .value
+        .as_ref()
+        .map(|form| form.token.trim())
+        .unwrap_or_default();
+
+    if constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+        cookies.add(Cookie::new(ADMIN_COOKIE, sessions.start()));
+        Ok(Either::Left(Redirect::to(uri!(dashboard))))
+    } else {
+        Ok(Either::Right(render_login(
+            renderer,
+            &["The admin token is invalid"],
+        )))
+    }
+}
+
+#[rocket::post("/admin/logout")]
+pub fn logout(cookies: &CookieJar<'_>, sessions: &State<AdminSessions>) -> Redirect {
+    if let Some(cookie) = cookies.get(ADMIN_COOKIE) {
+        sessions.end(cookie.value());
+    }
+    cookies.remove(Cookie::from(ADMIN_COOKIE));
+    Redirect::to(uri!(dashboard))
+}
+
+#[rocket::post("/admin/clips/<shortcode>/delete")]
+pub async fn delete_clip(
+    _admin: Admin,
+    actor: Actor,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+) -> Result<Redirect, PageError> {
+    match action::delete_clip(shortcode.clone(), &actor.admin(), database.get_pool()).await {
+        Ok(()) => {
+            tracing::info!(shortcode = %shortcode.as_str(), "clip deleted by an admin");
+            Ok(Redirect::to(uri!(dashboard)))
+        }
+        Err(ServiceError::NotFound) => Err(PageError::NotFound("Clip Not found".to_owned())),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to delete clip");
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
+}
+
+#[rocket::post("/admin/keys/<id>/disable")]
+pub async fn disable_api_key(
+    _admin: Admin,
+    actor: Actor,
+    id: i64,
+    database: &State<AppDatabase>,
+) -> Result<Redirect, PageError> {
+    match action::disable_api_key(id, &actor.admin(), database.get_pool()).await {
+        Ok(()) => {
+            tracing::info!(key = id, "API key disabled by an admin");
+            Ok(Redirect::to(uri!(dashboard)))
+        }
+        Err(ServiceError::NotFound) => Err(PageError::NotFound("API key not found".to_owned())),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to disable API key");
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
+}
+
+#[rocket::get("/admin/reports")]
+pub async fn reports(
+    admin: Result<Admin, AdminError>,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    match admin {
+        Ok(_) => (),
+        Err(AdminError::Disabled) => return Err(PageError::NotFound("not found".to_owned())),
+        Err(AdminError::Unauthorized) => return Ok(render_login(renderer, &[])),
+    }
+
+    match action::get_moderation_queue(QUEUE_SIZE, database.get_pool()).await {
+        Ok(queue) => {
+            let context = ctx::AdminReports::new(queue);
+            Ok(status::Custom(
+                Status::Ok,
+                RawHtml(renderer.render(context, &[])),
+            ))
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to load the moderation queue");
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
+}
+
+// the result of a moderation action, the admin is sent back to the reports
+fn moderated(result: Result<(), ServiceError>, what: &str) -> Result<Redirect, PageError> {
+    match result {
+        Ok(()) => Ok(Redirect::to(uri!(reports))),
+        Err(ServiceError::NotFound) => Err(PageError::NotFound(format!("{} not found", what))),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to moderate {}", what);
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
+}
+
+#[rocket::post("/admin/reports/<id>/dismiss")]
+pub async fn dismiss_report(
+    _admin: Admin,
+    actor: Actor,
+    id: i64,
+    database: &State<AppDatabase>,
+) -> Result<Redirect, PageError> {
+    moderated(
+        action::dismiss_report(id, &actor.admin(), database.get_pool()).await,
+        "report",
+    )
+}
+
+#[rocket::post("/admin/clips/<shortcode>/takedown", data = "<form>")]
+pub async fn take_down_clip(
+    _admin: Admin,
+    actor: Actor,
+    shortcode: ShortCode,
+    form: Form<form::TakeDown>,
+    database: &State<AppDatabase>,
+) -> Result<Redirect, PageError> {
+    let form = form.into_inner();
+    let req = ask::TakeDown {
+        shortcode,
+        reason: form.reason,
+        note: form.note.trim().to_owned(),
+    };
+    moderated(
+        action::take_down_clip(req, &actor.admin(), database.get_pool()).await,
+        "clip",
+    )
+}
+
+#[rocket::post("/admin/clips/<shortcode>/restore")]
+pub async fn restore_clip(
+    _admin: Admin,
+    actor: Actor,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+) -> Result<Redirect, PageError> {
+    moderated(
+        action::restore_clip(shortcode, &actor.admin(), database.get_pool()).await,
+        "clip",
+    )
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        dashboard,
+        login,
+        logout,
+        delete_clip,
+        disable_api_key,
+        reports,
+        dismiss_report,
+        take_down_clip,
+        restore_clip
+    ]
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::{ContentType, Cookie, Header, Status};
+    use rocket::local::blocking::Client;
+
+    use super::AdminToken;
+    use crate::domain::audit::AuditAction;
+    use crate::test::new_async_runtime;
+    use crate::web::api::test::new_api_key;
+    use crate::web::api::v1::ClipResponse;
+    use crate::web::audit::AuditLogResponse;
+    use crate::web::test::{new_rocket_client, new_rocket_config};
+    use crate::web::ADMIN_COOKIE;
+
+    #[test]
+    fn admin_pages_are_disabled_without_a_token() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+
+        let response = client.get("/admin").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client
+            .post("/admin/keys/1/disable")
+            .header(Header::new("Authorization", "Bearer "))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn admins_delete_clips_and_disable_keys() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.admin_token = AdminToken(Some("secret".to_owned()));
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build a rocket instance");
+        let key = new_api_key(&client);
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"content": "leaked", "title": "oops"}"#)
+            .dispatch();
+        let clip: ClipResponse = response.into_json().unwrap();
+
+        let response = client.get("/admin").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .post("/admin/login")
+            .header(ContentType::Form)
+            .body("token=wrong")
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .post("/admin/login")
+            .header(ContentType::Form)
+            .body("token=secret")
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+
+        let response = client.get("/admin").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let html = response.into_string().unwrap();
+        assert!(html.contains(&format!("/admin/clips/{}/delete", clip.shortcode)));
+        assert!(html.contains("/admin/keys/1/disable"));
+
+        let response = client.post("/admin/keys/1/disable").dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client.get("/api/v1/clips").header(key).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let log: AuditLogResponse = client
+            .get("/admin/audit?key=1")
+            .dispatch()
+            .into_json()
+            .unwrap();
+        let disabled = &log.entries[0];
+        assert_eq!(disabled.action, AuditAction::DisableKey);
+        assert!(disabled.admin && disabled.target_key == Some(1));
+
+        let response = client
+            .post(format!("/admin/clips/{}/delete", clip.shortcode))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client
+            .get(format!("/api/v1/clips/{}", clip.shortcode))
+            .header(new_api_key(&client))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client.post("/admin/logout").dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client.post("/admin/keys/1/disable").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn reported_clips_are_taken_down_and_restored() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.admin_token = AdminToken(Some("secret".to_owned()));
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build a rocket instance");
+        let key = new_api_key(&client);
+        let admin = Header::new("Authorization", "Bearer secret");
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"content": "someone else's secret", "title": "leak"}"#)
+            .dispatch();
+        let clip: ClipResponse = response.into_json().unwrap();
+
+        let response = client
+            .post(format!("/clip/{}/report", clip.shortcode))
+            .header(ContentType::Form)
+            .body("reason=&contact=")
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client
+            .post(format!("/clip/{}/report", clip.shortcode))
+            .header(ContentType::Form)
+            .body("reason=it+leaks+my+password&contact=me%40example.com")
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+
+        let response = client
+            .get("/admin/reports")
+            .header(admin.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let html = response.into_string().unwrap();
+        assert!(html.contains("it leaks my password"));
+        assert!(html.contains(&format!("/admin/clips/{}/takedown", clip.shortcode)));
+
+        let response = client
+            .post(format!("/admin/clips/{}/takedown", clip.shortcode))
+            .header(admin.clone())
+            .header(ContentType::Form)
+            .body("reason=legal&note=court+order")
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client.get(format!("/clip/{}", clip.shortcode)).dispatch();
+        assert_eq!(response.status(), Status::UnavailableForLegalReasons);
+        assert!(response.into_string().unwrap().contains("court order"));
+        let response = client
+            .get(format!("/api/v1/clips/{}", clip.shortcode))
+            .header(key.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::UnavailableForLegalReasons);
+
+        // the report is resolved by the takedown, a new one is dismissed
+        let response = client
+            .get("/admin/reports")
+            .header(admin.clone())
+            .dispatch();
+        let html = response.into_string().unwrap();
+        assert!(!html.contains("/admin/reports/1/dismiss"));
+        assert!(html.contains("take_down"));
+
+        let response = client
+            .post(format!("/admin/clips/{}/restore", clip.shortcode))
+            .header(admin.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client.get(format!("/clip/{}", clip.shortcode)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .post(format!("/clip/{}/report", clip.shortcode))
+            .header(ContentType::Form)
+            .body("reason=spam&contact=")
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let response = client
+            .post("/admin/reports/2/dismiss")
+            .header(admin.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client
+            .post("/admin/reports/2/dismiss")
+            .header(admin.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client
+            .get("/admin/reports")
+            .header(admin.clone())
+            .dispatch();
+        let html = response.into_string().unwrap();
+        assert!(html.contains("No open reports"));
+        assert!(html.contains("restore"));
+        assert!(html.contains("dismiss_report"));
+
+        let log: AuditLogResponse = client
+            .get(format!("/admin/audit?shortcode={}", clip.shortcode))
+            .header(admin)
+            .dispatch()
+            .into_json()
+            .unwrap();
+        let actions = log.entries.iter().map(|e| e.action).collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::DismissReport,
+                AuditAction::Restore,
+                AuditAction::TakeDown,
+                AuditAction::Create
+            ]
+        );
+        assert!(log.entries[..3].iter().all(|e| e.admin));
+    }
+
+    #[test]
+    fn login_cookies_hold_a_session_instead_of_the_token() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.admin_token = AdminToken(Some("secret".to_owned()));
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build rocket instance");
+
+        let response = client
+            .get("/admin")
+            .cookie(Cookie::new(ADMIN_COOKIE, "secret"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let response = client
+            .post("/admin/login")
+            .header(ContentType::Form)
+            .body("token=secret")
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let session = response
+            .cookies()
+            .get(ADMIN_COOKIE)
+            .unwrap()
+            .value()
+            .to_owned();
+        assert!(!session.contains("secret"));
+
+        let response = client.post("/admin/logout").dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let response = client
+            .get("/admin")
+            .cookie(Cookie::new(ADMIN_COOKIE, session))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+}
@@ -0,0 +1,790 @@
+pub mod v1;
+
+use std::str::FromStr;
+
+use base64::{engine::general_purpose, Engine};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{CookieJar, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder};
+use rocket::serde::json::{self, Json};
+use rocket::{Response, State};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::data::AppDatabase;
+use crate::domain::audit::Actor;
+use crate::domain::clip::field::{Owner, Tags};
+use crate::domain::limits::StorageQuota;
+use crate::domain::report::ReportError;
+use crate::domain::secret::SecretScanner;
+use crate::domain::stats::ClipStats;
+use crate::service::ask::{ListClips, NewClip, UpdateClip};
+use crate::service::metrics::METRICS;
+use crate::service::{self, action};
+use crate::web::PASSWORD_COOKIE;
+use crate::{Clip, ClipError, ServiceError};
+
+use super::conditional::{Conditional, IfMatch, Preconditions, Validators};
+use super::hitcounter::{HitCounter, View};
+use super::problem::{FieldError, Problem};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+// the unversioned API, kept as an alias of the first version for existing clients
+pub const LEGACY_API_BASE: &str = "/api/clip";
+
+// Responder enables rocket to respond with this enum type directly
+#[derive(rocket::Responder, Debug, thiserror::Error, Serialize)]
+pub enum ApiKeyError {
+    #[error("API key not found")]
+    #[response(status = 404, content_type = "json")]
+    NotFound(String),
+    #[error("invalid API key format")]
+    #[response(status = 400, content_type = "json")]
+    DecodeError(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ApiKey(Vec<u8>);
+
+impl ApiKey {
+    pub fn to_base64(&self) -> String {
+        general_purpose::STANDARD.encode(self.0.as_slice())
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl Default for ApiKey {
+    fn default() -> Self {
+        let key = (0..16).map(|_| rand::random::<u8>()).collect();
+
+        Self(key)
+    }
+}
+
+impl FromStr for ApiKey {
+    type Err = ApiKeyError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        general_purpose::STANDARD
+            .decode(s)
+            .map(ApiKey)
+            .map_err(|e| Self::Err::DecodeError(e.to_string()))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("not found")]
+    NotFound(String),
+
+    #[error("server error")]
+    ServerError(String),
+
+    #[error("forbidden")]
+    Forbidden(String),
+
+    #[error("key error")]
+    KeyError(String),
+
+    #[error("conflict")]
+    Conflict(String),
+
+    #[error("bad request")]
+    BadRequest(String),
+
+    #[error("validation error")]
+    Validation(String, Vec<FieldError>),
+
+    #[error("precondition failed")]
+    PreconditionFailed(String),
+
+    #[error("precondition required")]
+    PreconditionRequired(String),
+
+    #[error("payload too large")]
+    PayloadTooLarge(String),
+
+    #[error("gone")]
+    Gone(String),
+
+    #[error("unavailable for legal reasons")]
+    UnavailableForLegalReasons(String),
+
+    #[error("insufficient storage")]
+    InsufficientStorage(String),
+}
+
+impl ApiError {
+    fn status(&self) -> Status {
+        match self {
+            Self::NotFound(_) => Status::NotFound,
+            Self::ServerError(_) => Status::InternalServerError,
+            Self::Forbidden(_) => Status::Forbidden,
+            Self::KeyError(_) => Status::Unauthorized,
+            Self::Conflict(_) => Status::Conflict,
+            Self::BadRequest(_) => Status::BadRequest,
+            Self::Validation(_, _) => Status::UnprocessableEntity,
+            Self::PreconditionFailed(_) => Status::PreconditionFailed,
+            Self::PreconditionRequired(_) => Status::new(428),
+            Self::PayloadTooLarge(_) => Status::PayloadTooLarge,
+            Self::Gone(_) => Status::Gone,
+            Self::UnavailableForLegalReasons(_) => Status::UnavailableForLegalReasons,
+            Self::InsufficientStorage(_) => Status::InsufficientStorage,
+        }
+    }
+}
+
+impl From<ApiError> for Problem {
+    fn from(value: ApiError) -> Self {
+        let problem = Problem::new(value.status());
+        match value {
+            ApiError::Validation(detail, errors) => problem.with_detail(detail).with_errors(errors),
+            ApiError::NotFound(detail)
+            | ApiError::ServerError(detail)
+            | ApiError::Forbidden(detail)
+            | ApiError::KeyError(detail)
+            | ApiError::Conflict(detail)
+            | ApiError::BadRequest(detail)
+            | ApiError::PreconditionFailed(detail)
+            | ApiError::PreconditionRequired(detail)
+            | ApiError::PayloadTooLarge(detail)
+            | ApiError::Gone(detail)
+            | ApiError::UnavailableForLegalReasons(detail)
+            | ApiError::InsufficientStorage(detail) => problem.with_detail(detail),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
+        Problem::from(self).respond_to(req)
+    }
+}
+
+impl From<ServiceError> for ApiError {
+    fn from(value: ServiceError) -> Self {
+        match value {
+            // every secret is reported, the client can point at each of them
+            ServiceError::Clip(ClipError::Secrets(findings)) => Self::Validation(
+                "the content contains possible secrets".to_owned(),
+                findings.iter().map(FieldError::from).collect(),
+            ),
+            ServiceError::Clip(ClipError::ContentTooLarge(max)) => {
+                Self::PayloadTooLarge(format!("the content is larger than {} bytes", max))
+            }
+            ServiceError::Clip(
+                c @ (ClipError::InvalidPassword(_)
+                | ClipError::InvalidTitle(_)
+                | ClipError::EmptyContent
+                | ClipError::InvalidDate(_)
+                | ClipError::DateParse(_)
+                | ClipError::InvalidVisibility(_)
+                | ClipError::InvalidTags(_)
+                | ClipError::InvalidCollection(_)),
+            ) => Self::Validation("the clip is invalid".to_owned(), vec![FieldError::from(&c)]),
+            // a stored clip that can't be read back is not the fault of the client
+            ServiceError::Clip(
+                c @ (ClipError::Id(_) | ClipError::Hits(_) | ClipError::Encoding(_)),
+            ) => {
+                tracing::error!(error = %c, "failed to read a stored clip");
+                Self::ServerError("a server error occurred".to_owned())
+            }
+            // moderation actions are only read from the database, an unknown one is a server error
+            ServiceError::Report(e @ ReportError::UnknownAction(_)) => {
+                tracing::error!(error = %e, "invalid moderation log");
+                Self::ServerError("a server error occurred".to_owned())
+            }
+            ServiceError::Report(e) => Self::Validation(
+                "the report is invalid".to_owned(),
+                vec![FieldError::from(&e)],
+            ),
+            // the audit log is only read from the database
+            ServiceError::Audit(e) => {
+                tracing::error!(error = %e, "invalid audit log");
+                Self::ServerError("a server error occurred".to_owned())
+            }
+            ServiceError::NotFound => Self::NotFound("clip not found".to_owned()),
+            ServiceError::Data(e) => {
+                tracing::error!(error = %e, "database error");
+                Self::ServerError("a server error occurred".to_owned())
+            }
+            ServiceError::PermissionError(msg) => Self::Forbidden(msg),
+            // the message of the database names tables and columns, it is only logged
+            ServiceError::Conflict(msg) => {
+                tracing::warn!(error = %msg, "write conflict");
+                Self::Conflict("the write conflicts with existing data, retry it".to_owned())
+            }
+            ServiceError::PreconditionFailed => Self::PreconditionFailed(
+                "the clip was changed since it was read, fetch it again and retry".to_owned(),
+            ),
+            ServiceError::TakenDown(takedown) if takedown.reason.is_legal() => {
+                Self::UnavailableForLegalReasons(takedown.note)
+            }
+            ServiceError::TakenDown(takedown) => Self::Gone(takedown.note),
+            ServiceError::QuotaExceeded(msg) => Self::InsufficientStorage(msg),
+        }
+    }
+}
+
+impl<'r> From<json::Error<'r>> for ApiError {
+    fn from(value: json::Error<'r>) -> Self {
+        match value {
+            json::Error::Io(e) => {
+                Self::BadRequest(format!("failed to read the request body: {}", e))
+            }
+            json::Error::Parse(_, e) if e.is_data() => Self::Validation(e.to_string(), vec![]),
+            json::Error::Parse(_, e) => Self::BadRequest(format!("malformed JSON: {}", e)),
+        }
+    }
+}
+
+// enables rocket to use an API key as a request guard
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ApiKey {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        fn server_error() -> Outcome<ApiKey, ApiError> {
+            Outcome::Error((
+                Status::InternalServerError,
+                ApiError::ServerError("server error".to_string()),
+            ))
+        }
+
+        fn key_error(e: ApiKeyError) -> Outcome<ApiKey, ApiError> {
+            Outcome::Error((Status::Unauthorized, ApiError::KeyError(e.to_string())))
+        }
+
+        match req.headers().get_one(API_KEY_HEADER) {
+            None => {
+                METRICS.api_key_failure();
+                key_error(ApiKeyError::NotFound("API key not found".to_string()))
+            }
+            Some(key) => {
+                let db = match req.guard::<&State<AppDatabase>>().await {
+                    Outcome::Success(db) => db,
+                    _ => return server_error(),
+                };
+
+                let api_key = match ApiKey::from_str(key) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        METRICS.api_key_failure();
+                        return key_error(e);
+                    }
+                };
+
+                match action::is_api_key_valid(api_key.clone(), db.get_pool()).await {
+                    Ok(valid) if valid => Outcome::Success(api_key),
+                    Ok(valid) if !valid => {
+                        key_error(ApiKeyError::NotFound("API key not found".to_string()))
+                    }
+                    _ => server_error(),
+                }
+            }
+        }
+    }
+}
+
+// Deprecation is a fairing that marks every response of the unversioned API as deprecated and
+// points clients at its successor
+pub struct Deprecation;
+
+#[rocket::async_trait]
+impl Fairing for Deprecation {
+    fn info(&self) -> Info {
+        Info {
+            name: "Legacy API Deprecation",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let path = req.uri().path();
+        let is_legacy = path
+            .as_str()
+            .strip_prefix(LEGACY_API_BASE)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+
+        if is_legacy {
+            res.set_header(Header::new("Deprecation", "true"));
+            res.set_header(Header::new(
+                "Link",
+                "</api/v1/clips>; rel=\"successor-version\"",
+            ));
+        }
+    }
+}
+
+// updates have to name the version of the clip they are based on so concurrent edits aren't lost
+pub(crate) fn expected_versions(
+    preconditions: &Preconditions,
+) -> Result<Option<Vec<String>>, ApiError> {
+    preconditions
+        .if_match()
+        .map(IfMatch::into_versions)
+        .ok_or_else(|| {
+            ApiError::PreconditionRequired(
+                "updates require an If-Match header with the ETag of the clip".to_owned(),
+            )
+        })
+}
+
+// the listing of public clips asked for by the query, limited to `v1::MAX_LIST_LIMIT` clips
+pub(crate) fn list_request(
+    tag: Option<String>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> Result<ListClips, ApiError> {
+    let tag = match tag {
+        Some(tag) => Some(Tags::tag(&tag).map_err(|e| {
+            ApiError::Validation("the tag is invalid".to_owned(), vec![FieldError::from(&e)])
+        })?),
+        None => None,
+    };
+    Ok(ListClips {
+        tag,
+        limit: limit
+            .unwrap_or(v1::DEFAULT_LIST_LIMIT)
+            .min(v1::MAX_LIST_LIMIT),
+        offset: offset.unwrap_or_default(),
+    })
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NewApiKey {
+    pub api_key: String,
+}
+
+// this is public for demo purpose only, this should be behind a pay wall or account registration.
+// The key is only ever returned to the requester, it must never end up in the logs
+#[utoipa::path(
+    get,
+    path = "/api/clip/key",
+    tag = "legacy",
+    responses(
+        (status = 200, description = "a new API key", body = NewApiKey),
+        (status = 500, description = "the key could not be stored", body = Problem),
+    )
+)]
+#[rocket::get("/key")]
+pub async fn new_api_key(
+    actor: Actor,
+    database: &State<AppDatabase>,
+) -> Result<Json<NewApiKey>, ApiError> {
+    let api_key = action::generate_api_key(&actor, database.get_pool()).await?;
+    tracing::info!("api key generated");
+    Ok(Json(NewApiKey {
+        api_key: api_key.to_base64(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/clip/{shortcode}",
+    tag = "legacy",
+    params(
+        ("shortcode" = String, Path, description = "shortcode of the clip"),
+        ("password" = Option<String>, Cookie, description = "password of a protected clip"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a cached copy"),
+    ),
+    responses(
+        (status = 200, description = "the clip", body = Clip),
+        (status = 304, description = "the cached copy is up to date"),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 403, description = "the clip is protected by another password", body = Problem),
+        (status = 404, description = "the clip does not exist", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::get("/<shortcode>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_clip(
+    shortcode: &str,
+    database: &State<AppDatabase>,
+    cookies: &CookieJar<'_>,
+    hit_counter: &State<HitCounter>,
+    view: View,
+    preconditions: Preconditions,
+    actor: Actor,
+    api_key: ApiKey,
+) -> Result<Conditional<Json<Clip>>, ApiError> {
+    use crate::domain::clip::field::Password;
+
+    let req = service::ask::GetClip {
+        shortcode: shortcode.into(),
+        password: cookies
+            .get(PASSWORD_COOKIE)
+            .map(|cookie| cookie.value())
+            .and_then(|raw_password| Password::new(raw_password.to_string()).ok())
+            .unwrap_or_default(),
+        requester: Owner::new(api_key.into_inner()),
+    };
+
+    let clip = action::get_clip(req, &actor, database.get_pool()).await?;
+    hit_counter.view(shortcode.into(), view).await;
+    let validators = Validators::strong(&clip);
+    Ok(Conditional::new(&preconditions, validators, Json(clip)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/clip",
+    tag = "legacy",
+    params(
+        ("tag" = Option<String>, Query, description = "only list clips with this tag"),
+        ("limit" = Option<u32>, Query, description = "number of clips, at most 100"),
+        ("offset" = Option<u32>, Query, description = "number of clips to skip"),
+    ),
+    responses(
+        (status = 200, description = "the most recent public clips without a password, newest \
+            first", body = [Clip]),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 422, description = "the tag is invalid", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::get("/?<tag>&<limit>&<offset>")]
+pub async fn list_clips(
+    tag: Option<String>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    database: &State<AppDatabase>,
+    _api_key: ApiKey,
+) -> Result<Json<Vec<Clip>>, ApiError> {
+    let req = list_request(tag, limit, offset)?;
+    let clips = action::list_public_clips(req, database.get_pool()).await?;
+    Ok(Json(clips))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/clip/{shortcode}/stats",
+    tag = "legacy",
+    params(("shortcode" = String, Path, description = "shortcode of the clip")),
+    responses(
+        (status = 200, description = "view analytics of the clip", body = ClipStats),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 403, description = "the API key does not own the clip", body = Problem),
+        (status = 404, description = "the clip does not exist", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::get("/<shortcode>/stats")]
+pub async fn get_clip_stats(
+    shortcode: &str,
+    database: &State<AppDatabase>,
+    api_key: ApiKey,
+) -> Result<Json<ClipStats>, ApiError> {
+    let req = service::ask::GetClipStats {
+        shortcode: shortcode.into(),
+        owner: Owner::new(api_key.into_inner()),
+    };
+
+    let stats = action::get_clip_stats(req, database.get_pool()).await?;
+    Ok(Json(stats))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/clip",
+    tag = "legacy",
+    request_body = NewClip,
+    responses(
+        (status = 200, description = "the created clip", body = Clip),
+        (status = 400, description = "the body is not valid JSON", body = Problem),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 422, description = "the clip is invalid", body = Problem),
+        (status = 413, description = "the content is larger than the max content size", body = Problem),
+        (status = 507, description = "the clip would exceed the storage quota of the API key", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::post("/", data = "<req>")]
+pub async fn new_clip(
+    req: Result<Json<NewClip>, json::Error<'_>>,
+    database: &State<AppDatabase>,
+    secrets: &State<SecretScanner>,
+    quota: &State<StorageQuota>,
+    actor: Actor,
+    api_key: ApiKey,
+) -> Result<Json<Clip>, ApiError> {
+    let mut req = req?.into_inner();
+    // the key that created the clip owns it
+    req.owner = Owner::new(api_key.into_inner());
+
+    let clip = action::new_clip(req, &actor, secrets, **quota, database.get_pool()).await?;
+    Ok(Json(clip))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/clip",
+    tag = "legacy",
+    params(("If-Match" = String, Header, description = "ETag of the clip the update is based on")),
+    request_body = UpdateClip,
+    responses(
+        (status = 200, description = "the updated clip", body = Clip),
+        (status = 400, description = "the body is not valid JSON", body = Problem),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 404, description = "the clip does not exist", body = Problem),
+        (status = 412, description = "the clip was changed since it was read", body = Problem),
+        (status = 422, description = "the clip is invalid", body = Problem),
+        (status = 413, description = "the content is larger than the max content size", body = Problem),
+        (status = 507, description = "the clip would exceed the storage quota of the API key", body = Problem),
+        (status = 428, description = "the If-Match header is missing", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::put("/", data = "<req>")]
+pub async fn update_clip(
+    req: Result<Json<UpdateClip>, json::Error<'_>>,
+    database: &State<AppDatabase>,
+    secrets: &State<SecretScanner>,
+    quota: &State<StorageQuota>,
+    actor: Actor,
+    preconditions: Preconditions,
+    api_key: ApiKey,
+) -> Result<Conditional<Json<Clip>>, ApiError> {
+    let mut req = req?.into_inner();
+    req.expected_versions = expected_versions(&preconditions)?;
+    req.requester = Owner::new(api_key.into_inner());
+
+    let clip = action::update_clip(req, &actor, secrets, **quota, database.get_pool()).await?;
+    Ok(Conditional::fresh(Validators::strong(&clip), Json(clip)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/clip/{shortcode}/fork",
+    tag = "legacy",
+    params(
+        ("shortcode" = String, Path, description = "shortcode of the clip to fork"),
+        ("password" = Option<String>, Cookie, description = "password of a protected clip"),
+    ),
+    responses(
+        (status = 200, description = "the fork, an unlisted clip with the content and title of \
+            the original", body = Clip),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 403, description = "the clip is protected by another password", body = Problem),
+        (status = 404, description = "the clip does not exist", body = Problem),
+        (status = 422, description = "the content contains possible secrets", body = Problem),
+        (status = 507, description = "the fork would exceed the storage quota of the API key", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::post("/<shortcode>/fork")]
+#[allow(clippy::too_many_arguments)]
+pub async fn fork_clip(
+    shortcode: &str,
+    database: &State<AppDatabase>,
+    secrets: &State<SecretScanner>,
+    quota: &State<StorageQuota>,
+    actor: Actor,
+    cookies: &CookieJar<'_>,
+    api_key: ApiKey,
+) -> Result<Json<Clip>, ApiError> {
+    use crate::domain::clip::field::Password;
+
+    let req = service::ask::ForkClip {
+        shortcode: shortcode.into(),
+        password: cookies
+            .get(PASSWORD_COOKIE)
+            .map(|cookie| cookie.value())
+            .and_then(|raw_password| Password::new(raw_password.to_string()).ok())
+            .unwrap_or_default(),
+        // the key that forked the clip owns the fork
+        requester: Owner::new(api_key.into_inner()),
+    };
+
+    let clip = action::fork_clip(req, &actor, secrets, **quota, database.get_pool()).await?;
+    Ok(Json(clip))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        get_clip,
+        list_clips,
+        get_clip_stats,
+        new_clip,
+        fork_clip,
+        update_clip,
+        new_api_key
+    ]
+}
+
+pub mod catcher {
+    use rocket::http::Status;
+    use rocket::Request;
+    use rocket::{catch, catchers, Catcher};
+
+    use crate::web::problem::Problem;
+
+    #[catch(default)]
+    fn default(status: Status, req: &Request) -> Problem {
+        if status.code >= 500 {
+            tracing::error!(uri = %req.uri(), status = status.code, "unhandled api error");
+        }
+        Problem::new(status)
+    }
+
+    #[catch(500)]
+    fn internal_error(req: &Request) -> Problem {
+        tracing::error!(uri = %req.uri(), "internal api error");
+        Problem::new(Status::InternalServerError).with_detail("internal server error")
+    }
+
+    #[catch(404)]
+    fn not_found() -> Problem {
+        Problem::new(Status::NotFound).with_detail("the requested resource does not exist")
+    }
+
+    #[catch(401)]
+    fn invalid_api_key() -> Problem {
+        Problem::new(Status::Unauthorized).with_detail("API key is missing or invalid")
+    }
+
+    #[catch(400)]
+    fn bad_request() -> Problem {
+        Problem::new(Status::BadRequest).with_detail("the request could not be understood")
+    }
+
+    #[catch(422)]
+    fn unprocessable() -> Problem {
+        Problem::new(Status::UnprocessableEntity).with_detail("the request body is invalid")
+    }
+
+    pub fn catchers() -> Vec<Catcher> {
+        catchers![
+            not_found,
+            default,
+            internal_error,
+            invalid_api_key,
+            bad_request,
+            unprocessable
+        ]
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::{ContentType, Cookie, Header, Status};
+    use rocket::local::blocking::Client;
+
+    use super::API_KEY_HEADER;
+    use crate::test::new_async_runtime;
+    use crate::web::problem::Problem;
+    use crate::web::test::new_rocket_client;
+    use crate::web::trace::REQUEST_ID_HEADER;
+
+    pub fn new_api_key(client: &Client) -> Header<'static> {
+        let body: serde_json::Value = client.get("/api/clip/key").dispatch().into_json().unwrap();
+        Header::new(API_KEY_HEADER, body["api_key"].as_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn only_invalid_input_is_a_validation_error() {
+        use super::ApiError;
+        use crate::{ClipError, ServiceError};
+
+        let error = ApiError::from(ServiceError::from(ClipError::EmptyContent));
+        assert!(matches!(error, ApiError::Validation(_, _)));
+        let error = ApiError::from(ServiceError::from(ClipError::Encoding(
+            "unknown encoding `lz4`".to_owned(),
+        )));
+        assert!(matches!(error, ApiError::ServerError(_)));
+    }
+
+    #[test]
+    fn conflicts_hide_the_schema() {
+        use super::ApiError;
+        use crate::ServiceError;
+
+        let error = ServiceError::Conflict("UNIQUE constraint failed: clips.shortcode".to_owned());
+        match ApiError::from(error) {
+            ApiError::Conflict(detail) => assert!(!detail.contains("clips")),
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn error_bodies_carry_the_request_id() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+
+        let response = client
+            .get("/api/clip/abc")
+            .header(Header::new(REQUEST_ID_HEADER, "ci-run-42"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert_eq!(
+            response.headers().get_one(REQUEST_ID_HEADER),
+            Some("ci-run-42")
+        );
+        let problem: Problem = response.into_json().unwrap();
+        assert_eq!(problem.request_id, "ci-run-42");
+
+        // a generated id is used when the client doesn't send one
+        let response = client.get("/api/clip/abc").dispatch();
+        let id = response
+            .headers()
+            .get_one(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_owned();
+        let problem: Problem = response.into_json().unwrap();
+        assert_eq!(problem.request_id, id);
+    }
+
+    #[test]
+    fn errors_are_problem_documents() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+        let key = new_api_key(&client);
+
+        let response = client
+            .get("/api/clip/missing")
+            .header(key.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "problem+json"))
+        );
+        let problem: Problem = response.into_json().unwrap();
+        assert_eq!(problem.kind, "urn:clipstash:problem:not-found");
+        assert_eq!(problem.status, 404);
+
+        let response = client
+            .post("/api/clip")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"content": " ", "title": null, "expires": null, "password": null}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let problem: Problem = response.into_json().unwrap();
+        assert_eq!(problem.kind, "urn:clipstash:problem:validation");
+
+        let response = client
+            .post("/api/clip")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"content": "secret", "title": null, "expires": null, "password": "pw"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let clip: serde_json::Value = response.into_json().unwrap();
+        let shortcode = clip["shortcode"].as_str().unwrap();
+
+        let response = client
+            .get(format!("/api/clip/{}", shortcode))
+            .header(key)
+            .cookie(Cookie::new("password", "wrong"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let problem: Problem = response.into_json().unwrap();
+        assert_eq!(problem.detail.as_deref(), Some("Invalid password"));
+    }
+}
@@ -0,0 +1,359 @@
+use chrono::{DateTime, Utc};
+use rocket::response::status::{Created, NoContent};
+use rocket::serde::json::{self, Json};
+use rocket::State;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::{validate_expires, ClipPassword, ClipResponse};
+use crate::data::AppDatabase;
+use crate::domain::audit::Actor;
+use crate::domain::clip::field::{Owner, Password, Title};
+use crate::domain::collection::Collection;
+use crate::service::{action, ask};
+use crate::web::api::{ApiError, ApiKey};
+use crate::web::problem::FieldError;
+use crate::{ServiceError, ShortCode};
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CollectionResponse {
+    #[schema(example = "aB3dE9x")]
+    pub shortcode: String,
+    pub title: Option<String>,
+    pub posted: DateTime<Utc>,
+    pub expires: Option<DateTime<Utc>>,
+    // the password itself is never sent back
+    pub protected: bool,
+    // the clips in order, without the ones the requester can't open
+    pub clips: Vec<ClipResponse>,
+    // shortcodes of clips protected by a password of their own
+    pub locked: Vec<String>,
+}
+
+impl From<Collection> for CollectionResponse {
+    fn from(collection: Collection) -> Self {
+        Self {
+            protected: collection.password.has_password(),
+            shortcode: collection.shortcode.into_inner(),
+            title: collection.title.into_inner(),
+            posted: collection.posted.into_inner().into_inner(),
+            expires: collection
+                .expires
+                .into_inner()
+                .map(|time| time.into_inner()),
+            clips: collection.clips.into_iter().map(Into::into).collect(),
+            locked: collection
+                .locked
+                .into_iter()
+                .map(ShortCode::into_inner)
+                .collect(),
+        }
+    }
+}
+
+// a new collection, an update replaces the title, expiration, password and clips. The expiration
+// and password also apply to the clips read through the collection
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CollectionRequest {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub password: Option<String>,
+    // shortcodes of the clips in the order they are shown
+    #[serde(default)]
+    #[schema(example = json!(["aB3dE9x", "c4d1a2b3c4"]))]
+    pub clips: Vec<String>,
+}
+
+impl TryFrom<CollectionRequest> for ask::NewCollection {
+    type Error = ApiError;
+
+    fn try_from(value: CollectionRequest) -> Result<Self, Self::Error> {
+        let mut errors = vec![];
+        let title = Title::parse(value.title)
+            .map_err(|e| errors.push(FieldError::from(&e)))
+            .ok();
+        let password = Password::new(value.password)
+            .map_err(|e| errors.push(FieldError::from(&e)))
+            .ok();
+        let expires = validate_expires(value.expires)
+            .map_err(|e| errors.push(FieldError::from(&e)))
+            .ok();
+
+        match (title, password, expires) {
+            (Some(title), Some(password), Some(expires)) => Ok(Self {
+                title,
+                expires,
+                password,
+                owner: Owner::default(),
+                clips: value.clips.into_iter().map(ShortCode::from).collect(),
+            }),
+            _ => Err(ApiError::Validation(
+                "the collection is invalid".to_owned(),
+                errors,
+            )),
+        }
+    }
+}
+
+// errors of the collection routes are about the collection, not a clip
+fn collection_error(e: ServiceError) -> ApiError {
+    match e {
+        ServiceError::NotFound => ApiError::NotFound("collection not found".to_owned()),
+        ServiceError::Clip(e) => ApiError::Validation(
+            "the collection is invalid".to_owned(),
+            vec![FieldError::from(&e)],
+        ),
+        e => e.into(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/collections",
+    tag = "collections",
+    request_body = CollectionRequest,
+    responses(
+        (status = 201, description = "the created collection", body = CollectionResponse),
+        (status = 400, description = "the body is not valid JSON", body = Problem),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 422, description = "the collection is invalid", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::post("/collections", data = "<req>")]
+pub async fn new_collection(
+    req: Result<Json<CollectionRequest>, json::Error<'_>>,
+    database: &State<AppDatabase>,
+    actor: Actor,
+    api_key: ApiKey,
+) -> Result<Created<Json<CollectionResponse>>, ApiError> {
+    let mut req: ask::NewCollection = req?.into_inner().try_into()?;
+    // the key that created the collection owns it
+    req.owner = Owner::new(api_key.into_inner());
+
+    let collection = action::new_collection(req, &actor, database.get_pool())
+        .await
+        .map_err(collection_error)?;
+    let location = format!("/api/v1/collections/{}", collection.shortcode.as_str());
+    Ok(Created::new(location).body(Json(collection.into())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/collections/{shortcode}",
+    tag = "collections",
+    params(
+        ("shortcode" = String, Path, description = "shortcode of the collection"),
+        ("x-clip-password" = Option<String>, Header, description = "password of the collection"),
+    ),
+    responses(
+        (status = 200, description = "the collection and its clips", body = CollectionResponse),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 403, description = "the collection is protected by another password", body = Problem),
+        (status = 404, description = "the collection does not exist", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::get("/collections/<shortcode>")]
+pub async fn get_collection(
+    shortcode: &str,
+    password: ClipPassword,
+    database: &State<AppDatabase>,
+    api_key: ApiKey,
+) -> Result<Json<CollectionResponse>, ApiError> {
+    let req = ask::GetCollection {
+        shortcode: shortcode.into(),
+        password: password.0,
+        requester: Owner::new(api_key.into_inner()),
+    };
+
+    let collection = action::get_collection(req, database.get_pool())
+        .await
+        .map_err(collection_error)?;
+    Ok(Json(collection.into()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/collections/{shortcode}",
+    tag = "collections",
+    params(("shortcode" = String, Path, description = "shortcode of the collection")),
+    request_body = CollectionRequest,
+    responses(
+        (status = 200, description = "the updated collection", body = CollectionResponse),
+        (status = 400, description = "the body is not valid JSON", body = Problem),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 403, description = "the API key does not own the collection", body = Problem),
+        (status = 404, description = "the collection does not exist", body = Problem),
+        (status = 422, description = "the collection is invalid", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::put("/collections/<shortcode>", data = "<req>")]
+pub async fn update_collection(
+    shortcode: &str,
+    req: Result<Json<CollectionRequest>, json::Error<'_>>,
+    database: &State<AppDatabase>,
+    actor: Actor,
+    api_key: ApiKey,
+) -> Result<Json<CollectionResponse>, ApiError> {
+    let fields: ask::NewCollection = req?.into_inner().try_into()?;
+    let requester = Owner::new(api_key.into_inner());
+    let req = ask::UpdateCollection {
+        shortcode: shortcode.into(),
+        title: fields.title,
+        expires: fields.expires,
+        password: fields.password.clone(),
+        clips: fields.clips,
+        requester: requester.clone(),
+    };
+
+    let pool = database.get_pool();
+    action::update_collection(req, &actor, pool)
+        .await
+        .map_err(collection_error)?;
+    // the owner reads the collection back with its new password
+    let req = ask::GetCollection {
+        shortcode: shortcode.into(),
+        password: fields.password,
+        requester,
+    };
+    let collection = action::get_collection(req, pool)
+        .await
+        .map_err(collection_error)?;
+    Ok(Json(collection.into()))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/collections/{shortcode}",
+    tag = "collections",
+    params(("shortcode" = String, Path, description = "shortcode of the collection")),
+    responses(
+        (status = 204, description = "the collection was deleted, its clips are kept"),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 403, description = "the API key does not own the collection", body = Problem),
+        (status = 404, description = "the collection does not exist", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::delete("/collections/<shortcode>")]
+pub async fn delete_collection(
+    shortcode: &str,
+    database: &State<AppDatabase>,
+    actor: Actor,
+    api_key: ApiKey,
+) -> Result<NoContent, ApiError> {
+    let req = ask::DeleteCollection {
+        shortcode: shortcode.into(),
+        requester: Owner::new(api_key.into_inner()),
+    };
+
+    action::delete_collection(req, &actor, database.get_pool())
+        .await
+        .map_err(collection_error)?;
+    Ok(NoContent)
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::{ContentType, Header, Status};
+
+    use super::CollectionResponse;
+    use crate::test::new_async_runtime;
+    use crate::web::api::test::new_api_key;
+    use crate::web::api::v1::{ClipResponse, CLIP_PASSWORD_HEADER};
+    use crate::web::test::new_rocket_client;
+
+    #[test]
+    fn collections_pass_their_password_and_expiry_to_the_clips() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+        let key = new_api_key(&client);
+
+        let mut shortcodes = vec![];
+        for body in [
+            r#"{"content": "first"}"#,
+            r#"{"content": "second", "password": "own"}"#,
+        ] {
+            let response = client
+                .post("/api/v1/clips")
+                .header(key.clone())
+                .header(ContentType::JSON)
+                .body(body)
+                .dispatch();
+            let clip: ClipResponse = response.into_json().unwrap();
+            shortcodes.push(clip.shortcode);
+        }
+
+        let body = serde_json::json!({
+            "title": "logs",
+            "expires": "2099-01-01T00:00:00Z",
+            "password": "hunter2",
+            "clips": [&shortcodes[1], &shortcodes[0]],
+        });
+        let response = client
+            .post("/api/v1/collections")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(body.to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let collection: CollectionResponse = response.into_json().unwrap();
+        assert!(collection.protected);
+        let uri = format!("/api/v1/collections/{}", collection.shortcode);
+
+        let response = client.get(uri.as_str()).header(key.clone()).dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .get(uri.as_str())
+            .header(key.clone())
+            .header(Header::new(CLIP_PASSWORD_HEADER, "hunter2"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let collection: CollectionResponse = response.into_json().unwrap();
+        assert_eq!(collection.locked, vec![shortcodes[1].clone()]);
+        assert_eq!(collection.clips.len(), 1);
+        let clip = &collection.clips[0];
+        assert_eq!(clip.shortcode, shortcodes[0]);
+        assert!(clip.protected);
+        assert_eq!(clip.expires, collection.expires);
+
+        let other = new_api_key(&client);
+        let response = client
+            .put(uri.as_str())
+            .header(other.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"clips": []}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client.delete(uri.as_str()).header(other).dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = client
+            .post("/api/v1/collections")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"clips": ["nothere"]}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+
+        let response = client.delete(uri.as_str()).header(key.clone()).dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        let response = client.get(uri.as_str()).header(key.clone()).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        // the clips themselves were never changed
+        let response = client
+            .get(format!("/api/v1/clips/{}", shortcodes[0]))
+            .header(key)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let clip: ClipResponse = response.into_json().unwrap();
+        assert!(!clip.protected);
+        assert_eq!(clip.expires, None);
+    }
+}
@@ -0,0 +1,232 @@
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::{Either, FromFormField, State};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::CLIP_PASSWORD_HEADER;
+use crate::data::AppDatabase;
+use crate::domain::audit::Actor;
+use crate::domain::clip::field::{Owner, Password};
+use crate::domain::diff::{Diff, Line};
+use crate::service::{action, ask};
+use crate::web::api::{ApiError, ApiKey};
+use crate::web::PASSWORD_COOKIE;
+
+pub const CLIP_PASSWORD_A_HEADER: &str = "x-clip-password-a";
+pub const CLIP_PASSWORD_B_HEADER: &str = "x-clip-password-b";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromFormField)]
+pub enum DiffFormat {
+    Json,
+    // the text of `diff -u`, for the CLI and patch tools
+    Unified,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct DiffResponse {
+    #[schema(example = "aB3dE9x")]
+    pub a: String,
+    #[schema(example = "c4d1a2b3c4")]
+    pub b: String,
+    pub insertions: usize,
+    pub deletions: usize,
+    pub lines: Vec<Line>,
+}
+
+impl From<Diff> for DiffResponse {
+    fn from(diff: Diff) -> Self {
+        Self {
+            a: diff.a.into_inner(),
+            b: diff.b.into_inner(),
+            insertions: diff.insertions,
+            deletions: diff.deletions,
+            lines: diff.lines,
+        }
+    }
+}
+
+// DiffPasswords unlock the two clips of a diff, each from its own header. The
+// `x-clip-password` header or the password cookie of the web pages unlock both
+pub struct DiffPasswords(Password, Password);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for DiffPasswords {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let shared = req
+            .headers()
+            .get_one(CLIP_PASSWORD_HEADER)
+            .map(|password| password.to_owned())
+            .or_else(|| {
+                req.cookies()
+                    .get(PASSWORD_COOKIE)
+                    .map(|c| c.value().to_owned())
+            });
+        let password = |header| {
+            let raw = req
+                .headers()
+                .get_one(header)
+                .map(|password| password.to_owned())
+                .or_else(|| shared.clone());
+            Password::new(raw)
+        };
+
+        match (
+            password(CLIP_PASSWORD_A_HEADER),
+            password(CLIP_PASSWORD_B_HEADER),
+        ) {
+            (Ok(a), Ok(b)) => Outcome::Success(DiffPasswords(a, b)),
+            (Err(e), _) | (_, Err(e)) => Outcome::Error((
+                Status::UnprocessableEntity,
+                ApiError::Validation("the password is invalid".to_owned(), vec![(&e).into()]),
+            )),
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/diff",
+    tag = "clips",
+    params(
+        ("a" = String, Query, description = "shortcode of the first clip"),
+        ("b" = String, Query, description = "shortcode of the second clip"),
+        ("words" = Option<bool>, Query, description = "also diff the words of replaced lines"),
+        ("format" = Option<String>, Query, description = "`json` (default) or `unified`"),
+        ("x-clip-password-a" = Option<String>, Header, description = "password of the first clip"),
+        ("x-clip-password-b" = Option<String>, Header, description = "password of the second clip"),
+        ("x-clip-password" = Option<String>, Header, description = "password of both clips"),
+    ),
+    responses(
+        (status = 200, description = "the line-level diff of the content of the clips", content(
+            ("application/json" = DiffResponse),
+            ("text/plain" = String),
+        )),
+        (status = 401, description = "API key is missing or invalid", body = Problem),
+        (status = 403, description = "a clip is protected by another password", body = Problem),
+        (status = 404, description = "a clip does not exist or is private", body = Problem),
+        (status = 422, description = "the format or a password is invalid", body = Problem),
+    ),
+    security(("api_key" = []))
+)]
+#[rocket::get("/diff?<a>&<b>&<words>&<format>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_diff(
+    a: &str,
+    b: &str,
+    words: Option<bool>,
+    format: Option<DiffFormat>,
+    passwords: DiffPasswords,
+    database: &State<AppDatabase>,
+    actor: Actor,
+    api_key: ApiKey,
+) -> Result<Either<Json<DiffResponse>, String>, ApiError> {
+    let requester = Owner::new(api_key.into_inner());
+    let DiffPasswords(password_a, password_b) = passwords;
+    let req = ask::GetDiff {
+        a: ask::GetClip {
+            shortcode: a.into(),
+            password: password_a,
+            requester: requester.clone(),
+        },
+        b: ask::GetClip {
+            shortcode: b.into(),
+            password: password_b,
+            requester,
+        },
+        words: words.unwrap_or_default(),
+    };
+
+    let diff = action::diff_clips(req, &actor, database.get_pool()).await?;
+    match format.unwrap_or(DiffFormat::Json) {
+        DiffFormat::Json => Ok(Either::Left(Json(diff.into()))),
+        DiffFormat::Unified => Ok(Either::Right(diff.unified())),
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::{ContentType, Header, Status};
+
+    use super::{DiffResponse, CLIP_PASSWORD_B_HEADER};
+    use crate::domain::diff::Change;
+    use crate::test::new_async_runtime;
+    use crate::web::api::test::new_api_key;
+    use crate::web::api::v1::ClipResponse;
+    use crate::web::test::new_rocket_client;
+
+    #[test]
+    fn diffs_unlock_each_clip_with_its_own_password() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+        let key = new_api_key(&client);
+
+        let mut shortcodes = vec![];
+        for body in [
+            r#"{"content": "fn main() {\n    let x = 1;\n}"}"#,
+            r#"{"content": "fn main() {\n    let y = 1;\n}\n// done", "password": "hunter2"}"#,
+        ] {
+            let response = client
+                .post("/api/v1/clips")
+                .header(key.clone())
+                .header(ContentType::JSON)
+                .body(body)
+                .dispatch();
+            let clip: ClipResponse = response.into_json().unwrap();
+            shortcodes.push(clip.shortcode);
+        }
+        let uri = format!("/api/v1/diff?a={}&b={}", shortcodes[0], shortcodes[1]);
+
+        let response = client.get(uri.as_str()).header(key.clone()).dispatch();
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let password = Header::new(CLIP_PASSWORD_B_HEADER, "hunter2");
+        let response = client
+            .get(format!("{}&words=true", uri))
+            .header(key.clone())
+            .header(password.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let diff: DiffResponse = response.into_json().unwrap();
+        assert_eq!((diff.insertions, diff.deletions), (2, 1));
+        let changes: Vec<_> = diff.lines.iter().map(|line| line.change).collect();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Equal,
+                Change::Delete,
+                Change::Insert,
+                Change::Equal,
+                Change::Insert
+            ]
+        );
+        assert_eq!(diff.lines[1].words[1].text, "x");
+        assert_eq!(diff.lines[2].words[1].text, "y");
+
+        let response = client
+            .get(format!("{}&format=unified", uri))
+            .header(key)
+            .header(password)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let unified = response.into_string().unwrap();
+        assert!(
+            unified.contains("@@ -1,3 +1,4 @@\n fn main() {\n-    let x = 1;\n+    let y = 1;\n")
+        );
+
+        let page = format!("/diff/{}/{}?words=true", shortcodes[0], shortcodes[1]);
+        let response = client.get(page.as_str()).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .post(page.as_str())
+            .header(ContentType::Form)
+            .body("password_a=&password_b=hunter2")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let html = response.into_string().unwrap();
+        assert!(html.contains(r#"<span class="word-insert">y</span>"#));
+    }
+}
@@ -0,0 +1,295 @@
+use std::convert::Infallible;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::serde::json::Json;
+use rocket::{FromForm, State};
+use serde::{Deserialize, Serialize};
+
+use super::admin::Admin;
+use super::api::{ApiError, ApiKey, API_KEY_HEADER};
+use super::problem::FieldError;
+use super::API_KEY_COOKIE;
+use crate::data::AppDatabase;
+use crate::domain::audit::{Actor, AuditAction, AuditEntry};
+use crate::domain::clip::field::Owner;
+use crate::service::{action, ask};
+use crate::{ShortCode, Time};
+
+// the number of entries returned when the request doesn't ask for a limit, and the largest one
+pub const DEFAULT_AUDIT_LIMIT: u32 = 100;
+pub const MAX_AUDIT_LIMIT: u32 = 1000;
+
+// the actor of a request is the API key it was sent with, from the header of the API or the
+// cookie of the web pages, and the address of the client. Keys are not checked here, the
+// entries of unknown keys have no key id
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Actor {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let key = req
+            .headers()
+            .get_one(API_KEY_HEADER)
+            .map(ToOwned::to_owned)
+            .or_else(|| {
+                req.cookies()
+                    .get(API_KEY_COOKIE)
+                    .map(|cookie| cookie.value().to_owned())
+            })
+            .and_then(|key| ApiKey::from_str(&key).ok());
+
+        Outcome::Success(Actor {
+            key: Owner::new(key.map(ApiKey::into_inner)),
+            admin: false,
+            ip: req.client_ip(),
+        })
+    }
+}
+
+// the filters of the audit log, `since` and `until` are RFC 3339 times or dates
+#[derive(Debug, FromForm)]
+pub struct AuditFilter {
+    action: Option<String>,
+    shortcode: Option<String>,
+    key: Option<i64>,
+    ip: Option<String>,
+    since: Option<String>,
+    until: Option<String>,
+    before: Option<i64>,
+    limit: Option<u32>,
+}
+
+fn parse_time(field: &str, value: Option<String>) -> Result<Option<Time>, FieldError> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    match value.parse::<DateTime<Utc>>() {
+        Ok(time) => Ok(Some(time.into())),
+        Err(_) => Time::from_str(&value).map(Some).map_err(|e| FieldError {
+            field: field.to_owned(),
+            message: format!("not an RFC 3339 time or a date: {}", e),
+        }),
+    }
+}
+
+impl TryFrom<AuditFilter> for ask::AuditQuery {
+    type Error = ApiError;
+
+    fn try_from(value: AuditFilter) -> Result<Self, Self::Error> {
+        let mut errors = vec![];
+        let action = match value.action.as_deref().map(AuditAction::from_str) {
+            Some(Ok(action)) => Some(action),
+            Some(Err(_)) => {
+                errors.push(FieldError {
+                    field: "action".to_owned(),
+                    message: "unknown action".to_owned(),
+                });
+                None
+            }
+            None => None,
+        };
+        let since = parse_time("since", value.since).unwrap_or_else(|e| {
+            errors.push(e);
+            None
+        });
+        let until = parse_time("until", value.until).unwrap_or_else(|e| {
+            errors.push(e);
+            None
+        });
+        if !errors.is_empty() {
+            return Err(ApiError::Validation(
+                "the filter is invalid".to_owned(),
+                errors,
+            ));
+        }
+
+        Ok(Self {
+            action,
+            shortcode: value.shortcode.map(ShortCode::from),
+            key: value.key,
+            ip: value.ip,
+            since,
+            until,
+            before: value.before,
+            limit: value
+                .limit
+                .unwrap_or(DEFAULT_AUDIT_LIMIT)
+                .min(MAX_AUDIT_LIMIT),
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditLogResponse {
+    pub entries: Vec<AuditEntry>,
+}
+
+// the entries of the audit log for the operator, the newest first. Older entries are paged
+// through with `before`, the id of the last entry of the previous page
+#[rocket::get("/admin/audit?<filter..>")]
+pub async fn get_audit_log(
+    _admin: Admin,
+    filter: AuditFilter,
+    database: &State<AppDatabase>,
+) -> Result<Json<AuditLogResponse>, ApiError> {
+    let req = ask::AuditQuery::try_from(filter)?;
+
+    let entries = action::get_audit_log(req, database.get_pool()).await?;
+    Ok(Json(AuditLogResponse { entries }))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![get_audit_log]
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+
+    use super::AuditLogResponse;
+    use crate::domain::audit::AuditAction;
+    use crate::test::new_async_runtime;
+    use crate::web::admin::AdminToken;
+    use crate::web::api::test::new_api_key;
+    use crate::web::api::v1::collection::CollectionResponse;
+    use crate::web::api::v1::ClipResponse;
+    use crate::web::test::new_rocket_config;
+
+    #[test]
+    fn writes_are_recorded_in_the_audit_log() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.admin_token = AdminToken(Some("secret".to_owned()));
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build a rocket instance");
+        let admin = Header::new("Authorization", "Bearer secret");
+        let key = new_api_key(&client);
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .remote("127.0.0.1:8000".parse().unwrap())
+            .body(r#"{"content": "first", "password": "hunter2"}"#)
+            .dispatch();
+        let clip: ClipResponse = response.into_json().unwrap();
+        let etag = client
+            .get(format!("/api/v1/clips/{}", clip.shortcode))
+            .header(key.clone())
+            .header(Header::new("x-clip-password", "hunter2"))
+            .dispatch()
+            .headers()
+            .get_one("ETag")
+            .unwrap()
+            .to_owned();
+        let response = client
+            .put(format!("/api/v1/clips/{}", clip.shortcode))
+            .header(key.clone())
+            .header(Header::new("If-Match", etag))
+            .header(Header::new("x-clip-password", "hunter2"))
+            .header(ContentType::JSON)
+            .body(r#"{"content": "second", "password": "hunter2"}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .post(format!("/clip/{}", clip.shortcode))
+            .header(ContentType::Form)
+            .body("password=hunter2")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .post(format!("/admin/clips/{}/delete", clip.shortcode))
+            .header(admin.clone())
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+
+        let response = client.get("/admin/audit").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let log: AuditLogResponse = client
+            .get(format!("/admin/audit?shortcode={}", clip.shortcode))
+            .header(admin.clone())
+            .dispatch()
+            .into_json()
+            .unwrap();
+        let actions = log.entries.iter().map(|e| e.action).collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::Delete,
+                AuditAction::Unlock,
+                AuditAction::Update,
+                AuditAction::Unlock,
+                AuditAction::Create
+            ]
+        );
+        let (delete, create) = (&log.entries[0], &log.entries[4]);
+        assert!(delete.admin && delete.key.is_none());
+        assert_eq!(create.key, Some(1));
+        assert_eq!(create.ip.as_deref(), Some("127.0.0.1"));
+
+        let log: AuditLogResponse = client
+            .get("/admin/audit?action=generate_key&key=1")
+            .header(admin.clone())
+            .dispatch()
+            .into_json()
+            .unwrap();
+        assert_eq!(log.entries.len(), 1);
+        assert_eq!(log.entries[0].target_key, Some(1));
+
+        let response = client
+            .get("/admin/audit?action=drop&since=yesterday")
+            .header(admin)
+            .dispatch();
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+    }
+
+    #[test]
+    fn collection_writes_are_recorded_in_the_audit_log() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.admin_token = AdminToken(Some("secret".to_owned()));
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build a rocket instance");
+        let key = new_api_key(&client);
+
+        let response = client
+            .post("/api/v1/collections")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"title": "logs", "clips": []}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let collection: CollectionResponse = response.into_json().unwrap();
+        let uri = format!("/api/v1/collections/{}", collection.shortcode);
+        let response = client
+            .put(uri.as_str())
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"title": "more logs", "clips": []}"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let response = client.delete(uri.as_str()).header(key).dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+
+        let log: AuditLogResponse = client
+            .get(format!("/admin/audit?shortcode={}", collection.shortcode))
+            .header(Header::new("Authorization", "Bearer secret"))
+            .dispatch()
+            .into_json()
+            .unwrap();
+        let actions = log.entries.iter().map(|e| e.action).collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                AuditAction::DeleteCollection,
+                AuditAction::UpdateCollection,
+                AuditAction::CreateCollection
+            ]
+        );
+        assert!(log.entries.iter().all(|e| e.key == Some(1)));
+    }
+}
@@ -0,0 +1,306 @@
+use std::convert::Infallible;
+
+use chrono::{DateTime, Utc};
+use rocket::http::{Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+use sha2::{Digest, Sha256};
+
+use crate::Clip;
+
+// http dates are always in GMT, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+// Validators identify the version of a clip a response was built from
+#[derive(Debug, Clone)]
+pub struct Validators {
+    version: String,
+    weak: bool,
+    last_modified: DateTime<Utc>,
+}
+
+impl Validators {
+    // strong validators change with every byte of the response, use them for the API and raw clips
+    pub fn strong(clip: &Clip) -> Self {
+        Self {
+            version: clip.version(),
+            weak: false,
+            last_modified: clip.updated.clone().into_inner().into_inner(),
+        }
+    }
+
+    // weak validators are for pages that also show data outside of the clip version, like hits
+    pub fn weak(clip: &Clip) -> Self {
+        Self {
+            weak: true,
+            ..Self::strong(clip)
+        }
+    }
+
+    // validators of a list of clips, they change when a clip of the list changes, joins or
+    // leaves it. They are weak as the links of the response depend on the host it was sent to
+    pub fn list(clips: &[Clip]) -> Self {
+        let mut hasher = Sha256::new();
+        for clip in clips {
+            hasher.update(clip.shortcode.as_str());
+            hasher.update(clip.version());
+        }
+        let version = hasher.finalize()[..16]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Self {
+            version,
+            weak: true,
+            last_modified: clips
+                .iter()
+                .map(|clip| clip.updated.clone().into_inner().into_inner())
+                .max()
+                .unwrap_or(DateTime::<Utc>::UNIX_EPOCH),
+        }
+    }
+
+    // every encoding of a response is a different representation with its own strong ETag
+    pub fn encoded(mut self, encoding: &str) -> Self {
+        self.version = format!("{}-{}", self.version, encoding);
+        self
+    }
+
+    pub fn etag(&self) -> String {
+        if self.weak {
+            format!("W/\"{}\"", self.version)
+        } else {
+            format!("\"{}\"", self.version)
+        }
+    }
+}
+
+// IfMatch is the `If-Match` header of an update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IfMatch {
+    // `*`, any version of the clip may be replaced
+    Any,
+    // versions of the strong entity tags, weak tags never match an update
+    Versions(Vec<String>),
+}
+
+impl IfMatch {
+    // versions the update may replace, `None` when any version may be replaced
+    pub fn into_versions(self) -> Option<Vec<String>> {
+        match self {
+            Self::Any => None,
+            Self::Versions(versions) => Some(versions),
+        }
+    }
+}
+
+// an entity tag of a conditional header, the opaque part and whether it is weak
+fn parse_entity_tags(header: &str) -> Vec<(bool, &str)> {
+    header
+        .split(',')
+        .map(str::trim)
+        .filter_map(|tag| {
+            let (weak, tag) = match tag.strip_prefix("W/") {
+                Some(tag) => (true, tag),
+                None => (false, tag),
+            };
+            tag.strip_prefix('"')
+                .and_then(|tag| tag.strip_suffix('"'))
+                .map(|tag| (weak, tag))
+        })
+        .collect()
+}
+
+// Preconditions are the conditional headers of a request
+#[derive(Debug, Default)]
+pub struct Preconditions {
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+    if_modified_since: Option<DateTime<Utc>>,
+    if_range: Option<String>,
+}
+
+impl Preconditions {
+    pub fn if_match(&self) -> Option<IfMatch> {
+        let header = self.if_match.as_deref()?;
+        if header.trim() == "*" {
+            return Some(IfMatch::Any);
+        }
+
+        let versions = parse_entity_tags(header)
+            .into_iter()
+            .filter(|(weak, _)| !weak)
+            .map(|(_, version)| version.to_owned())
+            .collect();
+        Some(IfMatch::Versions(versions))
+    }
+
+    // `If-None-Match` uses the weak comparison and takes precedence over `If-Modified-Since`
+    pub fn is_not_modified(&self, validators: &Validators) -> bool {
+        if let Some(header) = self.if_none_match.as_deref() {
+            return header.trim() == "*"
+                || parse_entity_tags(header)
+                    .into_iter()
+                    .any(|(_, version)| version == validators.version);
+        }
+
+        match self.if_modified_since {
+            // http dates have no sub-second precision
+            Some(since) => validators.last_modified.timestamp() <= since.timestamp(),
+            None => false,
+        }
+    }
+
+    // `If-Range` keeps the `Range` of a request only while the client has the current version,
+    // otherwise the whole clip is sent. Entity tags are compared strongly
+    pub fn is_range_current(&self, validators: &Validators) -> bool {
+        let Some(header) = self.if_range.as_deref() else {
+            return true;
+        };
+
+        if header.trim_start().starts_with(['"', 'W']) {
+            return !validators.weak
+                && parse_entity_tags(header)
+                    .into_iter()
+                    .any(|(weak, version)| !weak && version == validators.version);
+        }
+        DateTime::parse_from_rfc2822(header)
+            .is_ok_and(|date| date.timestamp() == validators.last_modified.timestamp())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Preconditions {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = req.headers();
+        Outcome::Success(Preconditions {
+            if_match: headers.get_one("If-Match").map(str::to_owned),
+            if_none_match: headers.get_one("If-None-Match").map(str::to_owned),
+            // invalid dates are ignored, as required by RFC 9110
+            if_modified_since: headers
+                .get_one("If-Modified-Since")
+                .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+                .map(|date| date.with_timezone(&Utc)),
+            if_range: headers.get_one("If-Range").map(str::to_owned),
+        })
+    }
+}
+
+// Conditional responds with `R` when the client doesn't have the current version of the clip
+// yet, otherwise with `304 Not Modified`. Both carry the `ETag` and `Last-Modified` headers
+pub struct Conditional<R> {
+    validators: Validators,
+    response: Option<R>,
+}
+
+impl<R> Conditional<R> {
+    pub fn new(preconditions: &Preconditions, validators: Validators, response: R) -> Self {
+        if preconditions.is_not_modified(&validators) {
+            Self {
+                validators,
+                response: None,
+            }
+        } else {
+            Self::fresh(validators, response)
+        }
+    }
+
+    // always responds with `R`, used to return the validators of a new version after a write
+    pub fn fresh(validators: Validators, response: R) -> Self {
+        Self {
+            validators,
+            response: Some(response),
+        }
+    }
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for Conditional<R> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let mut response = match self.response {
+            Some(response) => response.respond_to(req)?,
+            None => Response::build().status(Status::NotModified).finalize(),
+        };
+
+        response.set_header(Header::new("ETag", self.validators.etag()));
+        response.set_header(Header::new(
+            "Last-Modified",
+            self.validators
+                .last_modified
+                .format(HTTP_DATE_FORMAT)
+                .to_string(),
+        ));
+        // clips can be password protected, only the client may keep a copy and it has to
+        // revalidate it before every use
+        response.set_header(Header::new("Cache-Control", "private, no-cache"));
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::{ContentType, Header, Status};
+
+    use super::{parse_entity_tags, IfMatch, Preconditions};
+    use crate::test::new_async_runtime;
+    use crate::web::api::test::new_api_key;
+    use crate::web::api::v1::ClipResponse;
+    use crate::web::test::new_rocket_client;
+
+    #[test]
+    fn parse_conditional_headers() {
+        assert_eq!(
+            parse_entity_tags(r#""abc", W/"def" , *, broken"#),
+            vec![(false, "abc"), (true, "def")]
+        );
+
+        let preconditions = Preconditions {
+            if_match: Some(r#"W/"weak", "strong""#.to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(
+            preconditions.if_match(),
+            Some(IfMatch::Versions(vec!["strong".to_owned()]))
+        );
+
+        let preconditions = Preconditions {
+            if_match: Some("*".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(preconditions.if_match(), Some(IfMatch::Any));
+        assert_eq!(Preconditions::default().if_match(), None);
+    }
+
+    #[test]
+    fn pages_are_revalidated() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(new_api_key(&client))
+            .header(ContentType::JSON)
+            .body(r#"{"content": "log line"}"#)
+            .dispatch();
+        let clip: ClipResponse = response.into_json().unwrap();
+
+        for (uri, weak) in [
+            (format!("/clip/raw/{}", clip.shortcode), false),
+            (format!("/clip/{}", clip.shortcode), true),
+        ] {
+            let response = client.get(uri.as_str()).dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            let etag = response.headers().get_one("ETag").unwrap().to_owned();
+            assert_eq!(etag.starts_with("W/"), weak);
+
+            let response = client
+                .get(uri.as_str())
+                .header(Header::new("If-None-Match", etag))
+                .dispatch();
+            assert_eq!(response.status(), Status::NotModified);
+            assert!(response.into_string().is_none());
+        }
+    }
+}
@@ -2,6 +2,8 @@ use serde::Serialize; // all template contextst must be serialible
                       // so they can be turned inti a hashimap
 use derive_more::Constructor;
 
+use crate::domain::secret::Finding;
+
 // PageContext is used to get generic information about the context of every page
 // and set global information like page titles
 pub trait PageContext {
@@ -11,7 +13,34 @@ pub trait PageContext {
 }
 
 #[derive(Debug, Serialize, Default)]
-pub struct Home {}
+pub struct Home {
+    // tags already in use, suggested while typing the tags of a new clip
+    tags: Vec<String>,
+    // the clip being forked, its content and title prefill the form
+    fork: Option<crate::Clip>,
+    // the possible secrets in the submitted content, the author confirms they want to store them
+    secrets: Vec<Finding>,
+}
+
+impl Home {
+    pub fn new(tags: Vec<String>) -> Self {
+        Self {
+            tags,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_secrets(self, secrets: Vec<Finding>) -> Self {
+        Self { secrets, ..self }
+    }
+
+    pub fn with_fork(self, clip: crate::Clip) -> Self {
+        Self {
+            fork: Some(clip),
+            ..self
+        }
+    }
+}
 
 impl PageContext for Home {
     fn template_path(&self) -> &str {
@@ -62,3 +91,317 @@ impl PageContext for PasswordRequired {
         "base"
     }
 }
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct ViewClipStats {
+    pub stats: crate::domain::stats::ClipStats,
+}
+
+impl PageContext for ViewClipStats {
+    fn title(&self) -> &str {
+        "Clip Stats"
+    }
+
+    fn template_path(&self) -> &str {
+        "clip_stats"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct ApiKeyRequired {
+    shortcode: crate::ShortCode,
+}
+
+impl PageContext for ApiKeyRequired {
+    fn title(&self) -> &str {
+        "API Key Required"
+    }
+
+    fn template_path(&self) -> &str {
+        "clip_stats_need_key"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct TaggedClips {
+    tag: String,
+    clips: Vec<crate::Clip>,
+}
+
+impl PageContext for TaggedClips {
+    fn title(&self) -> &str {
+        "Tagged Clips"
+    }
+
+    fn template_path(&self) -> &str {
+        "tag"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct NewCollection {}
+
+impl PageContext for NewCollection {
+    fn title(&self) -> &str {
+        "New Collection"
+    }
+
+    fn template_path(&self) -> &str {
+        "collection_new"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct ViewCollection {
+    pub collection: crate::domain::collection::Collection,
+}
+
+impl PageContext for ViewCollection {
+    fn title(&self) -> &str {
+        "View Collection"
+    }
+
+    fn template_path(&self) -> &str {
+        "collection"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct CollectionPasswordRequired {
+    shortcode: crate::ShortCode,
+}
+
+impl PageContext for CollectionPasswordRequired {
+    fn title(&self) -> &str {
+        "Password Required"
+    }
+
+    fn template_path(&self) -> &str {
+        "collection_need_password"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct ViewDiff {
+    diff: crate::domain::diff::Diff,
+    // the lines paired up for the side-by-side view, empty in the unified view
+    rows: Vec<crate::domain::diff::Row>,
+    view: crate::web::form::DiffView,
+    words: bool,
+}
+
+impl PageContext for ViewDiff {
+    fn title(&self) -> &str {
+        "Diff"
+    }
+
+    fn template_path(&self) -> &str {
+        "diff"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct DiffPasswordRequired {
+    a: crate::ShortCode,
+    b: crate::ShortCode,
+    view: crate::web::form::DiffView,
+    words: bool,
+}
+
+impl PageContext for DiffPasswordRequired {
+    fn title(&self) -> &str {
+        "Password Required"
+    }
+
+    fn template_path(&self) -> &str {
+        "diff_need_password"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EditClip {
+    clip: crate::Clip,
+    // the expiration date the way it is entered in the form
+    expires: Option<String>,
+    version: String,
+    // only clips with an owner can be private
+    owned: bool,
+    // the possible secrets in the submitted content, see `Home::secrets`
+    secrets: Vec<Finding>,
+}
+
+impl EditClip {
+    pub fn new(clip: crate::Clip) -> Self {
+        Self {
+            expires: clip.expires.date(),
+            version: clip.version(),
+            owned: clip.owner.has_owner(),
+            clip,
+            secrets: vec![],
+        }
+    }
+
+    pub fn with_secrets(self, secrets: Vec<Finding>) -> Self {
+        Self { secrets, ..self }
+    }
+}
+
+impl PageContext for EditClip {
+    fn title(&self) -> &str {
+        "Edit Clip"
+    }
+
+    fn template_path(&self) -> &str {
+        "clip_edit"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct EditCredentialRequired {
+    shortcode: crate::ShortCode,
+}
+
+impl PageContext for EditCredentialRequired {
+    fn title(&self) -> &str {
+        "Password Required"
+    }
+
+    fn template_path(&self) -> &str {
+        "clip_edit_need_credential"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct AdminLogin {}
+
+impl PageContext for AdminLogin {
+    fn title(&self) -> &str {
+        "Admin"
+    }
+
+    fn template_path(&self) -> &str {
+        "admin_login"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct AdminDashboard {
+    dashboard: crate::domain::admin::Dashboard,
+}
+
+impl PageContext for AdminDashboard {
+    fn title(&self) -> &str {
+        "Admin"
+    }
+
+    fn template_path(&self) -> &str {
+        "admin"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct TakenDown {
+    takedown: crate::domain::clip::field::Takedown,
+}
+
+impl PageContext for TakenDown {
+    fn title(&self) -> &str {
+        "Clip Unavailable"
+    }
+
+    fn template_path(&self) -> &str {
+        "clip_taken_down"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct ReportClip {
+    shortcode: crate::ShortCode,
+    // the report was filed, the page thanks the reporter instead of showing the form
+    filed: bool,
+}
+
+impl PageContext for ReportClip {
+    fn title(&self) -> &str {
+        "Report Clip"
+    }
+
+    fn template_path(&self) -> &str {
+        "clip_report"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize, Constructor)]
+pub struct AdminReports {
+    queue: crate::domain::report::ModerationQueue,
+}
+
+impl PageContext for AdminReports {
+    fn title(&self) -> &str {
+        "Reports"
+    }
+
+    fn template_path(&self) -> &str {
+        "admin_reports"
+    }
+
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
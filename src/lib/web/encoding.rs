@@ -0,0 +1,133 @@
+use std::convert::Infallible;
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rocket::request::{FromRequest, Outcome, Request};
+
+// AcceptEncoding is the `Accept-Encoding` header of a download
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AcceptEncoding {
+    pub zstd: bool,
+    pub gzip: bool,
+}
+
+impl AcceptEncoding {
+    pub fn parse(header: &str) -> Self {
+        let mut accepted = Self::default();
+        for coding in header.split(',') {
+            let mut params = coding.split(';').map(str::trim);
+            let name = params.next().unwrap_or_default().to_ascii_lowercase();
+            // `q=0` explicitly refuses a coding
+            let refused = params
+                .filter_map(|param| param.strip_prefix("q="))
+                .any(|q| q.parse::<f32>().is_ok_and(|q| q == 0.0));
+            match name.as_str() {
+                "zstd" => accepted.zstd = !refused,
+                "gzip" | "x-gzip" => accepted.gzip = !refused,
+                "*" if !refused => {
+                    accepted.zstd = true;
+                    accepted.gzip = true;
+                }
+                _ => (),
+            }
+        }
+        accepted
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AcceptEncoding {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(
+            req.headers()
+                .get_one("Accept-Encoding")
+                .map(AcceptEncoding::parse)
+                .unwrap_or_default(),
+        )
+    }
+}
+
+// clips are only stored compressed with zstd, gzip clients get them compressed on the fly
+pub fn gzip(content: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(content)?;
+    encoder.finish()
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+    use rocket::http::{ContentType, Header, Status};
+
+    use super::AcceptEncoding;
+    use crate::data::compression::COMPRESSION_THRESHOLD;
+    use crate::test::new_async_runtime;
+    use crate::web::api::test::new_api_key;
+    use crate::web::api::v1::ClipResponse;
+    use crate::web::test::new_rocket_client;
+
+    #[test]
+    fn parse_accept_encoding() {
+        assert_eq!(
+            AcceptEncoding::parse("gzip, deflate, br, zstd"),
+            AcceptEncoding {
+                zstd: true,
+                gzip: true
+            }
+        );
+        assert_eq!(
+            AcceptEncoding::parse("*, zstd;q=0"),
+            AcceptEncoding {
+                zstd: false,
+                gzip: true
+            }
+        );
+        assert_eq!(AcceptEncoding::parse("identity"), AcceptEncoding::default());
+    }
+
+    #[test]
+    fn raw_clips_are_sent_compressed() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+
+        let log = "INFO request served\n".repeat(COMPRESSION_THRESHOLD);
+        let response = client
+            .post("/api/v1/clips/raw")
+            .header(new_api_key(&client))
+            .header(ContentType::Plain)
+            .body(log.as_str())
+            .dispatch();
+        let clip: ClipResponse = response.into_json().unwrap();
+        let uri = format!("/clip/raw/{}", clip.shortcode);
+
+        let response = client
+            .get(uri.as_str())
+            .header(Header::new("Accept-Encoding", "gzip, zstd"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("zstd"));
+        assert_eq!(response.headers().get_one("Vary"), Some("Accept-Encoding"));
+        let body = zstd::decode_all(response.into_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(body, log.as_bytes());
+
+        let response = client
+            .get(uri.as_str())
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .dispatch();
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+        let mut body = String::new();
+        GzDecoder::new(response.into_bytes().unwrap().as_slice())
+            .read_to_string(&mut body)
+            .unwrap();
+        assert_eq!(body, log);
+
+        let response = client.get(uri.as_str()).dispatch();
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+        assert_eq!(response.into_string().unwrap(), log);
+    }
+}
@@ -0,0 +1,197 @@
+use rocket::http::ContentType;
+use rocket::State;
+
+use super::conditional::{Conditional, Preconditions, Validators};
+use super::PageError;
+use crate::data::AppDatabase;
+use crate::domain::clip::field::Tags;
+use crate::domain::feed::{Feed, FeedFormat, FEED_SIZE};
+use crate::service::{action, ask};
+
+// BaseUrl is the public address of the instance, like `https://stash.example`, the links of a
+// feed start with it. It is configured instead of taken from the request so clients can't change
+// the links of a cached feed with a forged Host header
+#[derive(Debug, Clone)]
+pub struct BaseUrl(pub String);
+
+impl Default for BaseUrl {
+    fn default() -> Self {
+        Self("http://localhost:8000".to_owned())
+    }
+}
+
+fn content_type(format: FeedFormat) -> ContentType {
+    let content_type = match format {
+        FeedFormat::Atom => ContentType::new("application", "atom+xml"),
+        FeedFormat::Rss => ContentType::new("application", "rss+xml"),
+    };
+    content_type.with_params(("charset", "utf-8"))
+}
+
+async fn render_feed(
+    tag: Option<&str>,
+    format: FeedFormat,
+    base: &BaseUrl,
+    preconditions: Preconditions,
+    database: &AppDatabase,
+) -> Result<Conditional<(ContentType, String)>, PageError> {
+    let tag = tag
+        .map(Tags::tag)
+        .transpose()
+        .map_err(|_| PageError::NotFound("Tag Not found".to_owned()))?;
+    let req = ask::ListClips {
+        tag: tag.clone(),
+        limit: FEED_SIZE,
+        offset: 0,
+    };
+
+    match action::list_public_clips(req, database.get_pool()).await {
+        Ok(clips) => {
+            let feed = Feed::new(&base.0, tag, &clips);
+            Ok(Conditional::new(
+                &preconditions,
+                Validators::list(&clips),
+                (content_type(format), feed.render(format)),
+            ))
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to list the clips of a feed");
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
+}
+
+// the most recent public clips, without the password protected ones
+#[rocket::get("/feed.atom")]
+pub async fn atom_feed(
+    base: &State<BaseUrl>,
+    preconditions: Preconditions,
+    database: &State<AppDatabase>,
+) -> Result<Conditional<(ContentType, String)>, PageError> {
+    render_feed(None, FeedFormat::Atom, base, preconditions, database).await
+}
+
+#[rocket::get("/feed.rss")]
+pub async fn rss_feed(
+    base: &State<BaseUrl>,
+    preconditions: Preconditions,
+    database: &State<AppDatabase>,
+) -> Result<Conditional<(ContentType, String)>, PageError> {
+    render_feed(None, FeedFormat::Rss, base, preconditions, database).await
+}
+
+// the most recent public clips with the tag
+#[rocket::get("/tag/<tag>/feed.atom")]
+pub async fn tag_atom_feed(
+    tag: &str,
+    base: &State<BaseUrl>,
+    preconditions: Preconditions,
+    database: &State<AppDatabase>,
+) -> Result<Conditional<(ContentType, String)>, PageError> {
+    render_feed(Some(tag), FeedFormat::Atom, base, preconditions, database).await
+}
+
+#[rocket::get("/tag/<tag>/feed.rss")]
+pub async fn tag_rss_feed(
+    tag: &str,
+    base: &State<BaseUrl>,
+    preconditions: Preconditions,
+    database: &State<AppDatabase>,
+) -> Result<Conditional<(ContentType, String)>, PageError> {
+    render_feed(Some(tag), FeedFormat::Rss, base, preconditions, database).await
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![atom_feed, rss_feed, tag_atom_feed, tag_rss_feed]
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::uri::Host;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::blocking::Client;
+    use rocket::uri;
+
+    use super::BaseUrl;
+    use crate::test::new_async_runtime;
+    use crate::web::api::test::new_api_key;
+    use crate::web::test::new_rocket_config;
+
+    #[test]
+    fn feeds_list_public_clips() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.base_url = BaseUrl("https://stash.example".to_owned());
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build rocket instance");
+        let key = new_api_key(&client);
+
+        for (fields, visibility) in [
+            (
+                r#""content": "<b>bold</b> & more", "title": "Tom & Jerry", "tags": ["rust"]"#,
+                "public",
+            ),
+            (r#""content": "untagged""#, "public"),
+            (
+                r#""content": "secret", "password": "hunter2", "tags": ["rust"]"#,
+                "public",
+            ),
+            (r#""content": "hidden", "tags": ["rust"]"#, "unlisted"),
+        ] {
+            let body = format!(r#"{{{}, "visibility": "{}"}}"#, fields, visibility);
+            let response = client
+                .post("/api/v1/clips")
+                .header(key.clone())
+                .header(ContentType::JSON)
+                .body(body)
+                .dispatch();
+            assert_eq!(response.status(), Status::Created);
+        }
+
+        let response = client.get("/feed.atom").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "atom+xml"))
+        );
+        let etag = response.headers().get_one("ETag").unwrap().to_owned();
+        assert!(etag.starts_with("W/"));
+        let atom = response.into_string().unwrap();
+        assert!(atom.contains(r#"<link rel="self" href="https://stash.example/feed.atom"/>"#));
+        assert!(atom.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(atom.contains("&lt;b&gt;bold&lt;/b&gt; &amp; more"));
+        assert!(atom.contains("untagged"));
+        assert!(!atom.contains("secret") && !atom.contains("hidden"));
+
+        let response = client
+            .get("/feed.atom")
+            .header(Header::new("If-None-Match", etag))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotModified);
+
+        let rss = client.get("/tag/rust/feed.rss").dispatch();
+        assert_eq!(
+            rss.content_type(),
+            Some(ContentType::new("application", "rss+xml"))
+        );
+        let rss = rss.into_string().unwrap();
+        assert!(rss.contains("<title>Tom &amp; Jerry</title>"));
+        assert!(rss.contains("&amp;lt;b&amp;gt;bold"));
+        assert!(rss.contains("https://stash.example/clip/"));
+        assert!(!rss.contains("untagged"));
+        assert!(!rss.contains("secret") && !rss.contains("hidden"));
+
+        // links don't follow the Host header, so a forged one can't end up in a cached feed
+        let mut request = client
+            .get("/feed.rss")
+            .header(Header::new("X-Forwarded-Proto", "http"));
+        request
+            .inner_mut()
+            .set_host(Host::from(uri!("evil.example")));
+        let rss = request.dispatch().into_string().unwrap();
+        assert!(!rss.contains("evil.example"));
+
+        let response = client.get("/tag/not%20a%20tag/feed.atom").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+}
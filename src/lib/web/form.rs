@@ -1,5 +1,7 @@
 use crate::domain::clip::field;
-use rocket::FromForm;
+use crate::domain::report;
+use crate::ShortCode;
+use rocket::{FromForm, FromFormField};
 use serde::Serialize;
 
 #[derive(Debug, Serialize, FromForm)]
@@ -8,9 +10,95 @@ pub struct NewClip {
     pub title: field::Title,
     pub expires: field::Expires,
     pub password: field::Password,
+    pub visibility: field::Visibility,
+    pub tags: field::Tags,
+    pub forked_from: field::ForkedFrom,
+    // set by the button that confirms the warning about secrets in the content
+    pub allow_secrets: bool,
+}
+
+#[derive(Debug, Serialize, FromForm)]
+pub struct NewCollection {
+    pub title: field::Title,
+    pub expires: field::Expires,
+    pub password: field::Password,
+    // shortcodes or links of the clips, one per line
+    pub clips: String,
+}
+
+impl NewCollection {
+    // `aB3dE9x` and `https://clip.example/clip/aB3dE9x` both add the clip `aB3dE9x`
+    pub fn shortcodes(&self) -> Vec<ShortCode> {
+        self.clips
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter_map(|clip| clip.trim_end_matches('/').rsplit('/').next())
+            .filter(|shortcode| !shortcode.is_empty())
+            .map(ShortCode::from)
+            .collect()
+    }
 }
 
 #[derive(Debug, Serialize, FromForm)]
 pub struct GetPasswordProtectedClip {
     pub password: field::Password,
 }
+
+#[derive(Debug, Serialize, FromForm)]
+pub struct EditClip {
+    pub content: field::Content,
+    pub title: field::Title,
+    pub expires: field::Expires,
+    // a new password, the current one is kept when it is empty
+    pub password: field::Password,
+    pub remove_password: bool,
+    pub visibility: field::Visibility,
+    // the version of the clip the edit is based on, edits of a changed clip are rejected
+    pub version: String,
+    // set by the button that confirms the warning about secrets in the content
+    pub allow_secrets: bool,
+}
+
+#[derive(Debug, Serialize, FromForm)]
+pub struct AdminLogin {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, FromForm)]
+pub struct NewReport {
+    pub reason: report::Reason,
+    pub contact: report::Contact,
+}
+
+#[derive(Debug, Serialize, FromForm)]
+pub struct TakeDown {
+    pub reason: field::TakedownReason,
+    pub note: String,
+}
+
+// either credential allows editing a clip, the password only when the clip has one
+#[derive(Debug, Serialize, FromForm)]
+pub struct EditCredential {
+    pub password: field::Password,
+    pub api_key: String,
+}
+
+// the two clips of a diff are unlocked with their own passwords
+#[derive(Debug, Serialize, FromForm)]
+pub struct GetPasswordProtectedDiff {
+    pub password_a: field::Password,
+    pub password_b: field::Password,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, FromFormField)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffView {
+    #[default]
+    #[field(value = "side-by-side")]
+    SideBySide,
+    Unified,
+}
+
+#[derive(Debug, Serialize, FromForm)]
+pub struct ClipOwner {
+    pub api_key: String,
+}
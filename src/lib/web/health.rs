@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+
+use super::hitcounter::HitCounter;
+use crate::data::AppDatabase;
+use crate::domain::maintenance::Maintenance;
+use crate::service::action;
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Check {
+    fn pass() -> Self {
+        Self {
+            ok: true,
+            detail: None,
+        }
+    }
+
+    fn fail<D: Into<String>>(detail: D) -> Self {
+        Self {
+            ok: false,
+            detail: Some(detail.into()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Health {
+    pub status: &'static str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<&'static str, Check>,
+}
+
+// the process is up and able to answer requests
+#[rocket::get("/healthz")]
+pub fn healthz() -> Json<Health> {
+    Json(Health {
+        status: "ok",
+        checks: BTreeMap::new(),
+    })
+}
+
+// the instance can serve traffic: the database is reachable and up to date and the
+// background workers are running
+#[rocket::get("/readyz")]
+pub async fn readyz(
+    database: &State<AppDatabase>,
+    hit_counter: &State<HitCounter>,
+    maintenance: &State<Maintenance>,
+) -> (Status, Json<Health>) {
+    let pool = database.get_pool();
+    let mut checks = BTreeMap::new();
+
+    // the response is public, the details of a failure are only logged
+    let reachable = match action::ping_database(pool).await {
+        Ok(()) => Check::pass(),
+        Err(e) => {
+            tracing::error!(error = %e, "readiness check failed to reach the database");
+            Check::fail("database unavailable")
+        }
+    };
+    let migrations = if reachable.ok {
+        match action::pending_migrations(pool).await {
+            Ok(pending) if pending.is_empty() => Check::pass(),
+            Ok(pending) => {
+                tracing::error!(?pending, "readiness check found pending migrations");
+                Check::fail("migrations pending")
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "readiness check failed to read the migrations");
+                Check::fail("database unavailable")
+            }
+        }
+    } else {
+        Check::fail("database is not reachable")
+    };
+    checks.insert("database", reachable);
+    checks.insert("migrations", migrations);
+
+    let worker = |alive: bool| {
+        if alive {
+            Check::pass()
+        } else {
+            Check::fail("worker is not running")
+        }
+    };
+    checks.insert("hit_counter", worker(hit_counter.is_alive()));
+    checks.insert("maintenance", worker(maintenance.is_alive()));
+
+    if checks.values().all(|check| check.ok) {
+        let status = "ready";
+        (Status::Ok, Json(Health { status, checks }))
+    } else {
+        let status = "unavailable";
+        (Status::ServiceUnavailable, Json(Health { status, checks }))
+    }
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![healthz, readyz]
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::Status;
+    use rocket::local::blocking::Client;
+
+    use crate::test::new_async_runtime;
+    use crate::web::test::{new_rocket_client, new_rocket_config};
+
+    #[test]
+    fn ready_when_database_and_workers_are_up() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+
+        let response = client.get("/healthz").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client.get("/readyz").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["status"], "ready");
+        assert_eq!(body["checks"]["migrations"]["ok"], true);
+    }
+
+    #[test]
+    fn not_ready_when_workers_are_gone() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+        // the workers run on this runtime
+        drop(rt);
+
+        let response = client.get("/readyz").dispatch();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        let body: serde_json::Value = response.into_json().unwrap();
+        assert_eq!(body["checks"]["hit_counter"]["ok"], false);
+        assert_eq!(body["checks"]["maintenance"]["ok"], false);
+    }
+
+    #[test]
+    fn failures_are_not_detailed_publicly() {
+        let rt = new_async_runtime();
+        let config = new_rocket_config(rt.handle());
+        let pool = config.database.get_pool().clone();
+        let version: i64 = rt.block_on(async {
+            sqlx::query_scalar(
+                "DELETE FROM _sqlx_migrations WHERE version = \
+                 (SELECT MAX(version) FROM _sqlx_migrations) RETURNING version",
+            )
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+        });
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build a rocket instance");
+
+        let response = client.get("/readyz").dispatch();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        let body = response.into_string().unwrap();
+        assert!(body.contains("migrations pending"));
+        assert!(!body.contains(&version.to_string()));
+    }
+}
@@ -1,115 +1,611 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-use crossbeam_channel::{unbounded, Sender, TryRecvError};
-use parking_lot::Mutex;
+use chrono::{NaiveDate, Utc};
+use rocket::request::{FromRequest, Outcome, Request};
+use sha2::{Digest, Sha256};
 use tokio::runtime::Handle;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
 
+use crate::service::ask;
 use crate::{data::DatabasePool, service, ServiceError, ShortCode};
 
 #[derive(Debug, thiserror::Error)]
 enum HitCountError {
     #[error("service error: {0}")]
     Service(#[from] ServiceError),
-    #[error("communication error: {0}")]
-    Channel(#[from] crossbeam_channel::SendError<HitCountMsg>),
 }
 
-type HitStore = Arc<Mutex<HashMap<ShortCode, u32>>>;
-
 enum HitCountMsg {
-    Commit,
     Hit(ShortCode, u32),
+    // a single view of a clip, counts as a hit and is recorded in the clip stats
+    View(ShortCode, View),
+    // flush whatever is pending right away and notify the sender once it's done
+    Flush(oneshot::Sender<()>),
+}
+
+// View describes a single clip view, it is extracted from the request by the `View` guard
+#[derive(Debug, Clone)]
+pub struct View {
+    day: NaiveDate,
+    referrer: Option<String>,
+    agent: &'static str,
+    visitor: String,
+}
+
+impl View {
+    pub fn new(referrer: Option<&str>, user_agent: Option<&str>, client: Option<IpAddr>) -> Self {
+        let day = Utc::now().date_naive();
+        let user_agent = user_agent.unwrap_or_default();
+
+        Self {
+            day,
+            referrer: referrer.and_then(referrer_host),
+            agent: agent_family(user_agent),
+            visitor: visitor_id(client, user_agent, day),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for View {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = req.headers();
+        let mut view = View::new(
+            headers.get_one("Referer"),
+            headers.get_one("User-Agent"),
+            req.client_ip(),
+        );
+
+        // navigating within clipstash itself is not a referral
+        if let (Some(referrer), Some(host)) = (&view.referrer, req.host()) {
+            if referrer.as_str() == host.domain().as_str() {
+                view.referrer = None;
+            }
+        }
+
+        Outcome::Success(view)
+    }
+}
+
+// extracts the lowercase host of a referrer url, e.g. `https://user@example.com:80/a?b` -> `example.com`
+fn referrer_host(referrer: &str) -> Option<String> {
+    let rest = referrer
+        .split_once("://")
+        .map(|(_, rest)| rest)
+        .unwrap_or(referrer);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = match host.strip_prefix('[') {
+        // ipv6 literal, the port follows the closing bracket
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+
+    if host.is_empty() {
+        None
+    } else {
+        Some(host.to_lowercase())
+    }
+}
+
+// maps a user agent string onto a small set of families, the order of the checks matters
+// because most browsers mention the engines of the others
+fn agent_family(user_agent: &str) -> &'static str {
+    let ua = user_agent.to_lowercase();
+    let families = [
+        ("bot", "Bot"),
+        ("spider", "Bot"),
+        ("crawler", "Bot"),
+        ("edg/", "Edge"),
+        ("opr/", "Opera"),
+        ("firefox/", "Firefox"),
+        ("chrome/", "Chrome"),
+        ("crios/", "Chrome"),
+        ("safari/", "Safari"),
+        ("curl/", "curl"),
+        ("wget/", "Wget"),
+        ("reqwest/", "clipclient"),
+    ];
+
+    if ua.trim().is_empty() {
+        return "Unknown";
+    }
+
+    families
+        .iter()
+        .find(|(needle, _)| ua.contains(needle))
+        .map(|(_, family)| *family)
+        .unwrap_or("Other")
+}
+
+// visitors are identified by a salted hash of their address and user agent. The salt is
+// generated on startup and the day is part of the hash, so visitors can't be followed
+// across days or be mapped back to an address
+fn visitor_id(client: Option<IpAddr>, user_agent: &str, day: NaiveDate) -> String {
+    static SALT: OnceLock<[u8; 16]> = OnceLock::new();
+    let salt = SALT.get_or_init(rand::random);
+
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(client.map(|ip| ip.to_string()).unwrap_or_default());
+    hasher.update([0]);
+    hasher.update(user_agent);
+    hasher.update([0]);
+    hasher.update(day.to_string());
+
+    hasher.finalize()[..16]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct ViewKey {
+    shortcode: ShortCode,
+    day: NaiveDate,
+    referrer: Option<String>,
+    agent: String,
+}
+
+// hits and views aggregated in memory until the next flush
+#[derive(Debug, Default)]
+struct Pending {
+    hits: HashMap<ShortCode, u32>,
+    views: HashMap<ViewKey, u32>,
+    visitors: HashSet<(ShortCode, NaiveDate, String)>,
 }
 
+impl Pending {
+    // the number of clips with pending hits. A view also adds a view bucket and a visitor, but
+    // only its hit counts towards a flush
+    fn clips(&self) -> usize {
+        self.hits.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.hits.is_empty() && self.views.is_empty() && self.visitors.is_empty()
+    }
+
+    fn add_hits(&mut self, shortcode: ShortCode, count: u32) {
+        let hits = self.hits.entry(shortcode).or_insert(0);
+        *hits = hits.saturating_add(count);
+    }
+
+    fn add_view(&mut self, shortcode: ShortCode, view: View) {
+        self.add_hits(shortcode.clone(), 1);
+        self.visitors
+            .insert((shortcode.clone(), view.day, view.visitor));
+
+        let key = ViewKey {
+            shortcode,
+            day: view.day,
+            referrer: view.referrer,
+            agent: view.agent.to_owned(),
+        };
+        let views = self.views.entry(key).or_insert(0);
+        *views = views.saturating_add(1);
+    }
+
+    fn take(&mut self) -> ask::HitBatch {
+        let pending = std::mem::take(self);
+
+        ask::HitBatch {
+            hits: pending.hits.into_iter().collect(),
+            views: pending
+                .views
+                .into_iter()
+                .map(|(key, views)| ask::ViewCount {
+                    shortcode: key.shortcode,
+                    day: key.day,
+                    referrer: key.referrer,
+                    agent: key.agent,
+                    views,
+                })
+                .collect(),
+            visitors: pending
+                .visitors
+                .into_iter()
+                .map(|(shortcode, day, visitor)| ask::Visitor {
+                    shortcode,
+                    day,
+                    visitor,
+                })
+                .collect(),
+        }
+    }
+
+    // puts a batch that failed to commit back so it's retried with the next flush
+    fn restore(&mut self, batch: ask::HitBatch) {
+        for (shortcode, count) in batch.hits {
+            self.add_hits(shortcode, count);
+        }
+        for view in batch.views {
+            let key = ViewKey {
+                shortcode: view.shortcode,
+                day: view.day,
+                referrer: view.referrer,
+                agent: view.agent,
+            };
+            let views = self.views.entry(key).or_insert(0);
+            *views = views.saturating_add(view.views);
+        }
+        for visitor in batch.visitors {
+            self.visitors
+                .insert((visitor.shortcode, visitor.day, visitor.visitor));
+        }
+    }
+}
+
+// HitCounterConfig controls how hits are buffered before being written to the database
+#[derive(Debug, Clone)]
+pub struct HitCounterConfig {
+    // maximum number of messages waiting in the channel before `hit` has to wait
+    pub capacity: usize,
+    // number of clips with pending hits that triggers a flush without waiting for the interval
+    pub batch_size: usize,
+    // maximum time a hit stays in memory before being written
+    pub flush_interval: Duration,
+}
+
+impl Default for HitCounterConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            batch_size: 256,
+            flush_interval: Duration::from_secs(5),
+        }
+    }
+}
+
+// counters shared between the HitCounter handle and the writer task
+#[derive(Debug, Default)]
+struct Metrics {
+    hits_received: AtomicU64,
+    hits_flushed: AtomicU64,
+    flushes: AtomicU64,
+    flush_failures: AtomicU64,
+    backpressure_waits: AtomicU64,
+    last_flush_micros: AtomicU64,
+}
+
+// HitCounterStats is a point in time snapshot of the HitCounter metrics
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct HitCounterStats {
+    // messages currently waiting in the channel
+    pub queue_depth: usize,
+    pub queue_capacity: usize,
+    pub hits_received: u64,
+    pub hits_flushed: u64,
+    pub flushes: u64,
+    pub flush_failures: u64,
+    // number of times `hit` found the channel full and had to wait for the writer
+    pub backpressure_waits: u64,
+    pub last_flush_micros: u64,
+}
+
+// HitCounter defers hit count writes: hits are sent over a bounded channel to a tokio task
+// that aggregates them per clip and flushes them in batches, either when enough clips are
+// pending or when the flush interval elapses
 pub struct HitCounter {
-    tx: Sender<HitCountMsg>,
+    tx: mpsc::Sender<HitCountMsg>,
+    metrics: Arc<Metrics>,
+    task: JoinHandle<()>,
 }
 
 impl HitCounter {
-    fn commit_hits(
-        hits: HitStore,
-        handle: Handle,
+    pub fn new(pool: DatabasePool, handle: Handle) -> Self {
+        Self::with_config(pool, handle, HitCounterConfig::default())
+    }
+
+    pub fn with_config(pool: DatabasePool, handle: Handle, config: HitCounterConfig) -> Self {
+        let (tx, rx) = mpsc::channel(config.capacity.max(1));
+        let metrics = Arc::new(Metrics::default());
+
+        let task = handle.spawn(Self::run(rx, pool, config, Arc::clone(&metrics)));
+
+        Self { tx, metrics, task }
+    }
+
+    async fn run(
+        mut rx: mpsc::Receiver<HitCountMsg>,
         pool: DatabasePool,
-    ) -> Result<(), HitCountError> {
-        let hits = Arc::clone(&hits);
-        let hits: Vec<(ShortCode, u32)> =
-            {
-                let mut hits = hits.lock();
-                let hits_vec = hits.iter().map(|(k, v)| (k.clone(), *v)).collect();
-                hits.clear();
-                hits_vec
-            };
+        config: HitCounterConfig,
+        metrics: Arc<Metrics>,
+    ) {
+        let mut pending = Pending::default();
+        let mut interval = tokio::time::interval(config.flush_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
-        handle.block_on(async move {
-            let transaction = service::action::begin_transaction(&pool).await?;
+        loop {
+            tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(HitCountMsg::Hit(shortcode, count)) => {
+                        pending.add_hits(shortcode, count);
+                        if pending.clips() >= config.batch_size {
+                            Self::flush_pending(&mut pending, &pool, &metrics).await;
+                        }
+                    }
+                    Some(HitCountMsg::View(shortcode, view)) => {
+                        pending.add_view(shortcode, view);
+                        if pending.clips() >= config.batch_size {
+                            Self::flush_pending(&mut pending, &pool, &metrics).await;
+                        }
+                    }
+                    Some(HitCountMsg::Flush(done)) => {
+                        Self::flush_pending(&mut pending, &pool, &metrics).await;
+                        let _ = done.send(());
+                    }
+                    // every HitCounter handle is gone, write what's left and stop
+                    None => {
+                        Self::flush_pending(&mut pending, &pool, &metrics).await;
+                        break;
+                    }
+                },
+                _ = interval.tick() => Self::flush_pending(&mut pending, &pool, &metrics).await,
+            }
+        }
+    }
 
-            for (shortcode, hits) in hits {
-                if let Err(e) = service::action::increase_hit_count(&shortcode, hits, &pool).await {
-                    eprintln!("failed to increase hit count: {}", e);
-                }
+    async fn flush_pending(pending: &mut Pending, pool: &DatabasePool, metrics: &Metrics) {
+        if pending.is_empty() {
+            return;
+        }
+
+        let batch = pending.take();
+        let total: u64 = batch.hits.iter().map(|(_, count)| u64::from(*count)).sum();
+        let started = Instant::now();
+
+        match Self::commit_hits(&batch, pool).await {
+            Ok(()) => {
+                metrics.hits_flushed.fetch_add(total, Ordering::Relaxed);
+                metrics.flushes.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to commit hits");
+                metrics.flush_failures.fetch_add(1, Ordering::Relaxed);
+                pending.restore(batch);
             }
+        }
 
-            Ok(service::action::end_transaction(transaction).await?)
-        })
+        let elapsed = u64::try_from(started.elapsed().as_micros()).unwrap_or(u64::MAX);
+        metrics.last_flush_micros.store(elapsed, Ordering::Relaxed);
     }
 
-    fn process_msg(
-        msg: HitCountMsg,
-        hits: HitStore,
-        handle: Handle,
-        pool: DatabasePool,
-    ) -> Result<(), HitCountError> {
-        match msg {
-            HitCountMsg::Commit => Self::commit_hits(hits.clone(), handle.clone(), pool.clone())?,
-            HitCountMsg::Hit(shortcode, count) => {
-                let mut hitcount = hits.lock();
-                let hitcount = hitcount.entry(shortcode).or_insert(0);
-
-                *hitcount += count;
+    async fn commit_hits(batch: &ask::HitBatch, pool: &DatabasePool) -> Result<(), HitCountError> {
+        Ok(service::action::commit_hits(batch, pool).await?)
+    }
+
+    // hit registers `count` views of a clip that are not recorded in the clip stats
+    pub async fn hit(&self, shortcode: ShortCode, count: u32) {
+        self.send(HitCountMsg::Hit(shortcode, count), count).await
+    }
+
+    // view registers a single view of a clip and records it in the clip stats
+    pub async fn view(&self, shortcode: ShortCode, view: View) {
+        self.send(HitCountMsg::View(shortcode, view), 1).await
+    }
+
+    // when the channel is full the caller waits until the writer catches up instead of
+    // dropping the hit
+    async fn send(&self, msg: HitCountMsg, hits: u32) {
+        let msg = match self.tx.try_send(msg) {
+            Ok(()) => None,
+            Err(TrySendError::Full(msg)) => {
+                self.metrics
+                    .backpressure_waits
+                    .fetch_add(1, Ordering::Relaxed);
+                Some(msg)
+            }
+            Err(TrySendError::Closed(_)) => {
+                tracing::error!("hit counter task is not running");
+                return;
+            }
+        };
+
+        if let Some(msg) = msg {
+            if self.tx.send(msg).await.is_err() {
+                tracing::error!("hit counter task is not running");
+                return;
             }
         }
 
-        Ok(())
+        self.metrics
+            .hits_received
+            .fetch_add(u64::from(hits), Ordering::Relaxed);
     }
 
-    pub fn new(pool: DatabasePool, handle: Handle) -> Self {
-        let (sx, rx) = unbounded();
-        let sx_clone = sx.clone();
-        let rx_clone = rx.clone();
-
-        let _ = std::thread::spawn(move || {
-            println!("HitCounter thread spawned");
-
-            let store: HitStore = Arc::new(Mutex::new(HashMap::new()));
-
-            loop {
-                match rx_clone.try_recv() {
-                    Ok(msg) => {
-                        if let Err(e) =
-                            Self::process_msg(msg, store.clone(), handle.clone(), pool.clone())
-                        {
-                            eprintln!("message processing failed: {}", e);
-                        }
-                    }
+    // flush writes all pending hits to the database and waits until they are committed
+    pub async fn flush(&self) {
+        let (done, wait) = oneshot::channel();
+        if self.tx.send(HitCountMsg::Flush(done)).await.is_ok() {
+            let _ = wait.await;
+        }
+    }
 
-                    Err(e) => match e {
-                        TryRecvError::Empty => {
-                            std::thread::sleep(Duration::from_secs(5));
-                            if let Err(e) = sx_clone.send(HitCountMsg::Commit) {
-                                eprintln!("failed to send commit msg to hits channel: {}", e)
-                            }
-                        }
-                        _ => break,
-                    },
+    // the writer task only stops when it panics or when the runtime shuts down
+    pub fn is_alive(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    pub fn stats(&self) -> HitCounterStats {
+        HitCounterStats {
+            queue_depth: self.tx.max_capacity() - self.tx.capacity(),
+            queue_capacity: self.tx.max_capacity(),
+            hits_received: self.metrics.hits_received.load(Ordering::Relaxed),
+            hits_flushed: self.metrics.hits_flushed.load(Ordering::Relaxed),
+            flushes: self.metrics.flushes.load(Ordering::Relaxed),
+            flush_failures: self.metrics.flush_failures.load(Ordering::Relaxed),
+            backpressure_waits: self.metrics.backpressure_waits.load(Ordering::Relaxed),
+            last_flush_micros: self.metrics.last_flush_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::time::Duration;
+
+    use super::{agent_family, referrer_host, HitCounter, HitCounterConfig, Pending, View};
+    use crate::data::test::new_db;
+    use crate::domain::audit::Actor;
+    use crate::domain::clip::field;
+    use crate::domain::secret::SecretScanner;
+    use crate::service::{action, ask};
+    use crate::test::new_async_runtime;
+
+    #[test]
+    fn hits_are_flushed_in_batches() {
+        let rt = new_async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool().clone();
+
+        let config = HitCounterConfig {
+            capacity: 4,
+            batch_size: 2,
+            flush_interval: Duration::from_secs(60),
+        };
+        let hit_counter = HitCounter::with_config(pool.clone(), rt.handle().clone(), config);
+
+        rt.block_on(async move {
+            let secrets = SecretScanner::default();
+            let mut shortcodes = vec![];
+            for i in 0..3 {
+                let req = ask::NewClip {
+                    content: field::Content::new(&format!("clip {}", i)).unwrap(),
+                    title: field::Title::default(),
+                    expires: field::Expires::default(),
+                    password: field::Password::default(),
+                    owner: field::Owner::default(),
+                    visibility: field::Visibility::default(),
+                    tags: field::Tags::default(),
+                    forked_from: field::ForkedFrom::default(),
+                    allow_secrets: false,
+                };
+                let clip =
+                    action::new_clip(req, &Actor::default(), &secrets, Default::default(), &pool)
+                        .await
+                        .unwrap();
+                shortcodes.push(clip.shortcode);
+            }
+
+            for _ in 0..5 {
+                for shortcode in &shortcodes {
+                    hit_counter.hit(shortcode.clone(), 1).await;
                 }
             }
+            hit_counter.flush().await;
+
+            for shortcode in shortcodes {
+                let clip = action::get_clip(shortcode.into(), &Actor::default(), &pool)
+                    .await
+                    .unwrap();
+                assert_eq!(clip.hits.into_inner(), 5);
+            }
+
+            let stats = hit_counter.stats();
+            assert_eq!(stats.hits_received, 15);
+            assert_eq!(stats.hits_flushed, 15);
+            assert_eq!(stats.flush_failures, 0);
         });
-        Self { tx: sx }
     }
 
-    pub fn hit(&self, shortcode: ShortCode, count: u32) {
-        if let Err(e) = self.tx.send(HitCountMsg::Hit(shortcode, count)) {
-            eprintln!("hit count error: {}", e);
-        }
+    #[test]
+    fn views_count_once_towards_a_flush() {
+        let mut pending = Pending::default();
+        pending.add_view("abc".into(), View::new(None, None, None));
+        pending.add_view("abc".into(), View::new(None, Some("curl/8.0"), None));
+        assert_eq!(pending.clips(), 1);
+
+        pending.add_hits("def".into(), 3);
+        assert_eq!(pending.clips(), 2);
+        assert!(!pending.is_empty());
+        pending.take();
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn views_are_recorded_in_clip_stats() {
+        let rt = new_async_runtime();
+        let db = new_db(rt.handle());
+        let pool = db.get_pool().clone();
+        let hit_counter = HitCounter::new(pool.clone(), rt.handle().clone());
+
+        rt.block_on(async move {
+            let owner = field::Owner::new(vec![1, 2, 3]);
+            let req = ask::NewClip {
+                content: field::Content::new("clip").unwrap(),
+                title: field::Title::default(),
+                expires: field::Expires::default(),
+                password: field::Password::default(),
+                owner: owner.clone(),
+                visibility: field::Visibility::default(),
+                tags: field::Tags::default(),
+                forked_from: field::ForkedFrom::default(),
+                allow_secrets: false,
+            };
+            let secrets = SecretScanner::default();
+            let clip =
+                action::new_clip(req, &Actor::default(), &secrets, Default::default(), &pool)
+                    .await
+                    .unwrap();
+            let shortcode = clip.shortcode;
+
+            let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:120.0) Gecko/20100101 Firefox/120.0";
+            let visitors = [
+                (Some("https://example.com/a"), firefox, [10, 0, 0, 1]),
+                (Some("https://example.com/b"), firefox, [10, 0, 0, 1]),
+                (None, "curl/8.0", [10, 0, 0, 2]),
+            ];
+            for (referrer, agent, ip) in visitors {
+                let view = View::new(referrer, Some(agent), Some(ip.into()));
+                hit_counter.view(shortcode.clone(), view).await;
+            }
+            hit_counter.flush().await;
+
+            let req = ask::GetClipStats {
+                shortcode: shortcode.clone(),
+                owner: field::Owner::new(vec![3, 2, 1]),
+            };
+            assert!(action::get_clip_stats(req, &pool).await.is_err());
+
+            let req = ask::GetClipStats { shortcode, owner };
+            let stats = action::get_clip_stats(req, &pool).await.unwrap();
+            assert_eq!(stats.hits, 3);
+            assert_eq!(stats.daily.len(), 1);
+            assert_eq!(stats.daily[0].views, 3);
+            assert_eq!(stats.daily[0].unique_visitors, 2);
+            assert_eq!(stats.referrers.len(), 1);
+            assert_eq!(stats.referrers[0].host, "example.com");
+            assert_eq!(stats.referrers[0].views, 2);
+            assert_eq!(stats.agents[0].family, "Firefox");
+        });
+    }
+
+    #[test]
+    fn parse_view_details() {
+        assert_eq!(
+            referrer_host("https://user@Example.com:8080/path?q=1").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(referrer_host("http://[::1]:8000/").as_deref(), Some("::1"));
+        assert_eq!(referrer_host(""), None);
+
+        let chrome = "Mozilla/5.0 (Windows NT 10.0) AppleWebKit/537.36 Chrome/119.0 Safari/537.36";
+        let edge =
+            "Mozilla/5.0 (Windows NT 10.0) AppleWebKit/537.36 Chrome/119.0 Safari/537.36 Edg/119.0";
+        assert_eq!(agent_family(chrome), "Chrome");
+        assert_eq!(agent_family(edge), "Edge");
+        assert_eq!(agent_family("Googlebot/2.1"), "Bot");
+        assert_eq!(agent_family(""), "Unknown");
     }
 }
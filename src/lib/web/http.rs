@@ -1,19 +1,48 @@
-use super::hitcounter::HitCounter;
-use super::{form, PASSWORD_COOKIE};
+use super::api::ApiKey;
+use super::conditional::{Conditional, Preconditions, Validators};
+use super::encoding::{self, AcceptEncoding};
+use super::hitcounter::{HitCounter, View};
+use super::range::{ByteRange, RangeRequest, Ranged};
+use super::{form, API_KEY_COOKIE, PASSWORD_COOKIE};
+use crate::data::compression::{ContentEncoding, StoredContent, COMPRESSION_THRESHOLD};
 use crate::data::AppDatabase;
+use crate::domain::audit::Actor;
+use crate::domain::clip::field::{Owner, Password, Tags, Takedown};
+use crate::domain::limits::StorageQuota;
+use crate::domain::secret::{SecretPolicy, SecretScanner};
 use crate::service::{self, action, ask};
 use crate::web::{ctx, renderer::Renderer, PageError};
-use crate::{ServiceError, ShortCode};
+use crate::{Clip, ClipError, ServiceError, ShortCode};
 use rocket::form::{Contextual, Form};
 use rocket::http::{Cookie, CookieJar, Status};
+use rocket::request::{FromRequest, Outcome, Request};
 use rocket::response::content::RawHtml;
 use rocket::response::status::{self};
 use rocket::response::Redirect;
-use rocket::{uri, State};
+use rocket::{uri, Either, State};
+use std::convert::Infallible;
+use std::str::FromStr;
+
+// the number of tags suggested on the home page and of clips shown on a tag page
+const SUGGESTED_TAGS: u32 = 50;
+const TAGGED_CLIPS: u32 = 50;
+
+const INVALID_STATS_KEY: &str = "A valid API key is required to view the clip stats";
+
+// the home page suggests the tags that are already in use
+async fn home_context(database: &AppDatabase) -> ctx::Home {
+    match action::popular_tags(SUGGESTED_TAGS, database.get_pool()).await {
+        Ok(tags) => ctx::Home::new(tags),
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to load the suggested tags");
+            ctx::Home::default()
+        }
+    }
+}
 
 #[rocket::get("/")]
-fn home(renderer: &State<Renderer<'_>>) -> RawHtml<String> {
-    let context = ctx::Home::default();
+async fn home(database: &State<AppDatabase>, renderer: &State<Renderer<'_>>) -> RawHtml<String> {
+    let context = home_context(database).await;
     RawHtml(renderer.render(context, &[]))
 }
 
@@ -22,25 +51,48 @@ pub async fn new_clip(
     form: Form<Contextual<'_, form::NewClip>>,
 
     database: &State<AppDatabase>,
+    secrets: &State<SecretScanner>,
+    quota: &State<StorageQuota>,
+    actor: Actor,
     renderer: &State<Renderer<'_>>,
 ) -> Result<Redirect, (Status, RawHtml<String>)> {
     let form = form.into_inner();
     if let Some(value) = form.value {
+        // the form is shown again with the entered values when the author has to confirm secrets
+        let entered = handlebars::to_json(&form.context);
         let req = service::ask::NewClip {
             content: value.content,
             title: value.title,
             expires: value.expires,
             password: value.password,
+            owner: Default::default(),
+            visibility: value.visibility,
+            tags: value.tags,
+            forked_from: value.forked_from,
+            allow_secrets: value.allow_secrets,
         };
 
-        match action::new_clip(req, database.get_pool()).await {
+        match action::new_clip(req, &actor, secrets, **quota, database.get_pool()).await {
             Ok(clip) => Ok(Redirect::to(uri!(get_clip(shortcode = clip.shortcode)))),
+            Err(ServiceError::Clip(ClipError::Secrets(findings)))
+                if secrets.policy() == SecretPolicy::Warn =>
+            {
+                let context = home_context(database).await.with_secrets(findings);
+                Err((
+                    Status::BadRequest,
+                    RawHtml(renderer.render_with_data(context, ("clip", &entered), &[])),
+                ))
+            }
+            Err(ServiceError::Clip(e)) => Err((
+                Status::BadRequest,
+                RawHtml(renderer.render(home_context(database).await, &[e.to_string().as_str()])),
+            )),
             Err(e) => {
-                eprintln!("internal error: {}", e);
+                tracing::error!(error = %e, "failed to create clip");
                 Err((
                     Status::InternalServerError,
                     RawHtml(renderer.render(
-                        ctx::Home::default(),
+                        home_context(database).await,
                         &["A server error occured, please try again"],
                     )),
                 ))
@@ -55,46 +107,109 @@ pub async fn new_clip(
                     if let ErrorKind::Validation(msg) = &err.kind {
                         msg.as_ref()
                     } else {
-                        eprintln!("unhandler error, {}", err);
+                        tracing::warn!(error = %err, "unhandled form error");
                         "A server error occured, please try again"
                     }
                 })
                 .collect::<Vec<_>>();
         Err((
             Status::BadRequest,
-            RawHtml(
-                renderer.render_with_data(ctx::Home::default(), ("clip", &form.context), &errors)
-            ),
+            RawHtml(renderer.render_with_data(
+                home_context(database).await,
+                ("clip", &form.context),
+                &errors,
+            )),
         ))
     }
 }
 
+// clips an admin took down are answered with a page explaining why, legal takedowns with 451
+fn taken_down(takedown: Takedown, renderer: &Renderer<'_>) -> PageError {
+    let legal = takedown.reason.is_legal();
+    let page = RawHtml(renderer.render(ctx::TakenDown::new(takedown), &[]));
+    if legal {
+        PageError::UnavailableForLegalReasons(page)
+    } else {
+        PageError::Gone(page)
+    }
+}
+
+// keys that were revoked or disabled are refused the same way the API key guard refuses them
+async fn active_key(api_key: Option<ApiKey>, database: &AppDatabase) -> Option<ApiKey> {
+    let api_key = api_key?;
+    match action::is_api_key_valid(api_key.clone(), database.get_pool()).await {
+        Ok(true) => Some(api_key),
+        Ok(_) => None,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to check an API key");
+            None
+        }
+    }
+}
+
+// the owner of the API key cookie, only active keys open private clips
+struct CookieOwner(Owner);
+
+// the API key the owner entered on the stats or edit page also opens their private clips. The
+// key is checked once per request, the requester of an invalid key is no one
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Owner {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let owner = req
+            .local_cache_async(async {
+                let api_key = req
+                    .cookies()
+                    .get(API_KEY_COOKIE)
+                    .and_then(|cookie| ApiKey::from_str(cookie.value()).ok());
+                let owner = match req.rocket().state::<AppDatabase>() {
+                    Some(database) => active_key(api_key, database).await,
+                    None => None,
+                };
+                CookieOwner(Owner::new(owner.map(ApiKey::into_inner)))
+            })
+            .await;
+        Outcome::Success(owner.0.clone())
+    }
+}
+
 #[rocket::get("/clip/<shortcode>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_clip(
+    requester: Owner,
     shortcode: ShortCode,
     database: &State<AppDatabase>,
     hit_counter: &State<HitCounter>,
+    view: View,
+    preconditions: Preconditions,
+    actor: Actor,
     renderer: &State<Renderer<'_>>,
-) -> Result<status::Custom<RawHtml<String>>, PageError> {
-    fn render_with_status<T: ctx::PageContext + serde::Serialize + std::fmt::Debug>(
-        status: Status,
-        context: T,
-        renderer: &Renderer,
-    ) -> Result<status::Custom<RawHtml<String>>, PageError> {
-        Ok(status::Custom(status, RawHtml(renderer.render(context, &[]))))
-    }
-
-    match action::get_clip(shortcode.clone().into(), database.get_pool()).await {
+) -> Result<Either<Conditional<RawHtml<String>>, status::Custom<RawHtml<String>>>, PageError> {
+    let req = ask::GetClip {
+        requester,
+        ..ask::GetClip::from(shortcode.clone())
+    };
+    match action::get_clip(req, &actor, database.get_pool()).await {
         Ok(clip) => {
-            hit_counter.hit(shortcode.clone(), 1);
+            hit_counter.view(shortcode.clone(), view).await;
+            // the page also shows the hits, which are not part of the clip version
+            let validators = Validators::weak(&clip);
             let context = ctx::ViewClip::new(clip);
-            render_with_status(Status::Ok, context, renderer)
+            let page = RawHtml(renderer.render(context, &[]));
+            Ok(Either::Left(Conditional::new(
+                &preconditions,
+                validators,
+                page,
+            )))
         }
         Err(e) => match e {
             ServiceError::PermissionError(_) => {
                 let context = ctx::PasswordRequired::new(shortcode);
-                render_with_status(Status::Unauthorized, context, renderer)
+                let page = RawHtml(renderer.render(context, &[]));
+                Ok(Either::Right(status::Custom(Status::Unauthorized, page)))
             }
+            ServiceError::TakenDown(takedown) => Err(taken_down(takedown, renderer)),
             ServiceError::NotFound => Err(PageError::NotFound("Clip Not found".to_owned())),
             _ => Err(PageError::Internal("server error".to_owned())),
         },
@@ -102,11 +217,15 @@ pub async fn get_clip(
 }
 
 #[rocket::post("/clip/<shortcode>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn submit_clip_password(
     cookies: &CookieJar<'_>,
+    requester: Owner,
     form: Form<Contextual<'_, form::GetPasswordProtectedClip>>,
     shortcode: ShortCode,
     hit_counter: &State<HitCounter>,
+    view: View,
+    actor: Actor,
     database: &State<AppDatabase>,
     renderer: &State<Renderer<'_>>,
 ) -> Result<RawHtml<String>, PageError> {
@@ -115,11 +234,12 @@ pub async fn submit_clip_password(
             service::ask::GetClip {
                 shortcode: shortcode.clone(),
                 password: form.password.clone(),
+                requester,
             };
 
-        match action::get_clip(req, database.get_pool()).await {
+        match action::get_clip(req, &actor, database.get_pool()).await {
             Ok(clip) => {
-                hit_counter.hit(shortcode.clone(), 1);
+                hit_counter.view(shortcode.clone(), view).await;
                 let context = ctx::ViewClip::new(clip);
                 cookies.add(Cookie::new(
                     PASSWORD_COOKIE,
@@ -134,6 +254,7 @@ pub async fn submit_clip_password(
 
                     Ok(RawHtml(renderer.render(context, &[e.as_str()])))
                 }
+                ServiceError::TakenDown(takedown) => Err(taken_down(takedown, renderer)),
                 ServiceError::NotFound => Err(PageError::NotFound("clip not found".to_owned())),
                 _ => Err(PageError::Internal("server error".to_owned())),
             },
@@ -145,13 +266,19 @@ pub async fn submit_clip_password(
 }
 
 #[rocket::get("/clip/raw/<shortcode>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_raw_clip(
     cookies: &CookieJar<'_>,
+    requester: Owner,
     shortcode: ShortCode,
     hit_counter: &State<HitCounter>,
+    view: View,
+    preconditions: Preconditions,
+    range: RangeRequest,
+    accept_encoding: AcceptEncoding,
+    actor: Actor,
     database: &State<AppDatabase>,
-) -> Result<status::Custom<String>, Status> {
-    use crate::domain::clip::field::Password;
+) -> Result<Either<Conditional<Ranged>, status::Custom<String>>, Status> {
     let req = ask::GetClip {
         shortcode: shortcode.clone(),
         password: cookies
@@ -159,23 +286,793 @@ pub async fn get_raw_clip(
             .map(|cookie| cookie.value())
             .and_then(|raw_pass| Password::new(raw_pass.to_string()).ok())
             .unwrap_or_default(),
+        requester,
     };
 
-    match action::get_clip(req, database.get_pool()).await {
-        Ok(clip) => {
-            hit_counter.hit(shortcode.clone(), 1);
-            Ok(status::Custom(Status::Ok, clip.content.into_inner()))
+    match action::get_raw_clip(req, &actor, database.get_pool()).await {
+        Ok((clip, stored)) => {
+            let validators = Validators::strong(&clip);
+            let range = range
+                .0
+                .filter(|_| preconditions.is_range_current(&validators));
+            let (validators, content) =
+                raw_representation(clip, stored, validators, range, accept_encoding);
+            if !content.is_continuation() {
+                hit_counter.view(shortcode.clone(), view).await;
+            }
+            Ok(Either::Left(Conditional::new(
+                &preconditions,
+                validators,
+                content,
+            )))
         }
         Err(e) => match e {
-            ServiceError::PermissionError(msg) => Ok(status::Custom(Status::Unauthorized, msg)),
+            ServiceError::PermissionError(msg) => {
+                Ok(Either::Right(status::Custom(Status::Unauthorized, msg)))
+            }
+            ServiceError::TakenDown(takedown) if takedown.reason.is_legal() => {
+                Err(Status::UnavailableForLegalReasons)
+            }
+            ServiceError::TakenDown(_) => Err(Status::Gone),
             ServiceError::NotFound => Err(Status::NotFound),
             _ => Err(Status::InternalServerError),
         },
     }
 }
 
+// stored zstd content is sent as is to clients that accept it, gzip clients get large clips
+// compressed on the fly and everyone else, including range requests, the plain text
+fn raw_representation(
+    clip: Clip,
+    stored: StoredContent,
+    validators: Validators,
+    range: Option<ByteRange>,
+    accept_encoding: AcceptEncoding,
+) -> (Validators, Ranged) {
+    if range.is_none() {
+        if accept_encoding.zstd && stored.encoding == ContentEncoding::Zstd {
+            return (
+                validators.encoded("zstd"),
+                Ranged::encoded(stored.bytes, "zstd"),
+            );
+        }
+
+        let content = clip.content.as_str().as_bytes();
+        if accept_encoding.gzip && content.len() >= COMPRESSION_THRESHOLD {
+            match encoding::gzip(content) {
+                Ok(compressed) => {
+                    return (
+                        validators.encoded("gzip"),
+                        Ranged::encoded(compressed, "gzip"),
+                    )
+                }
+                Err(e) => tracing::warn!(error = %e, "failed to compress a raw clip"),
+            }
+        }
+    }
+    (validators, Ranged::new(clip.content.into_inner(), range))
+}
+
+// the home page prefilled with the content and title of the clip, the new clip links back to it
+#[rocket::get("/clip/<shortcode>/fork", rank = 2)]
+pub async fn fork_clip(
+    cookies: &CookieJar<'_>,
+    requester: Owner,
+    shortcode: ShortCode,
+    actor: Actor,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<Either<RawHtml<String>, status::Custom<RawHtml<String>>>, PageError> {
+    let req = ask::GetClip {
+        shortcode: shortcode.clone(),
+        password: cookies
+            .get(PASSWORD_COOKIE)
+            .and_then(|cookie| Password::new(cookie.value().to_string()).ok())
+            .unwrap_or_default(),
+        requester,
+    };
+
+    match action::get_clip(req, &actor, database.get_pool()).await {
+        Ok(clip) => {
+            let context = home_context(database).await.with_fork(clip);
+            Ok(Either::Left(RawHtml(renderer.render(context, &[]))))
+        }
+        Err(e) => match e {
+            ServiceError::PermissionError(_) => {
+                let context = ctx::PasswordRequired::new(shortcode);
+                let page = RawHtml(renderer.render(context, &[]));
+                Ok(Either::Right(status::Custom(Status::Unauthorized, page)))
+            }
+            ServiceError::TakenDown(takedown) => Err(taken_down(takedown, renderer)),
+            ServiceError::NotFound => Err(PageError::NotFound("Clip Not found".to_owned())),
+            _ => Err(PageError::Internal("server error".to_owned())),
+        },
+    }
+}
+
+async fn render_diff(
+    req: ask::GetDiff,
+    view: form::DiffView,
+    actor: &Actor,
+    database: &AppDatabase,
+    renderer: &Renderer<'_>,
+    errors: &[&str],
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    let (a, b, words) = (req.a.shortcode.clone(), req.b.shortcode.clone(), req.words);
+    match action::diff_clips(req, actor, database.get_pool()).await {
+        Ok(diff) => {
+            let rows = match view {
+                form::DiffView::SideBySide => diff.rows(),
+                form::DiffView::Unified => vec![],
+            };
+            let context = ctx::ViewDiff::new(diff, rows, view, words);
+            Ok(status::Custom(
+                Status::Ok,
+                RawHtml(renderer.render(context, &[])),
+            ))
+        }
+        Err(e) => match e {
+            ServiceError::PermissionError(_) => {
+                let context = ctx::DiffPasswordRequired::new(a, b, view, words);
+                Ok(status::Custom(
+                    Status::Unauthorized,
+                    RawHtml(renderer.render(context, errors)),
+                ))
+            }
+            ServiceError::TakenDown(takedown) => Err(taken_down(takedown, renderer)),
+            ServiceError::NotFound => Err(PageError::NotFound("Clip Not found".to_owned())),
+            _ => Err(PageError::Internal("server error".to_owned())),
+        },
+    }
+}
+
+// the content of clip `a` compared to clip `b`, side by side or in the unified format
+#[rocket::get("/diff/<a>/<b>?<view>&<words>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_diff(
+    cookies: &CookieJar<'_>,
+    requester: Owner,
+    a: ShortCode,
+    b: ShortCode,
+    view: Option<form::DiffView>,
+    words: Option<bool>,
+    actor: Actor,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    let password = cookies
+        .get(PASSWORD_COOKIE)
+        .and_then(|cookie| Password::new(cookie.value().to_string()).ok())
+        .unwrap_or_default();
+    let req = ask::GetDiff {
+        a: ask::GetClip {
+            shortcode: a,
+            password: password.clone(),
+            requester: requester.clone(),
+        },
+        b: ask::GetClip {
+            shortcode: b,
+            password,
+            requester,
+        },
+        words: words.unwrap_or_default(),
+    };
+
+    let view = view.unwrap_or_default();
+    render_diff(req, view, &actor, database, renderer, &[]).await
+}
+
+#[rocket::post("/diff/<a>/<b>?<view>&<words>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn submit_diff_passwords(
+    requester: Owner,
+    form: Form<Contextual<'_, form::GetPasswordProtectedDiff>>,
+    a: ShortCode,
+    b: ShortCode,
+    view: Option<form::DiffView>,
+    words: Option<bool>,
+    actor: Actor,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    let (password_a, password_b) = match &form.value {
+        Some(form) => (form.password_a.clone(), form.password_b.clone()),
+        None => Default::default(),
+    };
+    let req = ask::GetDiff {
+        a: ask::GetClip {
+            shortcode: a,
+            password: password_a,
+            requester: requester.clone(),
+        },
+        b: ask::GetClip {
+            shortcode: b,
+            password: password_b,
+            requester,
+        },
+        words: words.unwrap_or_default(),
+    };
+
+    let errors = ["Invalid password"];
+    let view = view.unwrap_or_default();
+    render_diff(req, view, &actor, database, renderer, &errors).await
+}
+
+// the most recent public clips with the tag
+#[rocket::get("/tag/<tag>")]
+pub async fn get_tag(
+    tag: &str,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<RawHtml<String>, PageError> {
+    let tag = Tags::tag(tag).map_err(|_| PageError::NotFound("Tag Not found".to_owned()))?;
+    let req = ask::ListClips {
+        tag: Some(tag.clone()),
+        limit: TAGGED_CLIPS,
+        offset: 0,
+    };
+
+    match action::list_public_clips(req, database.get_pool()).await {
+        Ok(clips) => {
+            let context = ctx::TaggedClips::new(tag, clips);
+            Ok(RawHtml(renderer.render(context, &[])))
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to list tagged clips");
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
+}
+
+#[rocket::get("/collection")]
+fn new_collection_page(renderer: &State<Renderer<'_>>) -> RawHtml<String> {
+    RawHtml(renderer.render(ctx::NewCollection::default(), &[]))
+}
+
+// collections created from the web have no owner, so they can only hold clips without one
+#[rocket::post("/collection", data = "<form>")]
+pub async fn new_collection(
+    form: Form<Contextual<'_, form::NewCollection>>,
+    actor: Actor,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<Redirect, (Status, RawHtml<String>)> {
+    let form = form.into_inner();
+    if let Some(value) = form.value {
+        let req = ask::NewCollection {
+            clips: value.shortcodes(),
+            title: value.title,
+            expires: value.expires,
+            password: value.password,
+            owner: Default::default(),
+        };
+
+        match action::new_collection(req, &actor, database.get_pool()).await {
+            Ok(collection) => Ok(Redirect::to(uri!(get_collection(
+                shortcode = collection.shortcode
+            )))),
+            Err(ServiceError::Clip(e)) => Err((
+                Status::BadRequest,
+                RawHtml(renderer.render(ctx::NewCollection::default(), &[e.to_string().as_str()])),
+            )),
+            Err(e) => {
+                tracing::error!(error = %e, "failed to create collection");
+                Err((
+                    Status::InternalServerError,
+                    RawHtml(renderer.render(
+                        ctx::NewCollection::default(),
+                        &["A server error occured, please try again"],
+                    )),
+                ))
+            }
+        }
+    } else {
+        let errors = form
+            .context
+            .errors()
+            .map(|err| {
+                use rocket::form::error::ErrorKind;
+                if let ErrorKind::Validation(msg) = &err.kind {
+                    msg.as_ref()
+                } else {
+                    tracing::warn!(error = %err, "unhandled form error");
+                    "A server error occured, please try again"
+                }
+            })
+            .collect::<Vec<_>>();
+        Err((
+            Status::BadRequest,
+            RawHtml(renderer.render_with_data(
+                ctx::NewCollection::default(),
+                ("collection", &form.context),
+                &errors,
+            )),
+        ))
+    }
+}
+
+#[rocket::get("/collection/<shortcode>")]
+pub async fn get_collection(
+    cookies: &CookieJar<'_>,
+    requester: Owner,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    let req = ask::GetCollection {
+        shortcode: shortcode.clone(),
+        password: cookies
+            .get(PASSWORD_COOKIE)
+            .and_then(|cookie| Password::new(cookie.value().to_string()).ok())
+            .unwrap_or_default(),
+        requester,
+    };
+
+    match action::get_collection(req, database.get_pool()).await {
+        Ok(collection) => {
+            let context = ctx::ViewCollection::new(collection);
+            Ok(status::Custom(
+                Status::Ok,
+                RawHtml(renderer.render(context, &[])),
+            ))
+        }
+        Err(e) => match e {
+            ServiceError::PermissionError(_) => {
+                let context = ctx::CollectionPasswordRequired::new(shortcode);
+                Ok(status::Custom(
+                    Status::Unauthorized,
+                    RawHtml(renderer.render(context, &[])),
+                ))
+            }
+            ServiceError::NotFound => Err(PageError::NotFound("Collection Not found".to_owned())),
+            _ => Err(PageError::Internal("server error".to_owned())),
+        },
+    }
+}
+
+#[rocket::post("/collection/<shortcode>", data = "<form>")]
+pub async fn submit_collection_password(
+    cookies: &CookieJar<'_>,
+    requester: Owner,
+    form: Form<Contextual<'_, form::GetPasswordProtectedClip>>,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<RawHtml<String>, PageError> {
+    if let Some(form) = &form.value {
+        let req = ask::GetCollection {
+            shortcode: shortcode.clone(),
+            password: form.password.clone(),
+            requester,
+        };
+
+        match action::get_collection(req, database.get_pool()).await {
+            Ok(collection) => {
+                let context = ctx::ViewCollection::new(collection);
+                cookies.add(Cookie::new(
+                    PASSWORD_COOKIE,
+                    form.password.clone().into_inner().unwrap_or_default(),
+                ));
+
+                Ok(RawHtml(renderer.render(context, &[])))
+            }
+            Err(e) => match e {
+                ServiceError::PermissionError(e) => {
+                    let context = ctx::CollectionPasswordRequired::new(shortcode);
+
+                    Ok(RawHtml(renderer.render(context, &[e.as_str()])))
+                }
+                ServiceError::NotFound => {
+                    Err(PageError::NotFound("collection not found".to_owned()))
+                }
+                _ => Err(PageError::Internal("server error".to_owned())),
+            },
+        }
+    } else {
+        let context = ctx::CollectionPasswordRequired::new(shortcode);
+        Ok(RawHtml(renderer.render(
+            context,
+            &["A password is required to view the collection"],
+        )))
+    }
+}
+
+// renders the clip stats when `owner` owns the clip, otherwise asks for the owner's key
+async fn render_clip_stats(
+    shortcode: ShortCode,
+    owner: Owner,
+    database: &AppDatabase,
+    renderer: &Renderer<'_>,
+    errors: &[&str],
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    if !owner.has_owner() {
+        let context = ctx::ApiKeyRequired::new(shortcode);
+        return Ok(status::Custom(
+            Status::Unauthorized,
+            RawHtml(renderer.render(context, errors)),
+        ));
+    }
+
+    let req = ask::GetClipStats {
+        shortcode: shortcode.clone(),
+        owner,
+    };
+
+    match action::get_clip_stats(req, database.get_pool()).await {
+        Ok(stats) => {
+            let context = ctx::ViewClipStats::new(stats);
+            Ok(status::Custom(
+                Status::Ok,
+                RawHtml(renderer.render(context, &[])),
+            ))
+        }
+        Err(e) => match e {
+            ServiceError::PermissionError(msg) => {
+                let context = ctx::ApiKeyRequired::new(shortcode);
+                Ok(status::Custom(
+                    Status::Unauthorized,
+                    RawHtml(renderer.render(context, &[msg.as_str()])),
+                ))
+            }
+            ServiceError::NotFound => Err(PageError::NotFound("Clip Not found".to_owned())),
+            _ => Err(PageError::Internal("server error".to_owned())),
+        },
+    }
+}
+
+// rank 2 avoids colliding with `/clip/raw/<shortcode>`, shortcodes are never `raw`
+#[rocket::get("/clip/<shortcode>/stats", rank = 2)]
+pub async fn get_clip_stats(
+    cookies: &CookieJar<'_>,
+    requester: Owner,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    // the key of the cookie was revoked or disabled since it was entered
+    let errors: &[&str] = match cookies.get(API_KEY_COOKIE) {
+        Some(_) if !requester.has_owner() => &[INVALID_STATS_KEY],
+        _ => &[],
+    };
+    render_clip_stats(shortcode, requester, database, renderer, errors).await
+}
+
+#[rocket::post("/clip/<shortcode>/stats", data = "<form>", rank = 2)]
+pub async fn submit_clip_owner(
+    cookies: &CookieJar<'_>,
+    form: Form<Contextual<'_, form::ClipOwner>>,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    let api_key = form
+        .value
+        .as_ref()
+        .and_then(|form| ApiKey::from_str(form.api_key.trim()).ok());
+
+    match active_key(api_key, database).await {
+        Some(api_key) => {
+            cookies.add(Cookie::new(API_KEY_COOKIE, api_key.to_base64()));
+            let owner = Owner::new(api_key.into_inner());
+            render_clip_stats(shortcode, owner, database, renderer, &[]).await
+        }
+        None => {
+            let errors = [INVALID_STATS_KEY];
+            render_clip_stats(shortcode, Owner::default(), database, renderer, &errors).await
+        }
+    }
+}
+
+// the credentials for editing a clip are the password cookie and the API key cookie
+fn edit_request(cookies: &CookieJar<'_>, requester: Owner, shortcode: ShortCode) -> ask::EditClip {
+    ask::EditClip {
+        shortcode,
+        password: Password::new(
+            cookies
+                .get(PASSWORD_COOKIE)
+                .map(|cookie| cookie.value().to_owned()),
+        )
+        .unwrap_or_default(),
+        requester,
+    }
+}
+
+// asks for the password of the clip or the owner's key when the credentials do not allow editing
+fn render_edit_error(
+    e: ServiceError,
+    shortcode: ShortCode,
+    renderer: &Renderer<'_>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    match e {
+        ServiceError::PermissionError(msg) => {
+            let context = ctx::EditCredentialRequired::new(shortcode);
+            Ok(status::Custom(
+                Status::Unauthorized,
+                RawHtml(renderer.render(context, &[msg.as_str()])),
+            ))
+        }
+        ServiceError::TakenDown(takedown) => Err(taken_down(takedown, renderer)),
+        ServiceError::NotFound => Err(PageError::NotFound("Clip Not found".to_owned())),
+        _ => Err(PageError::Internal("server error".to_owned())),
+    }
+}
+
+#[rocket::get("/clip/<shortcode>/edit", rank = 2)]
+pub async fn edit_clip(
+    cookies: &CookieJar<'_>,
+    requester: Owner,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    let req = edit_request(cookies, requester, shortcode.clone());
+    match action::get_clip_for_edit(req, database.get_pool()).await {
+        Ok(clip) => {
+            let context = ctx::EditClip::new(clip);
+            Ok(status::Custom(
+                Status::Ok,
+                RawHtml(renderer.render(context, &[])),
+            ))
+        }
+        Err(e) => render_edit_error(e, shortcode, renderer),
+    }
+}
+
+#[rocket::post("/clip/<shortcode>/edit/unlock", data = "<form>", rank = 2)]
+pub async fn submit_edit_credential(
+    cookies: &CookieJar<'_>,
+    form: Form<Contextual<'_, form::EditCredential>>,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<Either<Redirect, status::Custom<RawHtml<String>>>, PageError> {
+    let (password, api_key) = match &form.value {
+        Some(form) => (
+            form.password.clone(),
+            ApiKey::from_str(form.api_key.trim()).ok(),
+        ),
+        None => (Password::default(), None),
+    };
+    // only an active key is the owner and is kept in the cookie
+    let api_key = active_key(api_key, database).await;
+    let req = ask::EditClip {
+        shortcode: shortcode.clone(),
+        password: password.clone(),
+        requester: Owner::new(api_key.clone().map(ApiKey::into_inner)),
+    };
+
+    match action::get_clip_for_edit(req, database.get_pool()).await {
+        Ok(_) => {
+            if password.has_password() {
+                cookies.add(Cookie::new(
+                    PASSWORD_COOKIE,
+                    password.into_inner().unwrap_or_default(),
+                ));
+            }
+            if let Some(api_key) = api_key {
+                cookies.add(Cookie::new(API_KEY_COOKIE, api_key.to_base64()));
+            }
+            Ok(Either::Left(Redirect::to(uri!(edit_clip(
+                shortcode = shortcode
+            )))))
+        }
+        Err(e) => render_edit_error(e, shortcode, renderer).map(Either::Right),
+    }
+}
+
+#[rocket::post("/clip/<shortcode>/edit", data = "<form>", rank = 2)]
+#[allow(clippy::too_many_arguments)]
+pub async fn update_clip(
+    cookies: &CookieJar<'_>,
+    requester: Owner,
+    form: Form<Contextual<'_, form::EditClip>>,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    secrets: &State<SecretScanner>,
+    quota: &State<StorageQuota>,
+    actor: Actor,
+    renderer: &State<Renderer<'_>>,
+) -> Result<Either<Redirect, status::Custom<RawHtml<String>>>, PageError> {
+    let edit = edit_request(cookies, requester, shortcode.clone());
+    let clip = match action::get_clip_for_edit(edit.clone(), database.get_pool()).await {
+        Ok(clip) => clip,
+        Err(e) => return render_edit_error(e, shortcode, renderer).map(Either::Right),
+    };
+
+    let form = form.into_inner();
+    // the page is shown again with the entered values and the errors
+    let entered = handlebars::to_json(&form.context);
+    let render_page = |context: ctx::EditClip, status: Status, errors: &[&str]| {
+        Either::Right(status::Custom(
+            status,
+            RawHtml(renderer.render_with_data(context, ("form", &entered), errors)),
+        ))
+    };
+    let render = |clip: Clip, status: Status, errors: &[&str]| {
+        render_page(ctx::EditClip::new(clip), status, errors)
+    };
+
+    let value = match &form.value {
+        Some(value) => value,
+        None => {
+            let errors = form
+                .context
+                .errors()
+                .map(|err| {
+                    use rocket::form::error::ErrorKind;
+                    if let ErrorKind::Validation(msg) = &err.kind {
+                        msg.as_ref()
+                    } else {
+                        tracing::warn!(error = %err, "unhandled form error");
+                        "A server error occured, please try again"
+                    }
+                })
+                .collect::<Vec<_>>();
+            return Ok(render(clip, Status::BadRequest, &errors));
+        }
+    };
+
+    let password = if value.remove_password {
+        Password::default()
+    } else if value.password.has_password() {
+        value.password.clone()
+    } else {
+        clip.password.clone()
+    };
+    // the form only holds the date, an unchanged date keeps the time of the expiration
+    let expires = if value.expires.date() == clip.expires.date() {
+        clip.expires.clone()
+    } else {
+        value.expires.clone()
+    };
+    let req = ask::UpdateClip {
+        content: value.content.clone(),
+        title: value.title.clone(),
+        expires,
+        password: password.clone(),
+        shortcode: shortcode.clone(),
+        visibility: Some(value.visibility),
+        expected_versions: Some(vec![value.version.clone()]),
+        requester: edit.requester.clone(),
+        allow_secrets: value.allow_secrets,
+    };
+
+    match action::update_clip(req, &actor, secrets, **quota, database.get_pool()).await {
+        Ok(updated) => {
+            if password.has_password() {
+                cookies.add(Cookie::new(
+                    PASSWORD_COOKIE,
+                    password.into_inner().unwrap_or_default(),
+                ));
+            }
+            Ok(Either::Left(Redirect::to(uri!(get_clip(
+                shortcode = updated.shortcode
+            )))))
+        }
+        Err(ServiceError::Clip(ClipError::Secrets(findings)))
+            if secrets.policy() == SecretPolicy::Warn =>
+        {
+            let context = ctx::EditClip::new(clip).with_secrets(findings);
+            Ok(render_page(context, Status::BadRequest, &[]))
+        }
+        Err(ServiceError::Clip(e)) => {
+            Ok(render(clip, Status::BadRequest, &[e.to_string().as_str()]))
+        }
+        Err(ServiceError::PermissionError(msg)) => {
+            Ok(render(clip, Status::Forbidden, &[msg.as_str()]))
+        }
+        Err(e @ ServiceError::QuotaExceeded(_)) => Ok(render(
+            clip,
+            Status::InsufficientStorage,
+            &[e.to_string().as_str()],
+        )),
+        // the page is shown with the current version, submitting again overwrites the changes
+        Err(ServiceError::PreconditionFailed) => {
+            match action::get_clip_for_edit(edit, database.get_pool()).await {
+                Ok(current) => Ok(render(
+                    current,
+                    Status::Conflict,
+                    &["The clip was changed since you started editing it, submit again to overwrite the changes"],
+                )),
+                Err(e) => render_edit_error(e, shortcode, renderer).map(Either::Right),
+            }
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to update clip");
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
+}
+
+#[rocket::get("/clip/<shortcode>/report", rank = 2)]
+pub fn report_clip_page(shortcode: ShortCode, renderer: &State<Renderer<'_>>) -> RawHtml<String> {
+    RawHtml(renderer.render(ctx::ReportClip::new(shortcode, false), &[]))
+}
+
+#[rocket::post("/clip/<shortcode>/report", data = "<form>", rank = 2)]
+pub async fn report_clip(
+    requester: Owner,
+    form: Form<Contextual<'_, form::NewReport>>,
+    shortcode: ShortCode,
+    database: &State<AppDatabase>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    let form = form.into_inner();
+    let entered = handlebars::to_json(&form.context);
+    let render = |status: Status, filed: bool, errors: &[&str]| {
+        status::Custom(
+            status,
+            RawHtml(renderer.render_with_data(
+                ctx::ReportClip::new(shortcode.clone(), filed),
+                ("form", &entered),
+                errors,
+            )),
+        )
+    };
+
+    let Some(value) = form.value else {
+        let errors = form
+            .context
+            .errors()
+            .map(|err| {
+                use rocket::form::error::ErrorKind;
+                if let ErrorKind::Validation(msg) = &err.kind {
+                    msg.as_ref()
+                } else {
+                    tracing::warn!(error = %err, "unhandled form error");
+                    "A server error occured, please try again"
+                }
+            })
+            .collect::<Vec<_>>();
+        return Ok(render(Status::BadRequest, false, &errors));
+    };
+
+    let req = ask::NewReport {
+        shortcode: shortcode.clone(),
+        reason: value.reason,
+        contact: value.contact,
+        requester,
+    };
+    match action::file_report(req, database.get_pool()).await {
+        Ok(report) => {
+            tracing::info!(report, shortcode = %shortcode.as_str(), "clip reported");
+            Ok(render(Status::Created, true, &[]))
+        }
+        Err(ServiceError::Clip(e)) => {
+            Ok(render(Status::BadRequest, false, &[e.to_string().as_str()]))
+        }
+        Err(ServiceError::TakenDown(takedown)) => Err(taken_down(takedown, renderer)),
+        Err(ServiceError::NotFound) => Err(PageError::NotFound("Clip Not found".to_owned())),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to file report");
+            Err(PageError::Internal("server error".to_owned()))
+        }
+    }
+}
+
 pub fn routes() -> Vec<rocket::Route> {
-    rocket::routes![home, get_clip, new_clip, submit_clip_password, get_raw_clip]
+    rocket::routes![
+        home,
+        get_clip,
+        new_clip,
+        submit_clip_password,
+        get_raw_clip,
+        fork_clip,
+        get_diff,
+        submit_diff_passwords,
+        get_tag,
+        new_collection_page,
+        new_collection,
+        get_collection,
+        submit_collection_password,
+        get_clip_stats,
+        submit_clip_owner,
+        edit_clip,
+        submit_edit_credential,
+        update_clip,
+        report_clip_page,
+        report_clip
+    ]
 }
 
 // catchers in rocket catch any unhandled errors
@@ -185,13 +1082,13 @@ pub mod catcher {
 
     #[catch(default)]
     fn default(req: &Request) -> &'static str {
-        eprintln!("General error: {:?}", req);
+        tracing::error!(uri = %req.uri(), "unhandled error");
         "something went wrong"
     }
 
     #[catch(500)]
     fn internal_error(req: &Request) -> &'static str {
-        eprintln!("Internal error: {:?}", req);
+        tracing::error!(uri = %req.uri(), "internal error");
         "internal server error"
     }
 
@@ -204,3 +1101,238 @@ pub mod catcher {
         catchers![not_found, default, internal_error]
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::{ContentType, Cookie, Header, RawStr, Status};
+    use rocket::local::blocking::Client;
+
+    use crate::test::new_async_runtime;
+    use crate::web::admin::AdminToken;
+    use crate::web::api::test::new_api_key;
+    use crate::web::api::v1::ClipResponse;
+    use crate::web::test::{new_rocket_client, new_rocket_config};
+    use crate::web::API_KEY_COOKIE;
+
+    // the hidden version of the clip on the edit page
+    fn edit_version(client: &Client, shortcode: &str) -> String {
+        let html = client
+            .get(format!("/clip/{}/edit", shortcode))
+            .dispatch()
+            .into_string()
+            .unwrap();
+        let start = html.find(r#"name="version" value=""#).unwrap() + 22;
+        html[start..start + html[start..].find('"').unwrap()].to_owned()
+    }
+
+    #[test]
+    fn secrets_are_stashed_after_a_confirmation() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+        let body = "content=export+AWS_ACCESS_KEY_ID%3DAKIAIOSFODNN7EXAMPLE&title=env\
+                    &expires=&password=&visibility=public";
+
+        let response = client
+            .post("/")
+            .header(ContentType::Form)
+            .body(body)
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let html = response.into_string().unwrap();
+        assert!(html.contains("aws_access_key on line 1"));
+        assert!(html.contains(r#"name="allow_secrets" value="true""#));
+        // the form is filled with what was entered
+        assert!(html.contains(r#"value="env""#));
+        assert!(html.contains(r#"value="public" selected"#));
+
+        let response = client
+            .post("/")
+            .header(ContentType::Form)
+            .body(format!("{}&allow_secrets=true", body))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+    }
+
+    #[test]
+    fn clips_are_edited_with_their_password() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+
+        let response = client
+            .post("/")
+            .header(ContentType::Form)
+            .body("content=first&title=notes&expires=&password=hunter2&visibility=unlisted")
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        let location = response.headers().get_one("Location").unwrap();
+        let shortcode = location.trim_start_matches("/clip/").to_owned();
+        let edit = format!("/clip/{}/edit", shortcode);
+
+        let response = client.get(edit.as_str()).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .post(format!("{}/unlock", edit))
+            .header(ContentType::Form)
+            .body("password=wrong&api_key=")
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .post(format!("{}/unlock", edit))
+            .header(ContentType::Form)
+            .body("password=hunter2&api_key=")
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+
+        let response = client.get(edit.as_str()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let html = response.into_string().unwrap();
+        assert!(html.contains(">first</textarea>"));
+        assert!(html.contains(r#"value="notes""#));
+
+        let version = edit_version(&client, &shortcode);
+        let response = client
+            .post(edit.as_str())
+            .header(ContentType::Form)
+            .body(format!(
+                "content=&title=kept&expires=&password=&visibility=unlisted&version={}",
+                version
+            ))
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let html = response.into_string().unwrap();
+        assert!(html.contains("Error Editing Clip"));
+        assert!(html.contains(r#"value="kept""#));
+
+        let body = format!(
+            "content=second&title=notes&expires=&password=&visibility=unlisted&version={}",
+            version
+        );
+        let response = client
+            .post(edit.as_str())
+            .header(ContentType::Form)
+            .body(body.as_str())
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+        // the raw clip is unlocked by the password cookie
+        let raw = client.get(format!("/clip/raw/{}", shortcode)).dispatch();
+        assert_eq!(raw.into_string().unwrap(), "second");
+
+        // the edit was based on the first version of the clip
+        let response = client
+            .post(edit.as_str())
+            .header(ContentType::Form)
+            .body(body.as_str())
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+    }
+
+    #[test]
+    fn stats_are_refused_to_revoked_keys() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.admin_token = AdminToken(Some("secret".to_owned()));
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build a rocket instance");
+        let key = new_api_key(&client);
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"content": "mine"}"#)
+            .dispatch();
+        let clip: ClipResponse = response.into_json().unwrap();
+        let stats = format!("/clip/{}/stats", clip.shortcode);
+        let cookie = Cookie::new(API_KEY_COOKIE, key.value().to_owned());
+
+        let response = client.get(stats.as_str()).cookie(cookie.clone()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .post("/admin/keys/1/disable")
+            .header(Header::new("Authorization", "Bearer secret"))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+
+        let response = client.get(stats.as_str()).cookie(cookie).dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert!(response
+            .into_string()
+            .unwrap()
+            .contains("A valid API key is required to view the clip stats"));
+    }
+
+    #[test]
+    fn private_clips_are_hidden_from_disabled_keys() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.admin_token = AdminToken(Some("secret".to_owned()));
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build a rocket instance");
+        let key = new_api_key(&client);
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"content": "mine", "visibility": "private"}"#)
+            .dispatch();
+        let clip: ClipResponse = response.into_json().unwrap();
+        let page = format!("/clip/{}", clip.shortcode);
+        let cookie = Cookie::new(API_KEY_COOKIE, key.value().to_owned());
+
+        let response = client.get(page.as_str()).cookie(cookie.clone()).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .post("/admin/keys/1/disable")
+            .header(Header::new("Authorization", "Bearer secret"))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+
+        let response = client.get(page.as_str()).cookie(cookie.clone()).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client
+            .get(format!("{}/edit", page))
+            .cookie(cookie)
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn disabled_keys_do_not_unlock_edits() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.admin_token = AdminToken(Some("secret".to_owned()));
+        let client = Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build a rocket instance");
+        let key = new_api_key(&client);
+
+        let response = client
+            .post("/api/v1/clips")
+            .header(key.clone())
+            .header(ContentType::JSON)
+            .body(r#"{"content": "mine"}"#)
+            .dispatch();
+        let clip: ClipResponse = response.into_json().unwrap();
+        let unlock = format!("/clip/{}/edit/unlock", clip.shortcode);
+        let body = format!(
+            "password=&api_key={}",
+            RawStr::new(key.value()).percent_encode()
+        );
+
+        let response = client
+            .post("/admin/keys/1/disable")
+            .header(Header::new("Authorization", "Bearer secret"))
+            .dispatch();
+        assert_eq!(response.status(), Status::SeeOther);
+
+        let response = client
+            .post(unlock.as_str())
+            .header(ContentType::Form)
+            .body(body.as_str())
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert!(client.cookies().get(API_KEY_COOKIE).is_none());
+    }
+}
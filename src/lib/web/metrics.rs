@@ -0,0 +1,367 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::{ContentType, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::{Data, Response, State};
+
+use super::hitcounter::HitCounter;
+use crate::data::AppDatabase;
+use crate::service::metrics::METRICS;
+
+// upper bounds of the request latency histogram, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct RequestLabels {
+    method: String,
+    route: String,
+    status: u16,
+}
+
+#[derive(Debug, Default)]
+struct RequestSeries {
+    count: u64,
+    sum: f64,
+    // cumulative counts, one per latency bucket
+    buckets: [u64; LATENCY_BUCKETS.len()],
+}
+
+// RequestMetrics holds the request counts and latencies recorded by the `RequestTimer` fairing
+#[derive(Debug, Default)]
+pub struct RequestMetrics(Mutex<BTreeMap<RequestLabels, RequestSeries>>);
+
+impl RequestMetrics {
+    fn record(&self, labels: RequestLabels, seconds: f64) {
+        let mut series = self.0.lock().expect("request metrics lock poisoned");
+        let series = series.entry(labels).or_default();
+
+        series.count += 1;
+        series.sum += seconds;
+        for (bucket, bound) in series.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+}
+
+// MetricsToken protects the `/metrics` endpoint when set, scrapers have to send it as a
+// bearer token
+#[derive(Debug, Clone, Default)]
+pub struct MetricsToken(pub Option<String>);
+
+// the time a request was received, cached on the request by the fairing
+struct RequestStart(Instant);
+
+// RequestTimer is a fairing that records the count and latency of every request
+pub struct RequestTimer;
+
+#[rocket::async_trait]
+impl Fairing for RequestTimer {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request Timer",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        req.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let metrics = match req.rocket().state::<RequestMetrics>() {
+            Some(metrics) => metrics,
+            None => return,
+        };
+
+        let started = req.local_cache(|| RequestStart(Instant::now()));
+        // unmatched requests are grouped together so random urls don't create new series
+        let route = req
+            .route()
+            .map(|route| route.uri.as_str().to_owned())
+            .unwrap_or_else(|| "unmatched".to_owned());
+
+        let labels = RequestLabels {
+            method: req.method().as_str().to_owned(),
+            route,
+            status: res.status().code,
+        };
+        metrics.record(labels, started.0.elapsed().as_secs_f64());
+    }
+}
+
+// Scraper is a request guard that checks the bearer token of metric scrapers
+pub struct Scraper;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Scraper {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let expected = match req.rocket().state::<MetricsToken>() {
+            Some(MetricsToken(Some(token))) => token,
+            _ => return Outcome::Success(Scraper),
+        };
+
+        let provided = req
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .unwrap_or_default();
+
+        if constant_time_eq(provided.as_bytes(), expected.as_bytes()) {
+            Outcome::Success(Scraper)
+        } else {
+            Outcome::Error((Status::Unauthorized, ()))
+        }
+    }
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+// helpers to write the prometheus text exposition format
+fn write_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn write_metric<V: std::fmt::Display>(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    value: V,
+) {
+    write_header(out, name, kind, help);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+fn render(requests: &RequestMetrics, hit_counter: &HitCounter, database: &AppDatabase) -> String {
+    let mut out = String::new();
+
+    write_header(
+        &mut out,
+        "clipstash_http_requests_total",
+        "counter",
+        "Number of handled HTTP requests.",
+    );
+    let series = requests.0.lock().expect("request metrics lock poisoned");
+    for (labels, series) in series.iter() {
+        let _ = writeln!(
+            out,
+            "clipstash_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+            labels.method,
+            escape_label(&labels.route),
+            labels.status,
+            series.count
+        );
+    }
+
+    write_header(
+        &mut out,
+        "clipstash_http_request_duration_seconds",
+        "histogram",
+        "HTTP request latency.",
+    );
+    for (labels, series) in series.iter() {
+        let labels = format!(
+            "method=\"{}\",route=\"{}\",status=\"{}\"",
+            labels.method,
+            escape_label(&labels.route),
+            labels.status
+        );
+        for (count, bound) in series.buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "clipstash_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                labels, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "clipstash_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+            labels, series.count
+        );
+        let _ = writeln!(
+            out,
+            "clipstash_http_request_duration_seconds_sum{{{}}} {}",
+            labels, series.sum
+        );
+        let _ = writeln!(
+            out,
+            "clipstash_http_request_duration_seconds_count{{{}}} {}",
+            labels, series.count
+        );
+    }
+    drop(series);
+
+    let service = METRICS.stats();
+    write_metric(
+        &mut out,
+        "clipstash_clips_created_total",
+        "counter",
+        "Number of created clips.",
+        service.clips_created,
+    );
+    write_metric(
+        &mut out,
+        "clipstash_clips_expired_total",
+        "counter",
+        "Number of expired clips deleted by the maintenance task.",
+        service.clips_expired,
+    );
+    write_metric(
+        &mut out,
+        "clipstash_api_key_failures_total",
+        "counter",
+        "Number of requests with a missing, malformed or unknown API key.",
+        service.api_key_failures,
+    );
+
+    let hits = hit_counter.stats();
+    write_metric(
+        &mut out,
+        "clipstash_hit_counter_queue_depth",
+        "gauge",
+        "Number of hit messages waiting to be processed.",
+        hits.queue_depth,
+    );
+    write_metric(
+        &mut out,
+        "clipstash_hit_counter_queue_capacity",
+        "gauge",
+        "Capacity of the hit counter channel.",
+        hits.queue_capacity,
+    );
+    write_metric(
+        &mut out,
+        "clipstash_hit_counter_hits_received_total",
+        "counter",
+        "Number of hits sent to the hit counter.",
+        hits.hits_received,
+    );
+    write_metric(
+        &mut out,
+        "clipstash_hit_counter_hits_flushed_total",
+        "counter",
+        "Number of hits written to the database.",
+        hits.hits_flushed,
+    );
+    write_metric(
+        &mut out,
+        "clipstash_hit_counter_flushes_total",
+        "counter",
+        "Number of successful hit counter flushes.",
+        hits.flushes,
+    );
+    write_metric(
+        &mut out,
+        "clipstash_hit_counter_flush_failures_total",
+        "counter",
+        "Number of failed hit counter flushes.",
+        hits.flush_failures,
+    );
+    write_metric(
+        &mut out,
+        "clipstash_hit_counter_backpressure_waits_total",
+        "counter",
+        "Number of hits that had to wait for room in the hit counter channel.",
+        hits.backpressure_waits,
+    );
+    write_metric(
+        &mut out,
+        "clipstash_hit_counter_last_flush_seconds",
+        "gauge",
+        "Duration of the last hit counter flush.",
+        hits.last_flush_micros as f64 / 1_000_000.0,
+    );
+
+    let pool = database.get_pool();
+    write_metric(
+        &mut out,
+        "clipstash_db_pool_connections",
+        "gauge",
+        "Number of open database connections.",
+        pool.size(),
+    );
+    write_metric(
+        &mut out,
+        "clipstash_db_pool_idle_connections",
+        "gauge",
+        "Number of idle database connections.",
+        pool.num_idle(),
+    );
+    write_metric(
+        &mut out,
+        "clipstash_db_pool_max_connections",
+        "gauge",
+        "Maximum number of database connections.",
+        pool.options().get_max_connections(),
+    );
+
+    out
+}
+
+#[rocket::get("/metrics")]
+pub fn metrics(
+    _scraper: Scraper,
+    requests: &State<RequestMetrics>,
+    hit_counter: &State<HitCounter>,
+    database: &State<AppDatabase>,
+) -> (ContentType, String) {
+    let content_type = ContentType::new("text", "plain").with_params(("version", "0.0.4"));
+    (content_type, render(requests, hit_counter, database))
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![metrics]
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::{Header, Status};
+
+    use super::MetricsToken;
+    use crate::test::new_async_runtime;
+    use crate::web::test::new_rocket_config;
+
+    #[test]
+    fn metrics_require_the_configured_token() {
+        let rt = new_async_runtime();
+        let mut config = new_rocket_config(rt.handle());
+        config.metrics_token = MetricsToken(Some("secret".to_owned()));
+        let client = rocket::local::blocking::Client::tracked(crate::build_a_rocket(config))
+            .expect("failed to build a rocket instance");
+
+        let response = client.get("/metrics").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let _ = client.get("/").dispatch();
+        let response = client
+            .get("/metrics")
+            .header(Header::new("Authorization", "Bearer secret"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let body = response.into_string().unwrap();
+        assert!(body
+            .contains(r#"clipstash_http_requests_total{method="GET",route="/",status="200"} 1"#));
+        assert!(body.contains("clipstash_hit_counter_queue_depth 0"));
+    }
+}
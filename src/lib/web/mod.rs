@@ -1,11 +1,26 @@
+pub mod admin;
 pub mod api;
+pub mod audit;
+pub mod conditional;
 pub mod ctx;
+pub mod encoding;
+pub mod feed;
 pub mod form;
+pub mod health;
 pub mod hitcounter;
 pub mod http;
+pub mod metrics;
+pub mod openapi;
+pub mod problem;
+pub mod range;
 pub mod renderer;
+pub mod trace;
+
+use rocket::response::content::RawHtml;
 
 pub const PASSWORD_COOKIE: &str = "password";
+pub const API_KEY_COOKIE: &str = "api_key";
+pub const ADMIN_COOKIE: &str = "admin";
 
 #[derive(rocket::Responder)]
 pub enum PageError {
@@ -17,6 +32,10 @@ pub enum PageError {
     NotFound(String),
     #[response(status = 500)]
     Internal(String),
+    #[response(status = 410)]
+    Gone(RawHtml<String>),
+    #[response(status = 451)]
+    UnavailableForLegalReasons(RawHtml<String>),
 }
 
 impl From<handlebars::RenderError> for PageError {
@@ -33,34 +52,39 @@ impl From<serde_json::Error> for PageError {
 
 #[cfg(test)]
 pub mod test {
-    use crate::test::new_async_runtime;
     use crate::RocketConfig;
+    use tokio::runtime::Handle;
 
     // local client that can be used for tests
     use rocket::local::blocking::Client;
 
-    pub fn new_rocket_config() -> RocketConfig {
+    // the background workers are spawned on `handle`, the runtime must outlive the client
+    pub fn new_rocket_config(handle: &Handle) -> RocketConfig {
         use crate::web::{hitcounter::HitCounter, renderer::Renderer};
 
-        let rt = new_async_runtime();
         let renderer = Renderer::new("templates/".into());
-        let database = crate::data::test::new_db(rt.handle());
+        let database = crate::data::test::new_db(handle);
         let maintenance = crate::domain::maintenance::Maintenance::spawn(
             database.get_pool().clone(),
-            rt.handle().clone(),
+            handle.clone(),
         );
-        let hit_counter = HitCounter::new(database.get_pool().clone(), rt.handle().clone());
+        let hit_counter = HitCounter::new(database.get_pool().clone(), handle.clone());
 
         RocketConfig {
             renderer,
             database,
             hit_counter,
             maintenance,
+            metrics_token: Default::default(),
+            admin_token: Default::default(),
+            secrets: Default::default(),
+            quota: Default::default(),
+            base_url: Default::default(),
         }
     }
 
-    pub fn new_rocket_client() -> Client {
-        let config = new_rocket_config();
+    pub fn new_rocket_client(handle: &Handle) -> Client {
+        let config = new_rocket_config(handle);
         Client::tracked(crate::build_a_rocket(config)).expect("failed to build a rocket instance")
     }
 }
@@ -0,0 +1,225 @@
+use rocket::serde::json::Json;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::openapi::Deprecated;
+use utoipa::{Modify, OpenApi};
+
+use super::api::{self, v1, API_KEY_HEADER, LEGACY_API_BASE};
+use super::problem::{FieldError, Problem};
+use crate::domain::clip::field;
+use crate::domain::diff;
+use crate::domain::stats;
+use crate::service::ask;
+
+// ApiDoc is the OpenAPI document of the JSON API, built from the `#[utoipa::path]` attributes of
+// the routes in `web::api` and the schemas of the types they exchange
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "clipstash",
+        description = "Share text clips through short links."
+    ),
+    paths(
+        v1::new_api_key,
+        v1::get_usage,
+        v1::get_clip,
+        v1::list_clips,
+        v1::get_clip_stats,
+        v1::new_clip,
+        v1::new_clips,
+        v1::new_raw_clip,
+        v1::fork_clip,
+        v1::diff::get_diff,
+        v1::get_clips,
+        v1::update_clip,
+        v1::collection::new_collection,
+        v1::collection::get_collection,
+        v1::collection::update_collection,
+        v1::collection::delete_collection,
+        api::new_api_key,
+        api::get_clip,
+        api::list_clips,
+        api::get_clip_stats,
+        api::new_clip,
+        api::fork_clip,
+        api::update_clip,
+    ),
+    components(schemas(
+        v1::ClipResponse,
+        v1::ClipListResponse,
+        v1::NewClipRequest,
+        v1::UpdateClipRequest,
+        v1::NewClipsRequest,
+        v1::GetClipsRequest,
+        v1::GetClipRequest,
+        v1::BatchResponse,
+        v1::BatchResult,
+        v1::ClipStatsResponse,
+        v1::UsageResponse,
+        v1::ViewsPerDay,
+        v1::ViewsPerReferrer,
+        v1::ViewsPerAgent,
+        v1::collection::CollectionRequest,
+        v1::collection::CollectionResponse,
+        v1::diff::DiffResponse,
+        diff::Line,
+        diff::Word,
+        diff::Change,
+        crate::Clip,
+        crate::Time,
+        ask::NewClip,
+        ask::UpdateClip,
+        field::ShortCode,
+        field::Visibility,
+        field::Tags,
+        field::ForkedFrom,
+        field::Forks,
+        stats::ClipStats,
+        stats::DailyViews,
+        stats::ReferrerViews,
+        stats::AgentViews,
+        api::NewApiKey,
+        Problem,
+        FieldError,
+    )),
+    modifiers(&ApiKeySecurity, &LegacyApi),
+    tags(
+        (name = "clips", description = "Create, read and update clips."),
+        (name = "collections", description = "Ordered collections of clips that share a link."),
+        (name = "keys", description = "API keys used to authenticate with the API."),
+        (name = "legacy", description = "Deprecated unversioned API, use `/api/v1` instead."),
+    )
+)]
+pub struct ApiDoc;
+
+// registers the `x-api-key` header that the `security` sections of the routes refer to
+struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+            );
+        }
+    }
+}
+
+// marks the operations of the unversioned API as deprecated
+struct LegacyApi;
+
+impl Modify for LegacyApi {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        for (path, item) in openapi.paths.paths.iter_mut() {
+            if path.starts_with(LEGACY_API_BASE) {
+                for operation in item.operations.values_mut() {
+                    operation.deprecated = Some(Deprecated::True);
+                }
+            }
+        }
+    }
+}
+
+#[rocket::get("/openapi.json")]
+pub fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![openapi_json]
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::collections::BTreeSet;
+
+    use rocket::http::Status;
+    use utoipa::OpenApi;
+
+    use super::ApiDoc;
+    use crate::test::new_async_runtime;
+    use crate::web::test::new_rocket_client;
+
+    #[test]
+    fn openapi_document_is_served() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+
+        let response = client.get("/api/openapi.json").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let spec: serde_json::Value = response.into_json().unwrap();
+        assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+        assert!(spec["paths"]["/api/clip/{shortcode}"]["get"].is_object());
+        assert!(spec["components"]["securitySchemes"]["api_key"].is_object());
+    }
+
+    // fails when an API route is added, removed or moved without updating `ApiDoc`
+    #[test]
+    fn openapi_document_matches_the_routes() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+
+        let mounted = client
+            .rocket()
+            .routes()
+            .map(|route| {
+                // rocket writes dynamic segments as `<name>`, openapi as `{name}`
+                let path = route.uri.path().replace('<', "{").replace('>', "}");
+                (route.method.as_str().to_lowercase(), path)
+            })
+            .filter(|(_, path)| path.starts_with("/api/") && path != "/api/openapi.json")
+            .collect::<BTreeSet<_>>();
+
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let documented = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, item)| {
+                item.as_object()
+                    .unwrap()
+                    .keys()
+                    .map(move |method| (method.to_owned(), path.to_owned()))
+            })
+            .collect::<BTreeSet<_>>();
+
+        assert_eq!(mounted, documented);
+    }
+
+    fn collect_refs(value: &serde_json::Value, refs: &mut BTreeSet<String>) {
+        match value {
+            serde_json::Value::Object(map) => {
+                if let Some(serde_json::Value::String(reference)) = map.get("$ref") {
+                    refs.insert(reference.to_owned());
+                }
+                map.values().for_each(|value| collect_refs(value, refs));
+            }
+            serde_json::Value::Array(values) => {
+                values.iter().for_each(|value| collect_refs(value, refs));
+            }
+            _ => (),
+        }
+    }
+
+    // fails when a type used by a route is missing from the `components` of `ApiDoc`
+    #[test]
+    fn openapi_schemas_are_registered() {
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+
+        let mut refs = BTreeSet::new();
+        collect_refs(&spec, &mut refs);
+        assert!(!refs.is_empty());
+
+        for reference in refs {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected reference {}", reference));
+            assert!(
+                spec["components"]["schemas"][name].is_object(),
+                "schema {} is not registered",
+                name
+            );
+        }
+    }
+}
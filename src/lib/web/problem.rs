@@ -0,0 +1,120 @@
+use rocket::http::{ContentType, Status};
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{Request, Response};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::trace::RequestId;
+use crate::domain::report::ReportError;
+use crate::domain::secret::Finding;
+use crate::ClipError;
+
+// FieldError points at the request field that failed validation
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl From<&ClipError> for FieldError {
+    fn from(value: &ClipError) -> Self {
+        Self {
+            field: value.field().to_owned(),
+            message: value.to_string(),
+        }
+    }
+}
+
+impl From<&ReportError> for FieldError {
+    fn from(value: &ReportError) -> Self {
+        Self {
+            field: value.field().to_owned(),
+            message: value.to_string(),
+        }
+    }
+}
+
+impl From<&Finding> for FieldError {
+    fn from(value: &Finding) -> Self {
+        Self {
+            field: "content".to_owned(),
+            message: format!("possible secret: {}", value),
+        }
+    }
+}
+
+// Problem is an RFC 7807 problem document, the body of every API error.
+// `request_id` matches the `X-Request-Id` response header so users can point at the failing
+// request when reporting a problem
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    #[schema(example = "urn:clipstash:problem:not-found")]
+    pub kind: String,
+    pub title: String,
+    pub status: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+    #[serde(default)]
+    pub request_id: String,
+}
+
+impl Problem {
+    pub fn new(status: Status) -> Self {
+        Self {
+            kind: problem_type(status).to_owned(),
+            title: status.reason_lossy().to_owned(),
+            status: status.code,
+            detail: None,
+            errors: vec![],
+            request_id: String::new(),
+        }
+    }
+
+    pub fn with_detail<D: Into<String>>(mut self, detail: D) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn with_errors(mut self, errors: Vec<FieldError>) -> Self {
+        self.errors = errors;
+        self
+    }
+}
+
+// problem types are stable identifiers clients can match on, they don't resolve to a page
+fn problem_type(status: Status) -> &'static str {
+    match status.code {
+        400 => "urn:clipstash:problem:bad-request",
+        401 => "urn:clipstash:problem:unauthorized",
+        403 => "urn:clipstash:problem:forbidden",
+        404 => "urn:clipstash:problem:not-found",
+        409 => "urn:clipstash:problem:conflict",
+        410 => "urn:clipstash:problem:gone",
+        412 => "urn:clipstash:problem:precondition-failed",
+        413 => "urn:clipstash:problem:payload-too-large",
+        422 => "urn:clipstash:problem:validation",
+        428 => "urn:clipstash:problem:precondition-required",
+        451 => "urn:clipstash:problem:unavailable-for-legal-reasons",
+        507 => "urn:clipstash:problem:insufficient-storage",
+        500..=599 => "urn:clipstash:problem:server-error",
+        _ => "about:blank",
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Problem {
+    fn respond_to(mut self, req: &'r Request<'_>) -> response::Result<'static> {
+        let status = Status::from_code(self.status).unwrap_or(Status::InternalServerError);
+        if self.request_id.is_empty() {
+            self.request_id = RequestId::of(req).0;
+        }
+
+        Response::build_from(Json(self).respond_to(req)?)
+            .status(status)
+            .header(ContentType::new("application", "problem+json"))
+            .ok()
+    }
+}
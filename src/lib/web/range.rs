@@ -0,0 +1,215 @@
+use std::convert::Infallible;
+use std::io::Cursor;
+
+use rocket::http::{ContentType, Header, Status};
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::response::{self, Responder, Response};
+
+// ByteRange is a single range of a `Range: bytes=...` header, multiple ranges are not supported
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    // `bytes=10-`, everything from the offset
+    From(u64),
+    // `bytes=10-19`, both ends are inclusive
+    Bounded(u64, u64),
+    // `bytes=-10`, the last bytes
+    Suffix(u64),
+}
+
+impl ByteRange {
+    // headers that can't be parsed are ignored and the whole content is sent, as required by
+    // RFC 9110
+    pub fn parse(header: &str) -> Option<Self> {
+        let range = header.trim().strip_prefix("bytes=")?;
+        if range.contains(',') {
+            return None;
+        }
+
+        let (start, end) = range.split_once('-')?;
+        let (start, end) = (start.trim(), end.trim());
+        match (start.is_empty(), end.is_empty()) {
+            (false, true) => start.parse().ok().map(Self::From),
+            (false, false) => {
+                let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+                (start <= end).then_some(Self::Bounded(start, end))
+            }
+            (true, false) => end.parse().ok().map(Self::Suffix),
+            (true, true) => None,
+        }
+    }
+
+    // the inclusive bounds of the range in content of `len` bytes, `None` when not satisfiable
+    pub fn resolve(&self, len: u64) -> Option<(u64, u64)> {
+        let (start, end) = match *self {
+            Self::From(start) => (start, len.checked_sub(1)?),
+            Self::Bounded(start, end) => (start, end.min(len.checked_sub(1)?)),
+            Self::Suffix(0) => return None,
+            Self::Suffix(suffix) => (len.saturating_sub(suffix), len.checked_sub(1)?),
+        };
+        (start <= end).then_some((start, end))
+    }
+}
+
+// RangeRequest is the `Range` header of a download
+#[derive(Debug, Default)]
+pub struct RangeRequest(pub Option<ByteRange>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RangeRequest {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RangeRequest(
+            req.headers().get_one("Range").and_then(ByteRange::parse),
+        ))
+    }
+}
+
+// Ranged responds with the requested range of `content` or all of it when no range was
+// requested. Clients resume interrupted downloads of large clips with the ranges
+pub struct Ranged {
+    content: Vec<u8>,
+    range: Option<ByteRange>,
+    encoding: Option<&'static str>,
+}
+
+impl Ranged {
+    pub fn new(content: String, range: Option<ByteRange>) -> Self {
+        Self {
+            content: content.into_bytes(),
+            range,
+            encoding: None,
+        }
+    }
+
+    // content compressed with `encoding`, ranges are only served from uncompressed content
+    pub fn encoded(content: Vec<u8>, encoding: &'static str) -> Self {
+        Self {
+            content,
+            range: None,
+            encoding: Some(encoding),
+        }
+    }
+
+    // partial downloads after the first byte continue a download, they are not new views
+    pub fn is_continuation(&self) -> bool {
+        self.range
+            .and_then(|range| range.resolve(self.content.len() as u64))
+            .is_some_and(|(start, _)| start > 0)
+    }
+}
+
+impl<'r> Responder<'r, 'static> for Ranged {
+    fn respond_to(self, _req: &'r Request<'_>) -> response::Result<'static> {
+        let len = self.content.len() as u64;
+        let mut response = Response::build();
+        response
+            .header(ContentType::Plain)
+            .header(Header::new("Accept-Ranges", "bytes"))
+            .header(Header::new("Vary", "Accept-Encoding"));
+        if let Some(encoding) = self.encoding {
+            response.header(Header::new("Content-Encoding", encoding));
+        }
+
+        match self.range.map(|range| range.resolve(len)) {
+            None => response.sized_body(self.content.len(), Cursor::new(self.content)),
+            Some(Some((start, end))) => {
+                let body = self.content[start as usize..=end as usize].to_vec();
+                response
+                    .status(Status::PartialContent)
+                    .header(Header::new(
+                        "Content-Range",
+                        format!("bytes {}-{}/{}", start, end, len),
+                    ))
+                    .sized_body(body.len(), Cursor::new(body))
+            }
+            Some(None) => response
+                .status(Status::RangeNotSatisfiable)
+                .header(Header::new("Content-Range", format!("bytes */{}", len))),
+        };
+        response.ok()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use rocket::http::{ContentType, Header, Status};
+
+    use super::ByteRange;
+    use crate::test::new_async_runtime;
+    use crate::web::api::test::new_api_key;
+    use crate::web::api::v1::ClipResponse;
+    use crate::web::test::new_rocket_client;
+
+    #[test]
+    fn parse_byte_ranges() {
+        assert_eq!(
+            ByteRange::parse("bytes=0-9"),
+            Some(ByteRange::Bounded(0, 9))
+        );
+        assert_eq!(ByteRange::parse("bytes=10-"), Some(ByteRange::From(10)));
+        assert_eq!(ByteRange::parse("bytes=-5"), Some(ByteRange::Suffix(5)));
+        assert_eq!(ByteRange::parse("bytes=5-1"), None);
+        assert_eq!(ByteRange::parse("bytes=0-1, 4-5"), None);
+        assert_eq!(ByteRange::parse("lines=0-1"), None);
+
+        assert_eq!(ByteRange::Bounded(5, 100).resolve(10), Some((5, 9)));
+        assert_eq!(ByteRange::Suffix(20).resolve(10), Some((0, 9)));
+        assert_eq!(ByteRange::From(10).resolve(10), None);
+        assert_eq!(ByteRange::From(0).resolve(0), None);
+    }
+
+    #[test]
+    fn raw_clips_are_downloaded_in_ranges() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+
+        let response = client
+            .post("/api/v1/clips/raw")
+            .header(new_api_key(&client))
+            .header(ContentType::Plain)
+            .body("0123456789")
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        let clip: ClipResponse = response.into_json().unwrap();
+        let uri = format!("/clip/raw/{}", clip.shortcode);
+
+        let response = client
+            .get(uri.as_str())
+            .header(Header::new("Range", "bytes=2-4"))
+            .dispatch();
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(
+            response.headers().get_one("Content-Range"),
+            Some("bytes 2-4/10")
+        );
+        let etag = response.headers().get_one("ETag").unwrap().to_owned();
+        assert_eq!(response.into_string().unwrap(), "234");
+
+        // a range of an outdated version returns the whole clip
+        let response = client
+            .get(uri.as_str())
+            .header(Header::new("Range", "bytes=-3"))
+            .header(Header::new("If-Range", "\"outdated\""))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.into_string().unwrap(), "0123456789");
+
+        let response = client
+            .get(uri.as_str())
+            .header(Header::new("Range", "bytes=-3"))
+            .header(Header::new("If-Range", etag))
+            .dispatch();
+        assert_eq!(response.into_string().unwrap(), "789");
+
+        let response = client
+            .get(uri.as_str())
+            .header(Header::new("Range", "bytes=20-"))
+            .dispatch();
+        assert_eq!(response.status(), Status::RangeNotSatisfiable);
+        assert_eq!(
+            response.headers().get_one("Content-Range"),
+            Some("bytes */10")
+        );
+    }
+}
@@ -0,0 +1,194 @@
+use std::convert::Infallible;
+use std::time::Instant;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::request::{FromRequest, Outcome, Request};
+use rocket::route::{self, Handler};
+use rocket::{Data, Response, Route};
+use tracing::{field, Instrument, Span};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+// RequestId identifies a request in the logs and in the responses. It is taken from the
+// `X-Request-Id` header when the client sends a sane one, otherwise a new one is generated
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    fn from_header(value: Option<&str>) -> Self {
+        match value {
+            Some(id)
+                if !id.is_empty()
+                    && id.len() <= 64
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+            {
+                Self(id.to_owned())
+            }
+            _ => Self(uuid::Uuid::new_v4().to_string()),
+        }
+    }
+
+    // the id of the request, also available to catchers and responders
+    pub fn of(req: &Request<'_>) -> Self {
+        req.local_cache(|| RequestId::from_header(req.headers().get_one(REQUEST_ID_HEADER)))
+            .clone()
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for RequestId {
+    type Error = Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(RequestId::of(req))
+    }
+}
+
+// the span of a request and the time it was received, cached on the request by the fairing
+struct RequestSpan {
+    span: Span,
+    started: Instant,
+}
+
+impl RequestSpan {
+    // requests the fairing didn't see, e.g. in tests without it, have no span
+    fn of<'r>(req: &'r Request<'_>) -> &'r Self {
+        req.local_cache(|| RequestSpan {
+            span: Span::none(),
+            started: Instant::now(),
+        })
+    }
+}
+
+// TracedHandler runs the handler of a route, guards included, within the span of the request
+// so everything it logs carries the request id
+#[derive(Clone)]
+struct TracedHandler(Box<dyn Handler>);
+
+#[rocket::async_trait]
+impl Handler for TracedHandler {
+    async fn handle<'r>(&self, req: &'r Request<'_>, data: Data<'r>) -> route::Outcome<'r> {
+        let span = RequestSpan::of(req).span.clone();
+        self.0.handle(req, data).instrument(span).await
+    }
+}
+
+// wraps the handlers of the routes, a fairing only sees a request before and after its handler
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(TracedHandler(route.handler));
+            route
+        })
+        .collect()
+}
+
+// RequestTracing is a fairing that opens a span for every request and logs its outcome
+pub struct RequestTracing;
+
+#[rocket::async_trait]
+impl Fairing for RequestTracing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request Tracing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _: &mut Data<'_>) {
+        let RequestId(id) = RequestId::of(req);
+        let span = tracing::info_span!(
+            "request",
+            request_id = %id,
+            method = %req.method(),
+            uri = %req.uri(),
+            route = field::Empty,
+            status = field::Empty,
+            latency_ms = field::Empty,
+        );
+
+        req.local_cache(|| RequestSpan {
+            span,
+            started: Instant::now(),
+        });
+    }
+
+    async fn on_response<'r>(&self, req: &'r Request<'_>, res: &mut Response<'r>) {
+        let RequestId(id) = RequestId::of(req);
+        res.set_header(Header::new(REQUEST_ID_HEADER, id));
+
+        let RequestSpan { span, started } = RequestSpan::of(req);
+        let status = res.status().code;
+
+        if let Some(route) = req.route() {
+            span.record("route", route.uri.as_str());
+        }
+        span.record("status", status);
+        span.record("latency_ms", started.elapsed().as_secs_f64() * 1000.0);
+
+        span.in_scope(|| {
+            if status >= 500 {
+                tracing::error!("request failed");
+            } else {
+                tracing::info!("request completed");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use rocket::http::Header;
+
+    use super::REQUEST_ID_HEADER;
+    use crate::test::new_async_runtime;
+    use crate::web::test::new_rocket_client;
+
+    // collects the formatted events of the test
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn handler_events_carry_the_request_id() {
+        let rt = new_async_runtime();
+        let client = new_rocket_client(rt.handle());
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            client
+                .post("/api/v1/keys")
+                .header(Header::new(REQUEST_ID_HEADER, "trace-test"))
+                .dispatch();
+        });
+
+        let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let event = logs
+            .lines()
+            .find(|line| line.contains("api key generated"))
+            .expect("the handler logged nothing");
+        assert!(event.contains("request_id=trace-test"), "{}", event);
+    }
+}